@@ -0,0 +1,311 @@
+//! This module provides the checksum algorithms used by archive formats
+
+/// The CRC-32 lookup table, for the reversed polynomial 0xedb88320
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+/// The lookup table of the CRC-32 used by BZIP2, for the polynomial 0x04c11db7
+/// processed most significant bit first
+const CRC32_BZIP2_TABLE: [u32; 256] = crc32_bzip2_table();
+
+/// The CRC-64 lookup table, for the reversed polynomial 0xc96c5795d7870f42
+/// (ECMA-182)
+const CRC64_TABLE: [u64; 256] = crc64_table();
+
+/// Build the CRC-32 lookup table at compile time
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut value = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            if value & 1 == 1 {
+                value = (value >> 1) ^ 0xedb88320;
+            } else {
+                value >>= 1;
+            }
+            bit += 1;
+        }
+        table[i] = value;
+        i += 1;
+    }
+    table
+}
+
+/// Build the lookup table of the CRC-32 used by BZIP2 at compile time
+const fn crc32_bzip2_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut value = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            if value & 0x80000000 != 0 {
+                value = (value << 1) ^ 0x04c11db7;
+            } else {
+                value <<= 1;
+            }
+            bit += 1;
+        }
+        table[i] = value;
+        i += 1;
+    }
+    table
+}
+
+/// Build the CRC-64 lookup table at compile time
+const fn crc64_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut value = i as u64;
+        let mut bit = 0;
+        while bit < 8 {
+            if value & 1 == 1 {
+                value = (value >> 1) ^ 0xc96c5795d7870f42;
+            } else {
+                value >>= 1;
+            }
+            bit += 1;
+        }
+        table[i] = value;
+        i += 1;
+    }
+    table
+}
+
+/// Update a CRC-32 register with one byte.
+/// The register is used as is, without the initial and final inversions.
+pub fn crc32_update_byte(register: u32, byte: u8) -> u32 {
+    CRC32_TABLE[((register ^ byte as u32) & 0xff) as usize] ^ (register >> 8)
+}
+
+/// Computes a CRC-32 incrementally, for data read in several chunks
+pub struct Crc32 {
+    /// The current value of the register
+    register: u32,
+}
+
+impl Crc32 {
+    /// Create a new CRC-32 computation
+    pub fn new() -> Crc32 {
+        Crc32 {
+            register: 0xffffffff,
+        }
+    }
+
+    /// Add data to the computation
+    pub fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.register = crc32_update_byte(self.register, *byte);
+        }
+    }
+
+    /// Get the CRC-32 of the data added so far
+    pub fn value(&self) -> u32 {
+        !self.register
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Computes the CRC-32 (as used by ZIP, gzip and PNG) of some data
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.value()
+}
+
+/// Computes the CRC-32 used by BZIP2 incrementally.
+/// It uses the same polynomial as the CRC-32 of ZIP, but processes the bits
+/// most significant first.
+pub struct Crc32Bzip2 {
+    /// The current value of the register
+    register: u32,
+}
+
+impl Crc32Bzip2 {
+    /// Create a new CRC-32 computation
+    pub fn new() -> Crc32Bzip2 {
+        Crc32Bzip2 {
+            register: 0xffffffff,
+        }
+    }
+
+    /// Add data to the computation
+    pub fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.register = CRC32_BZIP2_TABLE[((self.register >> 24) ^ *byte as u32) as usize] ^ (self.register << 8);
+        }
+    }
+
+    /// Get the CRC-32 of the data added so far
+    pub fn value(&self) -> u32 {
+        !self.register
+    }
+}
+
+impl Default for Crc32Bzip2 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Computes the CRC-64 used by XZ incrementally
+pub struct Crc64 {
+    /// The current value of the register
+    register: u64,
+}
+
+impl Crc64 {
+    /// Create a new CRC-64 computation
+    pub fn new() -> Crc64 {
+        Crc64 {
+            register: 0xffffffffffffffff,
+        }
+    }
+
+    /// Add data to the computation
+    pub fn update(&mut self, data: &[u8]) {
+        for byte in data {
+            self.register = CRC64_TABLE[((self.register ^ *byte as u64) & 0xff) as usize] ^ (self.register >> 8);
+        }
+    }
+
+    /// Get the CRC-64 of the data added so far
+    pub fn value(&self) -> u64 {
+        !self.register
+    }
+}
+
+impl Default for Crc64 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The primes used by XXH64
+const XXH64_PRIMES: [u64; 5] = [
+    0x9e3779b185ebca87,
+    0xc2b2ae3d27d4eb4f,
+    0x165667b19e3779f9,
+    0x85ebca77c2b2ae63,
+    0x27d4eb2f165667c5,
+];
+
+/// Computes the XXH64 hash, used by Zstandard, incrementally.
+/// Specification: https://github.com/Cyan4973/xxHash/blob/dev/doc/xxhash_spec.md
+pub struct Xxh64 {
+    /// The seed, used when the data is shorter than a stripe
+    seed: u64,
+    /// The four accumulators, updated with each stripe of 32 bytes
+    accumulators: [u64; 4],
+    /// The bytes not processed yet, less than a stripe
+    buffer: Vec<u8>,
+    /// The number of bytes added so far
+    length: u64,
+}
+
+impl Xxh64 {
+    /// Create a new XXH64 computation with a seed
+    pub fn new(seed: u64) -> Xxh64 {
+        Xxh64 {
+            seed,
+            accumulators: [
+                seed.wrapping_add(XXH64_PRIMES[0]).wrapping_add(XXH64_PRIMES[1]),
+                seed.wrapping_add(XXH64_PRIMES[1]),
+                seed,
+                seed.wrapping_sub(XXH64_PRIMES[0]),
+            ],
+            buffer: Vec::with_capacity(32),
+            length: 0,
+        }
+    }
+
+    /// Add data to the computation
+    pub fn update(&mut self, data: &[u8]) {
+        self.length += data.len() as u64;
+        let mut data = data;
+
+        // Complete the stripe started by the previous call
+        if !self.buffer.is_empty() {
+            let missing = (32 - self.buffer.len()).min(data.len());
+            self.buffer.extend_from_slice(&data[..missing]);
+            data = &data[missing..];
+            if self.buffer.len() < 32 {
+                return;
+            }
+            let stripe = std::mem::take(&mut self.buffer);
+            self.process_stripe(&stripe);
+        }
+
+        let mut stripes = data.chunks_exact(32);
+        for stripe in &mut stripes {
+            self.process_stripe(stripe);
+        }
+        self.buffer.extend_from_slice(stripes.remainder());
+    }
+
+    /// Get the hash of the data added so far
+    pub fn value(&self) -> u64 {
+        let mut hash = if self.length >= 32 {
+            let [a, b, c, d] = self.accumulators;
+            let mut hash = a.rotate_left(1)
+                .wrapping_add(b.rotate_left(7))
+                .wrapping_add(c.rotate_left(12))
+                .wrapping_add(d.rotate_left(18));
+            for accumulator in self.accumulators {
+                hash ^= xxh64_round(0, accumulator);
+                hash = hash.wrapping_mul(XXH64_PRIMES[0]).wrapping_add(XXH64_PRIMES[3]);
+            }
+            hash
+        } else {
+            self.seed.wrapping_add(XXH64_PRIMES[4])
+        };
+        hash = hash.wrapping_add(self.length);
+
+        let mut remaining = &self.buffer[..];
+        while remaining.len() >= 8 {
+            let lane = u64::from_le_bytes(remaining[..8].try_into().unwrap());
+            hash ^= xxh64_round(0, lane);
+            hash = hash.rotate_left(27).wrapping_mul(XXH64_PRIMES[0]).wrapping_add(XXH64_PRIMES[3]);
+            remaining = &remaining[8..];
+        }
+        if remaining.len() >= 4 {
+            let lane = u32::from_le_bytes(remaining[..4].try_into().unwrap()) as u64;
+            hash ^= lane.wrapping_mul(XXH64_PRIMES[0]);
+            hash = hash.rotate_left(23).wrapping_mul(XXH64_PRIMES[1]).wrapping_add(XXH64_PRIMES[2]);
+            remaining = &remaining[4..];
+        }
+        for byte in remaining {
+            hash ^= (*byte as u64).wrapping_mul(XXH64_PRIMES[4]);
+            hash = hash.rotate_left(11).wrapping_mul(XXH64_PRIMES[0]);
+        }
+
+        // Avalanche
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(XXH64_PRIMES[1]);
+        hash ^= hash >> 29;
+        hash = hash.wrapping_mul(XXH64_PRIMES[2]);
+        hash ^ (hash >> 32)
+    }
+
+    /// Update the accumulators with a stripe of 32 bytes
+    fn process_stripe(&mut self, stripe: &[u8]) {
+        for (accumulator, lane) in self.accumulators.iter_mut().zip(stripe.chunks_exact(8)) {
+            *accumulator = xxh64_round(*accumulator, u64::from_le_bytes(lane.try_into().unwrap()));
+        }
+    }
+}
+
+/// Mix a lane of 8 bytes into an accumulator
+fn xxh64_round(accumulator: u64, lane: u64) -> u64 {
+    accumulator
+        .wrapping_add(lane.wrapping_mul(XXH64_PRIMES[1]))
+        .rotate_left(31)
+        .wrapping_mul(XXH64_PRIMES[0])
+}
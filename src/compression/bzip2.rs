@@ -0,0 +1,505 @@
+//! This module implements the BZIP2 decompression algorithm.
+//! There is no formal specification, the format is described by the reference
+//! implementation at https://sourceware.org/bzip2/
+//! A stream is made of blocks, each compressed with a Burrows-Wheeler
+//! transform, a move-to-front transform, run-length encoding and Huffman
+//! coding. Concatenated streams, as produced by parallel compressors, are
+//! decompressed as a single one.
+
+use std::io::{Read, Write};
+use crate::checksum::Crc32Bzip2;
+use super::DecompressionSummary;
+
+/// The magic number at the start of a block, the digits of pi
+const BLOCK_MAGIC: u64 = 0x314159265359;
+
+/// The magic number at the end of a stream, the digits of the square root of pi
+const END_OF_STREAM_MAGIC: u64 = 0x177245385090;
+
+/// The number of symbols decoded with the same Huffman table
+const GROUP_SIZE: usize = 50;
+
+/// The maximum length of a Huffman code
+const MAX_CODE_LENGTH: u32 = 20;
+
+/// The run-length symbols encode the length of a run of the first byte of the
+/// move-to-front list, in a bijective base 2 where RUN_A is the digit 1...
+const RUN_A: u16 = 0;
+
+/// ... and RUN_B the digit 2
+const RUN_B: u16 = 1;
+
+/// Decompress a BZIP2 stream read from input, and write the decompressed data
+/// to output.
+/// The CRC-32 of each block and of the whole stream are checked.
+/// The input may contain data after the end of the stream: the number of bytes
+/// belonging to the stream is returned. Since the input is read by chunks, more
+/// bytes than that may have been read from it.
+pub fn decompress<R: Read, W: Write>(input: R, output: &mut W) -> Result<DecompressionSummary, String> {
+    let mut reader = BitReader::new(input);
+    let mut produced = 0;
+    let mut block_number = 0;
+
+    loop {
+        let max_block_size = read_stream_header(&mut reader)?;
+        let mut stream_crc: u32 = 0;
+        loop {
+            let magic = reader.read_bits_u64(48)?;
+            if magic == END_OF_STREAM_MAGIC {
+                break;
+            }
+            if magic != BLOCK_MAGIC {
+                return Err(format!("invalid block signature {:#x} at byte {}", magic, reader.consumed()));
+            }
+
+            block_number += 1;
+            let block = decompress_block(&mut reader, max_block_size)
+                .map_err(|e| format!("block {}: {}", block_number, e))?;
+            stream_crc = stream_crc.rotate_left(1) ^ block.crc;
+            output.write_all(&block.data)
+                .map_err(|e| format!("unable to write the decompressed data: {}", e))?;
+            produced += block.data.len() as u64;
+        }
+
+        let stored_crc = reader.read_bits(32)?;
+        if stored_crc != stream_crc {
+            return Err(format!("stream CRC-32 mismatch: stored {:#010x}, computed {:#010x}", stored_crc, stream_crc));
+        }
+        reader.align_to_byte();
+
+        // Another stream may follow
+        if !reader.next_bytes_are(b"BZh")? {
+            break;
+        }
+    }
+
+    output.flush()
+        .map_err(|e| format!("unable to write the decompressed data: {}", e))?;
+    Ok(DecompressionSummary {
+        consumed: reader.consumed(),
+        produced,
+    })
+}
+
+/// Read the header of a stream, and return the maximum size of its blocks
+/// before the first run-length encoding
+fn read_stream_header<R: Read>(reader: &mut BitReader<R>) -> Result<usize, String> {
+    let signature = reader.read_bits(24)?;
+    if signature != 0x425a68 {
+        return Err(format!("invalid signature {:#08x}, expected \"BZh\"", signature));
+    }
+    let level = reader.read_bits(8)? as u8;
+    if !(b'1'..=b'9').contains(&level) {
+        return Err(format!("invalid block size {:#04x}", level));
+    }
+    Ok((level - b'0') as usize * 100000)
+}
+
+/// A decompressed block, and its CRC-32
+struct Block {
+    /// The decompressed data
+    data: Vec<u8>,
+    /// The CRC-32 of the data, checked against the one stored
+    crc: u32,
+}
+
+/// Decompress a block, after its magic number
+fn decompress_block<R: Read>(reader: &mut BitReader<R>, max_block_size: usize) -> Result<Block, String> {
+    let stored_crc = reader.read_bits(32)?;
+    if reader.read_bits(1)? == 1 {
+        return Err(format!("randomized blocks are not supported, at byte {}", reader.consumed()));
+    }
+    let original_pointer = reader.read_bits(24)? as usize;
+
+    // The bytes used in the block, as a bitmap of 16 ranges of 16 bytes
+    let mut used_bytes = Vec::new();
+    let used_ranges = reader.read_bits(16)?;
+    for range in 0..16 {
+        if used_ranges & (0x8000 >> range) == 0 {
+            continue;
+        }
+        let used = reader.read_bits(16)?;
+        for byte in 0..16 {
+            if used & (0x8000 >> byte) != 0 {
+                used_bytes.push((range * 16 + byte) as u8);
+            }
+        }
+    }
+    if used_bytes.is_empty() {
+        return Err(format!("no byte used at byte {}", reader.consumed()));
+    }
+    // The symbols are the run-length symbols, the move-to-front indices 1 to
+    // 255 and the end of block
+    let alphabet_size = used_bytes.len() + 2;
+    let end_of_block = (alphabet_size - 1) as u16;
+
+    let table_count = reader.read_bits(3)? as usize;
+    if !(2..=6).contains(&table_count) {
+        return Err(format!("invalid number of Huffman tables {} at byte {}", table_count, reader.consumed()));
+    }
+    let selector_count = reader.read_bits(15)? as usize;
+    if selector_count == 0 {
+        return Err(format!("no Huffman table selector at byte {}", reader.consumed()));
+    }
+
+    // The selectors are encoded with a move-to-front transform, then in unary
+    let mut table_order: Vec<u8> = (0..table_count as u8).collect();
+    let mut selectors = Vec::with_capacity(selector_count);
+    for _ in 0..selector_count {
+        let mut index = 0;
+        while reader.read_bits(1)? == 1 {
+            index += 1;
+            if index >= table_count {
+                return Err(format!("invalid Huffman table selector at byte {}", reader.consumed()));
+            }
+        }
+        let table = table_order.remove(index);
+        table_order.insert(0, table);
+        selectors.push(table);
+    }
+
+    // The code lengths are delta encoded
+    let mut tables = Vec::with_capacity(table_count);
+    for _ in 0..table_count {
+        let mut lengths = vec![0u8; alphabet_size];
+        let mut length = reader.read_bits(5)?;
+        for symbol_length in lengths.iter_mut() {
+            loop {
+                if !(1..=MAX_CODE_LENGTH).contains(&length) {
+                    return Err(format!("invalid Huffman code length {} at byte {}", length, reader.consumed()));
+                }
+                if reader.read_bits(1)? == 0 {
+                    break;
+                }
+                if reader.read_bits(1)? == 0 {
+                    length += 1;
+                } else {
+                    length -= 1;
+                }
+            }
+            *symbol_length = length as u8;
+        }
+        tables.push(Huffman::new(&lengths));
+    }
+
+    // Decode the symbols, undo the second run-length encoding and the
+    // move-to-front transform
+    let mut move_to_front: Vec<u8> = used_bytes.clone();
+    let mut transformed = Vec::with_capacity(max_block_size);
+    let mut run_length = 0;
+    let mut run_weight = 1;
+    let mut symbol_index = 0;
+    loop {
+        let selector = symbol_index / GROUP_SIZE;
+        if selector >= selectors.len() {
+            return Err(format!("more symbols than announced by the selectors at byte {}", reader.consumed()));
+        }
+        let symbol = tables[selectors[selector] as usize].decode(reader)?;
+        symbol_index += 1;
+
+        if symbol == RUN_A || symbol == RUN_B {
+            run_length += run_weight << symbol;
+            run_weight <<= 1;
+            if run_length > max_block_size {
+                return Err(format!("run longer than the block size at byte {}", reader.consumed()));
+            }
+            continue;
+        }
+        if run_length > 0 {
+            if transformed.len() + run_length > max_block_size {
+                return Err(format!("block larger than {} bytes at byte {}", max_block_size, reader.consumed()));
+            }
+            transformed.resize(transformed.len() + run_length, move_to_front[0]);
+            run_length = 0;
+            run_weight = 1;
+        }
+        if symbol == end_of_block {
+            break;
+        }
+
+        let byte = move_to_front.remove((symbol - 1) as usize);
+        move_to_front.insert(0, byte);
+        if transformed.len() == max_block_size {
+            return Err(format!("block larger than {} bytes at byte {}", max_block_size, reader.consumed()));
+        }
+        transformed.push(byte);
+    }
+
+    if original_pointer >= transformed.len() {
+        return Err(format!("original pointer {} out of the block of {} bytes", original_pointer, transformed.len()));
+    }
+    let data = run_length_decode(&inverse_burrows_wheeler(&transformed, original_pointer));
+
+    let mut crc = Crc32Bzip2::new();
+    crc.update(&data);
+    if crc.value() != stored_crc {
+        return Err(format!("CRC-32 mismatch: stored {:#010x}, computed {:#010x}", stored_crc, crc.value()));
+    }
+    Ok(Block {
+        data,
+        crc: stored_crc,
+    })
+}
+
+/// Undo the Burrows-Wheeler transform, original_pointer being the position of
+/// the original data among the sorted rotations
+fn inverse_burrows_wheeler(transformed: &[u8], original_pointer: usize) -> Vec<u8> {
+    // The position of the first occurrence of each byte in the sorted data
+    let mut first_positions = [0usize; 256];
+    let mut counts = [0usize; 256];
+    for byte in transformed {
+        counts[*byte as usize] += 1;
+    }
+    let mut sum = 0;
+    for (first_position, count) in first_positions.iter_mut().zip(counts) {
+        *first_position = sum;
+        sum += count;
+    }
+
+    // For each position in the sorted data, the position of the next byte
+    let mut next = vec![0u32; transformed.len()];
+    for (index, byte) in transformed.iter().enumerate() {
+        next[first_positions[*byte as usize]] = index as u32;
+        first_positions[*byte as usize] += 1;
+    }
+
+    let mut data = Vec::with_capacity(transformed.len());
+    let mut position = next[original_pointer] as usize;
+    for _ in 0..transformed.len() {
+        data.push(transformed[position]);
+        position = next[position] as usize;
+    }
+    data
+}
+
+/// Undo the first run-length encoding: 4 identical bytes are followed by
+/// the number of additional repetitions
+fn run_length_decode(data: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(data.len());
+    let mut previous = None;
+    let mut identical = 0;
+    for byte in data {
+        if identical == 4 {
+            decoded.resize(decoded.len() + *byte as usize, previous.unwrap_or(0));
+            identical = 0;
+            previous = None;
+            continue;
+        }
+        if previous == Some(*byte) {
+            identical += 1;
+        } else {
+            identical = 1;
+            previous = Some(*byte);
+        }
+        decoded.push(*byte);
+    }
+    decoded
+}
+
+/// A canonical Huffman code, decoded bit by bit
+struct Huffman {
+    /// The symbols, sorted by code length then by value
+    symbols: Vec<u16>,
+    /// For each code length, the last code of this length, or -1
+    limits: [i64; MAX_CODE_LENGTH as usize + 1],
+    /// For each code length, the first code minus the index of its symbol
+    /// in symbols
+    offsets: [i64; MAX_CODE_LENGTH as usize + 1],
+    /// The length of the shortest code
+    min_length: u32,
+}
+
+impl Huffman {
+    /// Build a Huffman code from the code length of each symbol, between 1
+    /// and MAX_CODE_LENGTH
+    fn new(lengths: &[u8]) -> Huffman {
+        let min_length = *lengths.iter().min().unwrap_or(&1) as u32;
+        let mut symbols = Vec::with_capacity(lengths.len());
+        let mut limits = [-1i64; MAX_CODE_LENGTH as usize + 1];
+        let mut offsets = [0i64; MAX_CODE_LENGTH as usize + 1];
+
+        let mut code: i64 = 0;
+        for length in 1..=MAX_CODE_LENGTH as u8 {
+            offsets[length as usize] = code - symbols.len() as i64;
+            for (symbol, symbol_length) in lengths.iter().enumerate() {
+                if *symbol_length == length {
+                    symbols.push(symbol as u16);
+                    code += 1;
+                }
+            }
+            limits[length as usize] = code - 1;
+            code <<= 1;
+        }
+
+        Huffman {
+            symbols,
+            limits,
+            offsets,
+            min_length,
+        }
+    }
+
+    /// Read the next symbol of the stream
+    fn decode<R: Read>(&self, reader: &mut BitReader<R>) -> Result<u16, String> {
+        let mut length = self.min_length;
+        let mut code = reader.read_bits(length)? as i64;
+        loop {
+            if code <= self.limits[length as usize] {
+                let index = code - self.offsets[length as usize];
+                if index >= 0 && (index as usize) < self.symbols.len() {
+                    return Ok(self.symbols[index as usize]);
+                }
+            }
+            length += 1;
+            if length > MAX_CODE_LENGTH {
+                return Err(format!("invalid Huffman code at byte {}", reader.consumed()));
+            }
+            code = (code << 1) | reader.read_bits(1)? as i64;
+        }
+    }
+}
+
+/// Reads a stream bit by bit, most significant bit first
+struct BitReader<R: Read> {
+    /// The underlying stream
+    input: R,
+    /// The bytes read from the stream and not yet moved to bits
+    buffer: Vec<u8>,
+    /// The position of the next byte in buffer
+    position: usize,
+    /// The number of valid bytes in buffer
+    length: usize,
+    /// The bits available, the next one being the most significant of the
+    /// bit_count lowest bits
+    bits: u64,
+    /// The number of bits available
+    bit_count: u32,
+    /// The number of bytes moved to bits since the start of the stream
+    bytes_used: u64,
+}
+
+impl<R: Read> BitReader<R> {
+    /// Create a BitReader reading the given stream
+    fn new(input: R) -> BitReader<R> {
+        BitReader {
+            input,
+            buffer: vec![0; 8192],
+            position: 0,
+            length: 0,
+            bits: 0,
+            bit_count: 0,
+            bytes_used: 0,
+        }
+    }
+
+    /// Move as many bytes as possible to bits.
+    /// Stops silently at the end of the stream.
+    fn fill(&mut self) -> Result<(), String> {
+        while self.bit_count <= 56 {
+            if self.position == self.length {
+                self.length = self.input.read(&mut self.buffer)
+                    .map_err(|e| format!("unable to read the compressed data: {}", e))?;
+                self.position = 0;
+                if self.length == 0 {
+                    return Ok(());
+                }
+            }
+            self.bits = (self.bits << 8) | self.buffer[self.position] as u64;
+            self.bit_count += 8;
+            self.position += 1;
+            self.bytes_used += 1;
+        }
+        Ok(())
+    }
+
+    /// Read and consume count bits, up to 32
+    fn read_bits(&mut self, count: u32) -> Result<u32, String> {
+        if self.bit_count < count {
+            self.fill()?;
+            if self.bit_count < count {
+                return Err(format!("unexpected end of stream at byte {}", self.consumed()));
+            }
+        }
+        self.bit_count -= count;
+        Ok(((self.bits >> self.bit_count) & ((1 << count) - 1)) as u32)
+    }
+
+    /// Read and consume count bits, up to 64
+    fn read_bits_u64(&mut self, count: u32) -> Result<u64, String> {
+        let high = count.saturating_sub(32);
+        let high_bits = self.read_bits(high)? as u64;
+        Ok((high_bits << (count - high)) | self.read_bits(count - high)? as u64)
+    }
+
+    /// Drop the remaining bits of the current byte
+    fn align_to_byte(&mut self) {
+        self.bit_count -= self.bit_count % 8;
+    }
+
+    /// Check if the next bytes are the expected ones, without consuming them.
+    /// The reader must be aligned to a byte.
+    fn next_bytes_are(&mut self, expected: &[u8]) -> Result<bool, String> {
+        let count = 8 * expected.len() as u32;
+        if self.bit_count < count {
+            self.fill()?;
+            if self.bit_count < count {
+                return Ok(false);
+            }
+        }
+        let bits = (self.bits >> (self.bit_count - count)) & ((1 << count) - 1);
+        let expected = expected.iter().fold(0u64, |bits, byte| (bits << 8) | *byte as u64);
+        Ok(bits == expected)
+    }
+
+    /// The number of bytes consumed since the start of the stream, including
+    /// the partially consumed byte
+    fn consumed(&self) -> u64 {
+        self.bytes_used - (self.bit_count / 8) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::{assert_stops_at_end_of_stream, decompress_all, runs, squares};
+
+    // The fixtures were created with bzip2 1.0.8
+    const SQUARES: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares.bz2"));
+    const RUNS: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/runs.bz2"));
+
+    #[test]
+    fn decompresses_block() {
+        assert_eq!(decompress_all(SQUARES, decompress).unwrap(), squares());
+    }
+
+    #[test]
+    fn decompresses_runs_of_bytes() {
+        assert_eq!(decompress_all(RUNS, decompress).unwrap(), runs());
+    }
+
+    #[test]
+    fn decompresses_several_blocks() {
+        // 300 000 bytes compressed with blocks of 100 000 bytes (bzip2 -1)
+        let data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/digits.bz2"));
+        assert_eq!(decompress_all(data, decompress).unwrap(), b"0123456789".repeat(30000));
+    }
+
+    #[test]
+    fn decompresses_concatenated_streams() {
+        let data = [SQUARES, RUNS].concat();
+        assert_eq!(decompress_all(&data, decompress).unwrap(), [squares(), runs()].concat());
+    }
+
+    #[test]
+    fn counts_only_the_bytes_of_the_stream() {
+        assert_stops_at_end_of_stream(SQUARES, decompress);
+    }
+
+    #[test]
+    fn rejects_damaged_block() {
+        let mut data = SQUARES.to_vec();
+        let middle = data.len() / 2;
+        data[middle] ^= 0x01;
+        assert!(decompress(&data[..], &mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,367 @@
+//! This module implements the DEFLATE decompression algorithm, and its
+//! Deflate64 variant.
+//! Specification: https://www.rfc-editor.org/rfc/rfc1951
+//! Deflate64 isn't formally specified, it differs from DEFLATE by a window of
+//! 64 KiB, a length code 285 with 16 extra bits, and the distance codes 30
+//! and 31.
+
+use std::io::{Read, Write};
+use super::{BitReader, DecompressionSummary, OutputWindow};
+
+/// The base length of the length codes 257 to 285
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+
+/// The number of extra bits of the length codes 257 to 285
+const LENGTH_EXTRA_BITS: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+
+/// The base length of the length codes 257 to 285 in Deflate64
+const DEFLATE64_LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 3];
+
+/// The number of extra bits of the length codes 257 to 285 in Deflate64
+const DEFLATE64_LENGTH_EXTRA_BITS: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 16];
+
+/// The base distance of the distance codes 0 to 31.
+/// The codes 30 and 31 are only valid in Deflate64.
+const DISTANCE_BASE: [u32; 32] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577, 32769, 49153];
+
+/// The number of extra bits of the distance codes 0 to 31
+const DISTANCE_EXTRA_BITS: [u8; 32] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13, 14, 14];
+
+/// The order in which the code lengths of the code length alphabet are stored
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// The parameters that differ between DEFLATE and Deflate64
+struct Format {
+    /// The base length of the length codes 257 to 285
+    length_base: &'static [u16; 29],
+    /// The number of extra bits of the length codes 257 to 285
+    length_extra_bits: &'static [u8; 29],
+    /// The number of valid distance codes
+    distance_codes: usize,
+    /// The size of the window, the maximum distance of a back reference
+    window_size: usize,
+}
+
+/// The parameters of DEFLATE
+const DEFLATE: Format = Format {
+    length_base: &LENGTH_BASE,
+    length_extra_bits: &LENGTH_EXTRA_BITS,
+    distance_codes: 30,
+    window_size: 32768,
+};
+
+/// The parameters of Deflate64
+const DEFLATE64: Format = Format {
+    length_base: &DEFLATE64_LENGTH_BASE,
+    length_extra_bits: &DEFLATE64_LENGTH_EXTRA_BITS,
+    distance_codes: 32,
+    window_size: 65536,
+};
+
+/// Decompress a DEFLATE stream read from input, and write the decompressed
+/// data to output.
+/// The input may contain data after the end of the stream: the number of bytes
+/// belonging to the stream is returned. Since the input is read by chunks, more
+/// bytes than that may have been read from it.
+pub fn inflate<R: Read, W: Write>(input: R, output: &mut W) -> Result<DecompressionSummary, String> {
+    inflate_format(input, output, &DEFLATE)
+}
+
+/// Decompress a Deflate64 stream read from input, and write the decompressed
+/// data to output.
+/// The input is handled as with inflate().
+pub fn inflate64<R: Read, W: Write>(input: R, output: &mut W) -> Result<DecompressionSummary, String> {
+    inflate_format(input, output, &DEFLATE64)
+}
+
+/// Decompress a stream of the given format
+fn inflate_format<R: Read, W: Write>(input: R, output: &mut W, format: &Format) -> Result<DecompressionSummary, String> {
+    let mut reader = BitReader::new(input);
+    let mut window = OutputWindow::new(output, format.window_size);
+
+    loop {
+        let last_block = reader.read_bits(1)? == 1;
+        match reader.read_bits(2)? {
+            0 => inflate_stored_block(&mut reader, &mut window)?,
+            1 => {
+                let (literal_length, distance) = fixed_huffman_codes();
+                inflate_compressed_block(&mut reader, &mut window, &literal_length, &distance, format)?;
+            },
+            2 => {
+                let (literal_length, distance) = read_dynamic_huffman_codes(&mut reader, format)?;
+                inflate_compressed_block(&mut reader, &mut window, &literal_length, &distance, format)?;
+            },
+            _ => return Err(format!("invalid block type 3 at byte {}", reader.consumed())),
+        }
+        if last_block {
+            break;
+        }
+    }
+
+    window.flush_all()?;
+    Ok(DecompressionSummary {
+        consumed: reader.consumed(),
+        produced: window.produced,
+    })
+}
+
+/// Copy a stored (uncompressed) block
+fn inflate_stored_block<R: Read, W: Write>(reader: &mut BitReader<R>, window: &mut OutputWindow<W>) -> Result<(), String> {
+    reader.align_to_byte();
+    let length = reader.read_bits(16)?;
+    let length_complement = reader.read_bits(16)?;
+    if length != !length_complement & 0xffff {
+        return Err(format!("stored block length {} doesn't match its complement at byte {}", length, reader.consumed()));
+    }
+    for _ in 0..length {
+        window.push(reader.read_bits(8)? as u8)?;
+    }
+    Ok(())
+}
+
+/// Decode a block compressed with the given Huffman codes
+fn inflate_compressed_block<R: Read, W: Write>(reader: &mut BitReader<R>, window: &mut OutputWindow<W>, literal_length: &Huffman, distance: &Huffman, format: &Format) -> Result<(), String> {
+    loop {
+        let symbol = literal_length.decode(reader)?;
+        if symbol < 256 {
+            window.push(symbol as u8)?;
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+
+        let index = (symbol - 257) as usize;
+        if index >= format.length_base.len() {
+            return Err(format!("invalid length code {} at byte {}", symbol, reader.consumed()));
+        }
+        let length = format.length_base[index] as usize + reader.read_bits(format.length_extra_bits[index] as u32)? as usize;
+
+        let distance_symbol = distance.decode(reader)? as usize;
+        if distance_symbol >= format.distance_codes {
+            return Err(format!("invalid distance code {} at byte {}", distance_symbol, reader.consumed()));
+        }
+        let distance = DISTANCE_BASE[distance_symbol] as usize + reader.read_bits(DISTANCE_EXTRA_BITS[distance_symbol] as u32)? as usize;
+
+        window.copy(distance, length)
+            .map_err(|e| format!("{} at byte {}", e, reader.consumed()))?;
+    }
+}
+
+/// Build the fixed Huffman codes of block type 1
+fn fixed_huffman_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[0..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..288].fill(8);
+    // Distance codes 30 and 31 are part of the fixed code, but only valid in
+    // Deflate64
+    let distance_lengths = [5u8; 32];
+    (Huffman::new(&lengths).unwrap(), Huffman::new(&distance_lengths).unwrap())
+}
+
+/// Read the Huffman codes at the start of a block of type 2
+fn read_dynamic_huffman_codes<R: Read>(reader: &mut BitReader<R>, format: &Format) -> Result<(Huffman, Huffman), String> {
+    let literal_length_count = reader.read_bits(5)? as usize + 257;
+    let distance_count = reader.read_bits(5)? as usize + 1;
+    let code_length_count = reader.read_bits(4)? as usize + 4;
+    if literal_length_count > 286 || distance_count > format.distance_codes {
+        return Err(format!("invalid number of codes ({} literal/length, {} distance) at byte {}", literal_length_count, distance_count, reader.consumed()));
+    }
+
+    let mut code_length_lengths = [0u8; 19];
+    for index in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_length_lengths[*index] = reader.read_bits(3)? as u8;
+    }
+    let code_length = Huffman::new(&code_length_lengths)
+        .map_err(|e| format!("invalid code length code: {} at byte {}", e, reader.consumed()))?;
+
+    // The code lengths of both codes are stored as a single sequence, a repeat
+    // may cross from one code to the other
+    let mut lengths = vec![0u8; literal_length_count + distance_count];
+    let mut index = 0;
+    while index < lengths.len() {
+        let symbol = code_length.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                if index == 0 {
+                    return Err(format!("repeat of a code length without previous length at byte {}", reader.consumed()));
+                }
+                (lengths[index - 1], 3 + reader.read_bits(2)? as usize)
+            },
+            17 => (0, 3 + reader.read_bits(3)? as usize),
+            _ => (0, 11 + reader.read_bits(7)? as usize),
+        };
+        if index + repeat > lengths.len() {
+            return Err(format!("too many code lengths at byte {}", reader.consumed()));
+        }
+        lengths[index..index + repeat].fill(value);
+        index += repeat;
+    }
+
+    if lengths[256] == 0 {
+        return Err(format!("missing end of block code at byte {}", reader.consumed()));
+    }
+
+    let literal_length = Huffman::new(&lengths[..literal_length_count])
+        .map_err(|e| format!("invalid literal/length code: {} at byte {}", e, reader.consumed()))?;
+    let distance = Huffman::new(&lengths[literal_length_count..])
+        .map_err(|e| format!("invalid distance code: {} at byte {}", e, reader.consumed()))?;
+    Ok((literal_length, distance))
+}
+
+/// A canonical Huffman code, decoded with a lookup table indexed by the next
+/// bits of the stream
+struct Huffman {
+    /// For each possible value of the next max_length bits, the symbol and the
+    /// length of its code, as (symbol << 4) | length. 0 means no code matches.
+    table: Vec<u32>,
+    /// The length of the longest code
+    max_length: u32,
+}
+
+impl Huffman {
+    /// Build a Huffman code from the code length of each symbol.
+    /// Incomplete codes are accepted, the missing codes are reported when
+    /// decoded. Over-subscribed codes are rejected.
+    fn new(lengths: &[u8]) -> Result<Huffman, String> {
+        let mut count = [0u32; 16];
+        for length in lengths {
+            count[*length as usize] += 1;
+        }
+        count[0] = 0;
+
+        let mut left: i32 = 1;
+        for length_count in count.iter().skip(1) {
+            left = (left << 1) - *length_count as i32;
+            if left < 0 {
+                return Err("over-subscribed code".to_string());
+            }
+        }
+
+        let max_length = (1..16).rev().find(|length| count[*length] > 0).unwrap_or(1) as u32;
+        let mut next_code = [0u32; 16];
+        let mut code = 0;
+        for length in 1..16 {
+            code = (code + count[length - 1]) << 1;
+            next_code[length] = code;
+        }
+
+        let mut table = vec![0u32; 1 << max_length];
+        for (symbol, length) in lengths.iter().enumerate() {
+            let length = *length as u32;
+            if length == 0 {
+                continue;
+            }
+            let code = next_code[length as usize];
+            next_code[length as usize] += 1;
+            // Huffman codes are stored starting with their most significant bit
+            let reversed = code.reverse_bits() >> (32 - length);
+            let entry = ((symbol as u32) << 4) | length;
+            let mut index = reversed as usize;
+            while index < table.len() {
+                table[index] = entry;
+                index += 1 << length;
+            }
+        }
+
+        Ok(Huffman {
+            table,
+            max_length,
+        })
+    }
+
+    /// Read the next symbol of the stream
+    fn decode<R: Read>(&self, reader: &mut BitReader<R>) -> Result<u16, String> {
+        let (bits, available) = reader.peek_bits(self.max_length)?;
+        let entry = self.table[bits as usize];
+        let length = entry & 0xf;
+        if entry == 0 {
+            return Err(format!("invalid Huffman code at byte {}", reader.consumed()));
+        }
+        if length > available {
+            return Err(format!("unexpected end of stream at byte {}", reader.consumed()));
+        }
+        reader.consume_bits(length);
+        Ok((entry >> 4) as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::squares;
+
+    // The squares*.deflate fixtures were created with zlib
+
+    /// Decompress a stream of the given format, and check that the whole
+    /// stream was read
+    fn decompress(data: &[u8], format: &Format) -> Result<Vec<u8>, String> {
+        let mut output = Vec::new();
+        let summary = inflate_format(data, &mut output, format)?;
+        assert_eq!(summary.consumed, data.len() as u64);
+        assert_eq!(summary.produced, output.len() as u64);
+        Ok(output)
+    }
+
+    #[test]
+    fn inflates_zlib_dynamic_huffman_blocks() {
+        let data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares.deflate"));
+        assert_eq!(decompress(data, &DEFLATE).unwrap(), squares());
+    }
+
+    #[test]
+    fn inflates_zlib_fixed_huffman_blocks() {
+        let data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares_fixed.deflate"));
+        assert_eq!(decompress(data, &DEFLATE).unwrap(), squares());
+    }
+
+    #[test]
+    fn inflates_zlib_stored_blocks() {
+        let data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares_stored.deflate"));
+        assert_eq!(decompress(data, &DEFLATE).unwrap(), squares());
+    }
+
+    #[test]
+    fn counts_only_the_bytes_of_the_stream() {
+        let mut data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares.deflate")).to_vec();
+        let stream_size = data.len() as u64;
+        data.extend_from_slice(b"trailing data");
+        let mut output = Vec::new();
+        let summary = inflate(&data[..], &mut output).unwrap();
+        assert_eq!(summary.consumed, stream_size);
+        assert_eq!(output, squares());
+    }
+
+    #[test]
+    fn inflates_deflate_stream_without_length_258_as_deflate64() {
+        // Without a match of 258 bytes, a DEFLATE stream is a valid Deflate64 stream
+        let data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares.deflate"));
+        assert_eq!(decompress(data, &DEFLATE64).unwrap(), squares());
+    }
+
+    /// deflate64.bin is a fixed Huffman block assembled by hand, since no
+    /// Deflate64 compressor is freely available: the bytes 0 to 250, a
+    /// match of 65538 bytes (length code 285 and its 16 extra bits) at
+    /// distance 251, then matches of 10 bytes at distances 60000 (code 31)
+    /// and 40000 (code 30)
+    const DEFLATE64_STREAM: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/deflate64.bin"));
+
+    #[test]
+    fn inflates_deflate64_long_length_and_distances() {
+        let mut expected: Vec<u8> = (0..=250).collect();
+        for (length, distance) in [(65538, 251), (10, 60000), (10, 40000)] {
+            for _ in 0..length {
+                expected.push(expected[expected.len() - distance]);
+            }
+        }
+        assert_eq!(decompress(DEFLATE64_STREAM, &DEFLATE64).unwrap(), expected);
+    }
+
+    #[test]
+    fn rejects_deflate64_stream_as_deflate() {
+        assert!(inflate(DEFLATE64_STREAM, &mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+//! This module implements the Implode decompression algorithm, used by PKZIP 1.x.
+//! Specification: APPNOTE.TXT section 5.4
+//! The data is made of literals and back references, coded with 2 or 3
+//! Shannon-Fano trees: literals (optional), lengths and distances. The window
+//! is 4 or 8 KiB.
+
+use std::io::{Read, Write};
+use super::{BitReader, DecompressionSummary, OutputWindow};
+
+/// The size of the largest window
+const WINDOW_SIZE: usize = 8192;
+
+/// The length code followed by 8 bits added to the length
+const MAX_LENGTH_CODE: usize = 63;
+
+/// Decompress an imploded stream read from input, and write the decompressed
+/// data to output.
+/// large_window is set for a window of 8 KiB (bit 1 of the general purpose
+/// flag), literal_tree for 3 trees (bit 2). The stream has no end marker: it
+/// ends when the uncompressed size is reached.
+pub fn decompress<R: Read, W: Write>(input: R, output: &mut W, large_window: bool, literal_tree: bool, uncompressed_size: u64) -> Result<DecompressionSummary, String> {
+    let mut reader = BitReader::new(input);
+    let literals = if literal_tree {
+        Some(ShannonFanoTree::read(&mut reader, 256).map_err(|e| format!("literal tree: {}", e))?)
+    } else {
+        None
+    };
+    let lengths = ShannonFanoTree::read(&mut reader, 64).map_err(|e| format!("length tree: {}", e))?;
+    let distances = ShannonFanoTree::read(&mut reader, 64).map_err(|e| format!("distance tree: {}", e))?;
+    let minimum_length = if literal_tree { 3 } else { 2 };
+    let distance_low_bits = if large_window { 7 } else { 6 };
+
+    let mut window = OutputWindow::new(output, WINDOW_SIZE);
+    while window.produced < uncompressed_size {
+        if reader.read_bits(1)? == 1 {
+            let byte = match &literals {
+                Some(literals) => literals.decode(&mut reader)? as u8,
+                None => reader.read_bits(8)? as u8,
+            };
+            window.push(byte)?;
+        } else {
+            let low_bits = reader.read_bits(distance_low_bits)? as usize;
+            let distance = (distances.decode(&mut reader)? << distance_low_bits | low_bits) + 1;
+            let mut length = lengths.decode(&mut reader)?;
+            if length == MAX_LENGTH_CODE {
+                length += reader.read_bits(8)? as usize;
+            }
+            window.copy_or_zeros(distance, length + minimum_length)?;
+        }
+    }
+
+    window.flush_all()?;
+    Ok(DecompressionSummary {
+        consumed: reader.consumed(),
+        produced: window.produced,
+    })
+}
+
+/// A Shannon-Fano tree, decoding the codes bit by bit
+struct ShannonFanoTree {
+    /// For each code length, the first code and the symbols by increasing code
+    codes: Vec<(usize, Vec<usize>)>,
+}
+
+impl ShannonFanoTree {
+    /// Read a tree of symbol_count symbols.
+    /// The number of bytes of the tree is stored on a byte, minus one. Each
+    /// byte has the code length minus one in its low 4 bits, and the number of
+    /// following symbols with this length minus one in its high 4 bits.
+    fn read<R: Read>(reader: &mut BitReader<R>, symbol_count: usize) -> Result<ShannonFanoTree, String> {
+        let size = reader.read_bits(8)? + 1;
+        let mut lengths = Vec::with_capacity(symbol_count);
+        for _ in 0..size {
+            let byte = reader.read_bits(8)?;
+            let count = (byte >> 4) + 1;
+            lengths.extend(std::iter::repeat_n((byte & 0x0f) as u8 + 1, count as usize));
+        }
+        if lengths.len() != symbol_count {
+            return Err(format!("{} code lengths, {} expected", lengths.len(), symbol_count));
+        }
+        ShannonFanoTree::new(&lengths)
+    }
+
+    /// Create the tree from the code length of each symbol.
+    /// The codes are assigned from the longest to the shortest, then from the
+    /// last symbol to the first.
+    fn new(lengths: &[u8]) -> Result<ShannonFanoTree, String> {
+        let mut symbols: Vec<usize> = (0..lengths.len()).collect();
+        symbols.sort_by_key(|symbol| lengths[*symbol]);
+
+        let mut codes = vec![(0, Vec::new()); 17];
+        // The codes are computed on 16 bits, the code of a symbol being the
+        // high bits
+        let mut code: u32 = 0;
+        let mut increment = 0;
+        let mut last_length = 0;
+        for symbol in symbols.into_iter().rev() {
+            code += increment;
+            let length = lengths[symbol] as usize;
+            if length != last_length {
+                last_length = length;
+                increment = 1 << (16 - length);
+            }
+            if code > 0xffff {
+                return Err("too many short codes".to_string());
+            }
+            // The symbols come by increasing code
+            let (first_code, symbols) = &mut codes[length];
+            if symbols.is_empty() {
+                *first_code = (code >> (16 - length)) as usize;
+            }
+            symbols.push(symbol);
+        }
+        Ok(ShannonFanoTree {
+            codes,
+        })
+    }
+
+    /// Read a code and get its symbol
+    fn decode<R: Read>(&self, reader: &mut BitReader<R>) -> Result<usize, String> {
+        let mut code = 0;
+        for (first_code, symbols) in &self.codes[1..] {
+            code = (code << 1) | reader.read_bits(1)? as usize;
+            if code >= *first_code && code - first_code < symbols.len() {
+                return Ok(symbols[code - first_code]);
+            }
+        }
+        Err(format!("invalid Shannon-Fano code at byte {}", reader.consumed()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::squares;
+
+    // No Implode compressor is freely available: the fixtures were created
+    // with a small encoder written from APPNOTE.TXT, and checked against
+    // Info-ZIP UnZip 6.00
+
+    /// Decompress a stream, and check that the decompression stopped at the
+    /// uncompressed size
+    fn decompress_all(data: &[u8], large_window: bool, literal_tree: bool, uncompressed_size: u64) -> Result<Vec<u8>, String> {
+        let mut output = Vec::new();
+        let summary = decompress(data, &mut output, large_window, literal_tree, uncompressed_size)?;
+        assert_eq!(summary.produced, uncompressed_size);
+        Ok(output)
+    }
+
+    #[test]
+    fn decompresses_4k_window_with_2_trees() {
+        let data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares_4k_2trees.implode"));
+        assert_eq!(decompress_all(data, false, false, squares().len() as u64).unwrap(), squares());
+    }
+
+    #[test]
+    fn decompresses_4k_window_with_3_trees() {
+        let data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares_4k_3trees.implode"));
+        assert_eq!(decompress_all(data, false, true, squares().len() as u64).unwrap(), squares());
+    }
+
+    #[test]
+    fn decompresses_8k_window_with_2_trees() {
+        let data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares_8k_2trees.implode"));
+        assert_eq!(decompress_all(data, true, false, squares().len() as u64).unwrap(), squares());
+    }
+
+    #[test]
+    fn decompresses_8k_window_with_3_trees() {
+        let data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares_8k_3trees.implode"));
+        assert_eq!(decompress_all(data, true, true, squares().len() as u64).unwrap(), squares());
+    }
+
+    #[test]
+    fn decompresses_long_matches() {
+        // 1000 times the same byte, with matches longer than 63 bytes
+        let data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/run.implode"));
+        assert_eq!(decompress_all(data, true, true, 1000).unwrap(), b"a".repeat(1000));
+    }
+}
@@ -0,0 +1,678 @@
+//! This module implements the LZMA and LZMA2 decompression algorithms.
+//! Specification: lzma-specification.txt in the LZMA SDK, https://www.7-zip.org/sdk.html
+//! LZMA2 isn't formally specified, it's described by the reference
+//! implementation in the same SDK. It splits the data in chunks, either
+//! compressed with LZMA or stored, which can reset the state of the decoder.
+
+use std::io::{Read, Write};
+use super::{ByteReader, DecompressionSummary};
+
+/// The number of states of the decoder, depending on the previous packets
+const STATES: usize = 12;
+
+/// The first state reached after a match, states below follow a literal
+const FIRST_MATCH_STATE: usize = 7;
+
+/// The maximum number of position states, 1 << the maximum position bits
+const MAX_POSITION_STATES: usize = 16;
+
+/// The number of distance slot tables, depending on the match length
+const LENGTH_TO_POSITION_STATES: usize = 4;
+
+/// The first distance slot whose low bits are coded with fixed probabilities
+const END_POSITION_MODEL_INDEX: u32 = 14;
+
+/// The number of distances whose low bits are coded with adaptive probabilities
+const FULL_DISTANCES: usize = 128;
+
+/// The number of low bits of large distances coded with the align probabilities
+const ALIGN_BITS: u32 = 4;
+
+/// The length of the shortest match
+const MATCH_MIN_LENGTH: usize = 2;
+
+/// The initial value of the probabilities, 0.5 on 11 bits
+const PROBABILITY_INIT: u16 = 1 << 10;
+
+/// The number of probabilities of a literal coder
+const LITERAL_CODER_SIZE: usize = 0x300;
+
+/// The distance read in place of the end marker
+const END_MARKER_DISTANCE: u32 = 0xffffffff;
+
+/// The minimum number of bytes decompressed between two flushes of the window
+const FLUSH_SIZE: usize = 1 << 20;
+
+/// The properties of a LZMA stream
+pub struct LzmaProperties {
+    /// The number of high bits of the previous byte used as context for literals (lc)
+    pub literal_context_bits: u32,
+    /// The number of low bits of the position used as context for literals (lp)
+    pub literal_position_bits: u32,
+    /// The number of low bits of the position used as context for packets (pb)
+    pub position_bits: u32,
+    /// The size of the dictionary, the maximum distance of a match
+    pub dictionary_size: u32,
+}
+
+impl LzmaProperties {
+    /// Decode the properties byte, (pb * 5 + lp) * 9 + lc
+    pub fn new(properties: u8, dictionary_size: u32) -> Result<LzmaProperties, String> {
+        if properties >= 9 * 5 * 5 {
+            return Err(format!("invalid LZMA properties {:#04x}", properties));
+        }
+        let properties = properties as u32;
+        Ok(LzmaProperties {
+            literal_context_bits: properties % 9,
+            literal_position_bits: (properties / 9) % 5,
+            position_bits: properties / 45,
+            dictionary_size,
+        })
+    }
+}
+
+/// Decompress a LZMA stream read from input, without header, and write the
+/// decompressed data to output.
+/// When uncompressed_size is None, the stream must end with an end marker.
+/// The input may contain data after the end of the stream: the number of bytes
+/// belonging to the stream is returned. Since the input is read by chunks, more
+/// bytes than that may have been read from it.
+pub fn decompress<R: Read, W: Write>(input: R, output: &mut W, properties: &LzmaProperties, uncompressed_size: Option<u64>) -> Result<DecompressionSummary, String> {
+    let mut reader = ByteReader::new(input);
+    let mut window = Window::new(output, properties.dictionary_size);
+    let mut decoder = LzmaDecoder::new(properties);
+
+    let mut range = RangeDecoder::new(&mut reader)?;
+    let end_marker = decoder.decode(&mut range, &mut window, uncompressed_size, true)?;
+    if uncompressed_size.is_none() && !end_marker {
+        return Err("end of stream without end marker".to_string());
+    }
+
+    window.flush_all()?;
+    Ok(DecompressionSummary {
+        consumed: reader.consumed(),
+        produced: window.produced,
+    })
+}
+
+/// Decompress a LZMA2 stream read from input, and write the decompressed data
+/// to output.
+/// The input is handled as with decompress().
+pub fn decompress_lzma2<R: Read, W: Write>(input: R, output: &mut W, dictionary_size: u32) -> Result<DecompressionSummary, String> {
+    let mut reader = ByteReader::new(input);
+    let produced = decode_lzma2(&mut reader, output, dictionary_size)?;
+    Ok(DecompressionSummary {
+        consumed: reader.consumed(),
+        produced,
+    })
+}
+
+/// Decompress the chunks of a LZMA2 stream, up to its end marker, and return
+/// the number of bytes decompressed
+pub(super) fn decode_lzma2<R: Read, W: Write>(reader: &mut ByteReader<R>, output: &mut W, dictionary_size: u32) -> Result<u64, String> {
+    let mut window = Window::new(output, dictionary_size);
+    let mut decoder: Option<LzmaDecoder> = None;
+    let mut need_dictionary_reset = true;
+    let mut need_properties = true;
+
+    loop {
+        let control = reader.read_byte()?;
+        if control == 0x00 {
+            break;
+        }
+
+        // The first chunk, and the chunks following a dictionary reset, must reset the dictionary
+        if control == 0x01 || control >= 0xe0 {
+            window.reset();
+            need_dictionary_reset = false;
+            need_properties = true;
+        } else if need_dictionary_reset {
+            return Err(format!("the first LZMA2 chunk doesn't reset the dictionary at byte {}", reader.consumed()));
+        }
+
+        // Uncompressed chunk
+        if control < 0x80 {
+            if control > 0x02 {
+                return Err(format!("invalid LZMA2 control byte {:#04x} at byte {}", control, reader.consumed()));
+            }
+            let size = reader.read_u16_be()? as usize + 1;
+            for _ in 0..size {
+                window.push(reader.read_byte()?)?;
+            }
+            continue;
+        }
+
+        // LZMA chunk, bits 5 and 6 tell what is reset
+        let uncompressed_size = (((control & 0x1f) as u64) << 16) + reader.read_u16_be()? as u64 + 1;
+        let compressed_size = reader.read_u16_be()? as u64 + 1;
+        if control >= 0xc0 {
+            let properties = reader.read_byte()?;
+            let properties = LzmaProperties::new(properties, dictionary_size)?;
+            if properties.literal_context_bits + properties.literal_position_bits > 4 {
+                return Err(format!("invalid LZMA2 properties at byte {}, lc + lp is greater than 4", reader.consumed()));
+            }
+            decoder = Some(LzmaDecoder::new(&properties));
+            need_properties = false;
+        } else if need_properties {
+            return Err(format!("LZMA2 chunk without properties at byte {}", reader.consumed()));
+        }
+        let decoder = decoder.as_mut().unwrap();
+        if control >= 0xa0 {
+            decoder.reset();
+        }
+
+        let start = reader.consumed();
+        let mut range = RangeDecoder::new(reader)?;
+        decoder.decode(&mut range, &mut window, Some(uncompressed_size), false)?;
+        let used = reader.consumed() - start;
+        if used != compressed_size {
+            return Err(format!("LZMA2 chunk of {} bytes decompressed with {} bytes, at byte {}", compressed_size, used, reader.consumed()));
+        }
+    }
+
+    window.flush_all()?;
+    Ok(window.produced)
+}
+
+/// The state of a LZMA decoder: the probabilities, the state machine and the
+/// last distances used
+struct LzmaDecoder {
+    /// The number of high bits of the previous byte used as context for literals
+    literal_context_bits: u32,
+    /// The number of low bits of the position used as context for literals
+    literal_position_bits: u32,
+    /// The number of low bits of the position used as context for packets
+    position_bits: u32,
+    /// The probabilities of the literal coders
+    literal: Vec<u16>,
+    /// The probabilities that a packet is a match, by state and position state
+    is_match: [u16; STATES * MAX_POSITION_STATES],
+    /// The probabilities that a match is a repeated match
+    is_rep: [u16; STATES],
+    /// The probabilities that a repeated match uses the last distance
+    is_rep_g0: [u16; STATES],
+    /// The probabilities that a repeated match uses the second distance
+    is_rep_g1: [u16; STATES],
+    /// The probabilities that a repeated match uses the third distance
+    is_rep_g2: [u16; STATES],
+    /// The probabilities that a match with the last distance is longer than 1
+    is_rep0_long: [u16; STATES * MAX_POSITION_STATES],
+    /// The probabilities of the distance slots, by length
+    distance_slot: [[u16; 64]; LENGTH_TO_POSITION_STATES],
+    /// The probabilities of the low bits of distances below FULL_DISTANCES
+    distance_special: [u16; 1 + FULL_DISTANCES - END_POSITION_MODEL_INDEX as usize],
+    /// The probabilities of the low bits of larger distances
+    distance_align: [u16; 1 << ALIGN_BITS],
+    /// The decoder of the lengths of matches
+    match_length: LengthDecoder,
+    /// The decoder of the lengths of repeated matches
+    rep_length: LengthDecoder,
+    /// The current state
+    state: usize,
+    /// The last four distances, minus 1
+    reps: [u32; 4],
+}
+
+impl LzmaDecoder {
+    /// Create a decoder with the given properties
+    fn new(properties: &LzmaProperties) -> LzmaDecoder {
+        let literal_coders = 1 << (properties.literal_context_bits + properties.literal_position_bits);
+        LzmaDecoder {
+            literal_context_bits: properties.literal_context_bits,
+            literal_position_bits: properties.literal_position_bits,
+            position_bits: properties.position_bits,
+            literal: vec![PROBABILITY_INIT; LITERAL_CODER_SIZE * literal_coders],
+            is_match: [PROBABILITY_INIT; STATES * MAX_POSITION_STATES],
+            is_rep: [PROBABILITY_INIT; STATES],
+            is_rep_g0: [PROBABILITY_INIT; STATES],
+            is_rep_g1: [PROBABILITY_INIT; STATES],
+            is_rep_g2: [PROBABILITY_INIT; STATES],
+            is_rep0_long: [PROBABILITY_INIT; STATES * MAX_POSITION_STATES],
+            distance_slot: [[PROBABILITY_INIT; 64]; LENGTH_TO_POSITION_STATES],
+            distance_special: [PROBABILITY_INIT; 1 + FULL_DISTANCES - END_POSITION_MODEL_INDEX as usize],
+            distance_align: [PROBABILITY_INIT; 1 << ALIGN_BITS],
+            match_length: LengthDecoder::new(),
+            rep_length: LengthDecoder::new(),
+            state: 0,
+            reps: [0; 4],
+        }
+    }
+
+    /// Reset the probabilities, the state and the distances, keeping the properties
+    fn reset(&mut self) {
+        *self = LzmaDecoder::new(&LzmaProperties {
+            literal_context_bits: self.literal_context_bits,
+            literal_position_bits: self.literal_position_bits,
+            position_bits: self.position_bits,
+            dictionary_size: 0,
+        });
+    }
+
+    /// Decode packets until limit bytes are decompressed or, when there is no
+    /// limit, until the end marker.
+    /// Returns whether the end marker was found.
+    fn decode<R: Read, W: Write>(&mut self, range: &mut RangeDecoder<R>, window: &mut Window<W>, limit: Option<u64>, allow_end_marker: bool) -> Result<bool, String> {
+        let start = window.produced;
+        let position_mask = (1 << self.position_bits) - 1;
+        loop {
+            let remaining = match limit {
+                Some(limit) if window.produced - start >= limit => return Ok(false),
+                Some(limit) => limit - (window.produced - start),
+                None => u64::MAX,
+            };
+            let position_state = window.available as usize & position_mask;
+
+            if range.decode_bit(&mut self.is_match[self.state * MAX_POSITION_STATES + position_state])? == 0 {
+                self.decode_literal(range, window)?;
+                continue;
+            }
+
+            let length;
+            if range.decode_bit(&mut self.is_rep[self.state])? == 1 {
+                if window.available == 0 {
+                    return Err(format!("repeated match before any data at byte {}", range.consumed()));
+                }
+                if range.decode_bit(&mut self.is_rep_g0[self.state])? == 0 {
+                    // A single byte at the last distance
+                    if range.decode_bit(&mut self.is_rep0_long[self.state * MAX_POSITION_STATES + position_state])? == 0 {
+                        self.state = if self.state < FIRST_MATCH_STATE { 9 } else { 11 };
+                        self.check_distance(range, window)?;
+                        let byte = window.byte_at(self.reps[0] as usize + 1);
+                        window.push(byte)?;
+                        continue;
+                    }
+                } else {
+                    let distance;
+                    if range.decode_bit(&mut self.is_rep_g1[self.state])? == 0 {
+                        distance = self.reps[1];
+                    } else {
+                        if range.decode_bit(&mut self.is_rep_g2[self.state])? == 0 {
+                            distance = self.reps[2];
+                        } else {
+                            distance = self.reps[3];
+                            self.reps[3] = self.reps[2];
+                        }
+                        self.reps[2] = self.reps[1];
+                    }
+                    self.reps[1] = self.reps[0];
+                    self.reps[0] = distance;
+                }
+                length = self.rep_length.decode(range, position_state)?;
+                self.state = if self.state < FIRST_MATCH_STATE { 8 } else { 11 };
+            } else {
+                self.reps[3] = self.reps[2];
+                self.reps[2] = self.reps[1];
+                self.reps[1] = self.reps[0];
+                length = self.match_length.decode(range, position_state)?;
+                self.state = if self.state < FIRST_MATCH_STATE { 7 } else { 10 };
+                self.reps[0] = self.decode_distance(range, length)?;
+
+                if self.reps[0] == END_MARKER_DISTANCE {
+                    if !allow_end_marker {
+                        return Err(format!("unexpected end marker at byte {}", range.consumed()));
+                    }
+                    if !range.is_finished() {
+                        return Err(format!("invalid end marker at byte {}", range.consumed()));
+                    }
+                    return Ok(true);
+                }
+            }
+            self.check_distance(range, window)?;
+
+            let length = length as usize + MATCH_MIN_LENGTH;
+            if length as u64 > remaining {
+                return Err(format!("match goes beyond the uncompressed size at byte {}", range.consumed()));
+            }
+            window.copy(self.reps[0] as usize + 1, length)?;
+        }
+    }
+
+    /// Check that the last distance is in the data available
+    fn check_distance<R: Read, W: Write>(&self, range: &RangeDecoder<R>, window: &Window<W>) -> Result<(), String> {
+        if self.reps[0] as u64 >= window.available || self.reps[0] as usize >= window.size {
+            return Err(format!("match distance {} goes before the start of the data at byte {}", self.reps[0] as u64 + 1, range.consumed()));
+        }
+        Ok(())
+    }
+
+    /// Decode a literal, using the previous byte and the position as context.
+    /// After a match, the byte at the last distance is used as context too.
+    fn decode_literal<R: Read, W: Write>(&mut self, range: &mut RangeDecoder<R>, window: &mut Window<W>) -> Result<(), String> {
+        let previous_byte = if window.available > 0 { window.byte_at(1) as usize } else { 0 };
+        let position_mask = (1 << self.literal_position_bits) - 1;
+        let literal_state = ((window.available as usize & position_mask) << self.literal_context_bits)
+            + (previous_byte >> (8 - self.literal_context_bits));
+        let probabilities = &mut self.literal[LITERAL_CODER_SIZE * literal_state..LITERAL_CODER_SIZE * (literal_state + 1)];
+
+        let mut symbol: usize = 1;
+        if self.state >= FIRST_MATCH_STATE && (self.reps[0] as u64) < window.available {
+            let mut match_byte = window.byte_at(self.reps[0] as usize + 1) as usize;
+            while symbol < 0x100 {
+                let match_bit = (match_byte >> 7) & 1;
+                match_byte <<= 1;
+                let bit = range.decode_bit(&mut probabilities[((1 + match_bit) << 8) + symbol])? as usize;
+                symbol = (symbol << 1) | bit;
+                if match_bit != bit {
+                    break;
+                }
+            }
+        }
+        while symbol < 0x100 {
+            symbol = (symbol << 1) | range.decode_bit(&mut probabilities[symbol])? as usize;
+        }
+        window.push(symbol as u8)?;
+
+        self.state = match self.state {
+            0..=3 => 0,
+            4..=9 => self.state - 3,
+            _ => self.state - 6,
+        };
+        Ok(())
+    }
+
+    /// Decode the distance of a match, minus 1
+    fn decode_distance<R: Read>(&mut self, range: &mut RangeDecoder<R>, length: u32) -> Result<u32, String> {
+        let length_state = (length as usize).min(LENGTH_TO_POSITION_STATES - 1);
+        let slot = range.decode_bit_tree(&mut self.distance_slot[length_state], 6)?;
+        if slot < 4 {
+            return Ok(slot);
+        }
+
+        let direct_bits = (slot >> 1) - 1;
+        let distance = (2 | (slot & 1)) << direct_bits;
+        if slot < END_POSITION_MODEL_INDEX {
+            let probabilities = &mut self.distance_special[(distance - slot) as usize..];
+            return Ok(distance + range.decode_reverse_bit_tree(probabilities, direct_bits)?);
+        }
+        let high = range.decode_direct_bits(direct_bits - ALIGN_BITS)? << ALIGN_BITS;
+        let low = range.decode_reverse_bit_tree(&mut self.distance_align, ALIGN_BITS)?;
+        Ok(distance.wrapping_add(high).wrapping_add(low))
+    }
+}
+
+/// Decodes the lengths of matches, minus MATCH_MIN_LENGTH
+struct LengthDecoder {
+    /// The probability that the length is 8 or more
+    choice: u16,
+    /// The probability that the length is 16 or more
+    choice2: u16,
+    /// The probabilities of the lengths 0 to 7, by position state
+    low: [[u16; 8]; MAX_POSITION_STATES],
+    /// The probabilities of the lengths 8 to 15, by position state
+    middle: [[u16; 8]; MAX_POSITION_STATES],
+    /// The probabilities of the lengths 16 to 271
+    high: [u16; 256],
+}
+
+impl LengthDecoder {
+    /// Create a length decoder with the initial probabilities
+    fn new() -> LengthDecoder {
+        LengthDecoder {
+            choice: PROBABILITY_INIT,
+            choice2: PROBABILITY_INIT,
+            low: [[PROBABILITY_INIT; 8]; MAX_POSITION_STATES],
+            middle: [[PROBABILITY_INIT; 8]; MAX_POSITION_STATES],
+            high: [PROBABILITY_INIT; 256],
+        }
+    }
+
+    /// Decode a length
+    fn decode<R: Read>(&mut self, range: &mut RangeDecoder<R>, position_state: usize) -> Result<u32, String> {
+        if range.decode_bit(&mut self.choice)? == 0 {
+            return range.decode_bit_tree(&mut self.low[position_state], 3);
+        }
+        if range.decode_bit(&mut self.choice2)? == 0 {
+            return Ok(8 + range.decode_bit_tree(&mut self.middle[position_state], 3)?);
+        }
+        Ok(16 + range.decode_bit_tree(&mut self.high, 8)?)
+    }
+}
+
+/// Decodes bits with the range coder of LZMA, from probabilities or directly
+struct RangeDecoder<'a, R: Read> {
+    /// The compressed stream
+    input: &'a mut ByteReader<R>,
+    /// The size of the current range
+    range: u32,
+    /// The position of the code in the current range
+    code: u32,
+}
+
+impl<'a, R: Read> RangeDecoder<'a, R> {
+    /// Initialize a range decoder with the first 5 bytes of the stream
+    fn new(input: &'a mut ByteReader<R>) -> Result<RangeDecoder<'a, R>, String> {
+        if input.read_byte()? != 0 {
+            return Err(format!("invalid range coder initialization at byte {}", input.consumed()));
+        }
+        let mut code = 0;
+        for _ in 0..4 {
+            code = (code << 8) | input.read_byte()? as u32;
+        }
+        if code == 0xffffffff {
+            return Err(format!("invalid range coder initialization at byte {}", input.consumed()));
+        }
+        Ok(RangeDecoder {
+            input,
+            range: 0xffffffff,
+            code,
+        })
+    }
+
+    /// Read a byte when the range becomes too small
+    fn normalize(&mut self) -> Result<(), String> {
+        if self.range < 1 << 24 {
+            self.range <<= 8;
+            self.code = (self.code << 8) | self.input.read_byte()? as u32;
+        }
+        Ok(())
+    }
+
+    /// Decode a bit with its probability of being 0, on 11 bits, and adapt
+    /// the probability
+    fn decode_bit(&mut self, probability: &mut u16) -> Result<u32, String> {
+        let bound = (self.range >> 11) * *probability as u32;
+        let bit = if self.code < bound {
+            self.range = bound;
+            *probability += ((1 << 11) - *probability) >> 5;
+            0
+        } else {
+            self.range -= bound;
+            self.code -= bound;
+            *probability -= *probability >> 5;
+            1
+        };
+        self.normalize()?;
+        Ok(bit)
+    }
+
+    /// Decode bits with a probability of 0.5, most significant first
+    fn decode_direct_bits(&mut self, count: u32) -> Result<u32, String> {
+        let mut value = 0u32;
+        for _ in 0..count {
+            self.range >>= 1;
+            let bit = if self.code >= self.range {
+                self.code -= self.range;
+                1
+            } else {
+                0
+            };
+            value = (value << 1) | bit;
+            self.normalize()?;
+        }
+        Ok(value)
+    }
+
+    /// Decode a value of count bits, most significant first, each bit using
+    /// the probability of the bits before it
+    fn decode_bit_tree(&mut self, probabilities: &mut [u16], count: u32) -> Result<u32, String> {
+        let mut index = 1;
+        for _ in 0..count {
+            index = (index << 1) | self.decode_bit(&mut probabilities[index])? as usize;
+        }
+        Ok(index as u32 - (1 << count))
+    }
+
+    /// Decode a value of count bits, least significant first, each bit using
+    /// the probability of the bits before it
+    fn decode_reverse_bit_tree(&mut self, probabilities: &mut [u16], count: u32) -> Result<u32, String> {
+        let mut index = 1;
+        let mut value = 0;
+        for bit_index in 0..count {
+            let bit = self.decode_bit(&mut probabilities[index])?;
+            index = (index << 1) | bit as usize;
+            value |= bit << bit_index;
+        }
+        Ok(value)
+    }
+
+    /// Whether the stream ended properly, after the end marker
+    fn is_finished(&self) -> bool {
+        self.code == 0
+    }
+
+    /// The number of bytes consumed since the start of the stream
+    fn consumed(&self) -> u64 {
+        self.input.consumed()
+    }
+}
+
+/// The dictionary: keeps the last decompressed bytes for matches, and writes
+/// the decompressed data to the output
+struct Window<'a, W: Write> {
+    /// Where to write the decompressed data
+    output: &'a mut W,
+    /// The last decompressed bytes
+    data: Vec<u8>,
+    /// The position in data of the first byte not yet written to output
+    unwritten: usize,
+    /// The number of bytes decompressed since the start of the stream
+    produced: u64,
+    /// The number of bytes decompressed since the last reset of the dictionary
+    available: u64,
+    /// The size of the dictionary, the maximum distance of a match
+    size: usize,
+}
+
+impl<'a, W: Write> Window<'a, W> {
+    /// Create a dictionary of the given size writing to the given output.
+    /// Dictionaries are at least 4 KiB.
+    fn new(output: &'a mut W, size: u32) -> Window<'a, W> {
+        Window {
+            output,
+            data: Vec::new(),
+            unwritten: 0,
+            produced: 0,
+            available: 0,
+            size: size.max(4096) as usize,
+        }
+    }
+
+    /// Forget the previous bytes, they can't be used by matches anymore
+    fn reset(&mut self) {
+        self.available = 0;
+    }
+
+    /// Get the byte at the given distance, 1 being the last byte.
+    /// The distance must be available.
+    fn byte_at(&self, distance: usize) -> u8 {
+        self.data[self.data.len() - distance]
+    }
+
+    /// Add a decompressed byte
+    fn push(&mut self, byte: u8) -> Result<(), String> {
+        self.data.push(byte);
+        self.produced += 1;
+        self.available += 1;
+        if self.data.len() - self.unwritten >= self.size.max(FLUSH_SIZE) {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Copy length bytes starting distance bytes back
+    fn copy(&mut self, distance: usize, length: usize) -> Result<(), String> {
+        for _ in 0..length {
+            // The source may overlap the bytes being copied, and push() may
+            // drop the oldest bytes, so the source is computed from the end
+            self.push(self.byte_at(distance))?;
+        }
+        Ok(())
+    }
+
+    /// Write the pending bytes to the output, and keep only the last bytes
+    /// needed for matches
+    fn flush(&mut self) -> Result<(), String> {
+        self.output.write_all(&self.data[self.unwritten..])
+            .map_err(|e| format!("unable to write the decompressed data: {}", e))?;
+        let keep_from = self.data.len().saturating_sub(self.size);
+        self.data.drain(..keep_from);
+        self.unwritten = self.data.len();
+        Ok(())
+    }
+
+    /// Write all the pending bytes to the output
+    fn flush_all(&mut self) -> Result<(), String> {
+        self.flush()?;
+        self.output.flush()
+            .map_err(|e| format!("unable to write the decompressed data: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::squares;
+
+    // The fixtures were created with XZ Utils 5.8.2. The .lzma files have a
+    // header of 13 bytes: the properties byte, the dictionary size and the
+    // uncompressed size, unknown, so the stream ends with an end marker.
+
+    /// Decompress a .lzma file
+    fn decompress_lzma_file(data: &[u8], uncompressed_size: Option<u64>) -> Result<(Vec<u8>, DecompressionSummary), String> {
+        let dictionary_size = u32::from_le_bytes(data[1..5].try_into().unwrap());
+        let properties = LzmaProperties::new(data[0], dictionary_size)?;
+        let mut output = Vec::new();
+        let summary = decompress(&data[13..], &mut output, &properties, uncompressed_size)?;
+        assert_eq!(summary.produced, output.len() as u64);
+        Ok((output, summary))
+    }
+
+    #[test]
+    fn decompresses_stream_with_end_marker() {
+        let data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares.lzma"));
+        let (output, summary) = decompress_lzma_file(data, None).unwrap();
+        assert_eq!(output, squares());
+        assert_eq!(summary.consumed, data.len() as u64 - 13);
+    }
+
+    #[test]
+    fn decompresses_stream_of_known_size() {
+        let data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares.lzma"));
+        // The decompression stops at the uncompressed size, before the end marker
+        let (output, summary) = decompress_lzma_file(data, Some(squares().len() as u64)).unwrap();
+        assert_eq!(output, squares());
+        assert!(summary.consumed < data.len() as u64 - 13);
+    }
+
+    #[test]
+    fn decompresses_stream_with_other_properties() {
+        // lc=0, lp=2, pb=0
+        let data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares_lp2.lzma"));
+        assert_eq!(decompress_lzma_file(data, None).unwrap().0, squares());
+    }
+
+    #[test]
+    fn rejects_invalid_properties() {
+        assert!(LzmaProperties::new(225, 1 << 16).is_err());
+    }
+
+    #[test]
+    fn decompresses_lzma2_stream() {
+        // A raw LZMA2 stream, with a dictionary of 64 KiB
+        let data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares.lzma2"));
+        let mut output = Vec::new();
+        let summary = decompress_lzma2(&data[..], &mut output, 1 << 16).unwrap();
+        assert_eq!(summary.consumed, data.len() as u64);
+        assert_eq!(output, squares());
+    }
+}
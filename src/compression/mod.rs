@@ -0,0 +1,317 @@
+//! This module contains the decompression algorithms used by archive formats.
+//! They are implemented in the crate, without external dependencies.
+
+pub mod bzip2;
+pub mod deflate;
+pub mod implode;
+pub mod lzma;
+pub mod reduce;
+pub mod shrink;
+pub mod xz;
+pub mod zstd;
+
+use std::io::{Read, Write};
+
+/// The result of a successful decompression
+pub struct DecompressionSummary {
+    /// The number of compressed bytes read, up to the end of the compressed stream
+    pub consumed: u64,
+    /// The number of decompressed bytes written
+    pub produced: u64,
+}
+
+/// Reads a stream byte by byte, and counts the bytes consumed
+pub(crate) struct ByteReader<R: Read> {
+    /// The underlying stream
+    input: R,
+    /// The bytes read from the stream and not yet consumed
+    buffer: Vec<u8>,
+    /// The position of the next byte in buffer
+    position: usize,
+    /// The number of valid bytes in buffer
+    length: usize,
+    /// The number of bytes consumed since the start of the stream
+    consumed: u64,
+}
+
+impl<R: Read> ByteReader<R> {
+    /// Create a ByteReader reading the given stream
+    pub(crate) fn new(input: R) -> ByteReader<R> {
+        ByteReader {
+            input,
+            buffer: vec![0; 8192],
+            position: 0,
+            length: 0,
+            consumed: 0,
+        }
+    }
+
+    /// Get the next byte without consuming it, or None at the end of the stream
+    pub(crate) fn peek_byte(&mut self) -> Result<Option<u8>, String> {
+        if self.position == self.length {
+            self.length = self.input.read(&mut self.buffer)
+                .map_err(|e| format!("unable to read the compressed data: {}", e))?;
+            self.position = 0;
+            if self.length == 0 {
+                return Ok(None);
+            }
+        }
+        Ok(Some(self.buffer[self.position]))
+    }
+
+    /// Get the next bytes without consuming them, up to count bytes.
+    /// Less bytes are returned only at the end of the stream.
+    pub(crate) fn peek_bytes(&mut self, count: usize) -> Result<&[u8], String> {
+        if self.length - self.position < count {
+            // Move the remaining bytes at the start of the buffer, and fill it
+            self.buffer.copy_within(self.position..self.length, 0);
+            self.length -= self.position;
+            self.position = 0;
+            while self.length < count {
+                let read = self.input.read(&mut self.buffer[self.length..])
+                    .map_err(|e| format!("unable to read the compressed data: {}", e))?;
+                if read == 0 {
+                    break;
+                }
+                self.length += read;
+            }
+        }
+        Ok(&self.buffer[self.position..self.length.min(self.position + count)])
+    }
+
+    /// Read and consume a byte
+    pub(crate) fn read_byte(&mut self) -> Result<u8, String> {
+        let byte = self.peek_byte()?
+            .ok_or(format!("unexpected end of stream at byte {}", self.consumed))?;
+        self.position += 1;
+        self.consumed += 1;
+        Ok(byte)
+    }
+
+    /// Read and consume count bytes
+    pub(crate) fn read_bytes(&mut self, count: usize) -> Result<Vec<u8>, String> {
+        (0..count).map(|_| self.read_byte()).collect()
+    }
+
+    /// Read and consume 2 bytes, as a big-endian value
+    pub(crate) fn read_u16_be(&mut self) -> Result<u16, String> {
+        Ok(((self.read_byte()? as u16) << 8) | self.read_byte()? as u16)
+    }
+
+    /// Read and consume 4 bytes, as a little-endian value
+    pub(crate) fn read_u32_le(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap()))
+    }
+
+    /// The number of bytes consumed since the start of the stream
+    pub(crate) fn consumed(&self) -> u64 {
+        self.consumed
+    }
+}
+
+/// Reads a stream bit by bit, least significant bit first
+pub(crate) struct BitReader<R: Read> {
+    /// The underlying stream
+    input: R,
+    /// The bytes read from the stream and not yet moved to bits
+    buffer: Vec<u8>,
+    /// The position of the next byte in buffer
+    position: usize,
+    /// The number of valid bytes in buffer
+    length: usize,
+    /// The bits available, the next one being the least significant
+    bits: u64,
+    /// The number of bits available
+    bit_count: u32,
+    /// The number of bytes moved to bits since the start of the stream
+    bytes_used: u64,
+}
+
+impl<R: Read> BitReader<R> {
+    /// Create a BitReader reading the given stream
+    pub(crate) fn new(input: R) -> BitReader<R> {
+        BitReader {
+            input,
+            buffer: vec![0; 8192],
+            position: 0,
+            length: 0,
+            bits: 0,
+            bit_count: 0,
+            bytes_used: 0,
+        }
+    }
+
+    /// Move as many bytes as possible to bits.
+    /// Stops silently at the end of the stream.
+    pub(crate) fn fill(&mut self) -> Result<(), String> {
+        while self.bit_count <= 56 {
+            if self.position == self.length {
+                self.length = self.input.read(&mut self.buffer)
+                    .map_err(|e| format!("unable to read the compressed data: {}", e))?;
+                self.position = 0;
+                if self.length == 0 {
+                    return Ok(());
+                }
+            }
+            self.bits |= (self.buffer[self.position] as u64) << self.bit_count;
+            self.bit_count += 8;
+            self.position += 1;
+            self.bytes_used += 1;
+        }
+        Ok(())
+    }
+
+    /// Get the next count bits without consuming them, and the number of bits
+    /// really available, which is lower than count at the end of the stream
+    pub(crate) fn peek_bits(&mut self, count: u32) -> Result<(u64, u32), String> {
+        if self.bit_count < count {
+            self.fill()?;
+        }
+        Ok((self.bits & ((1 << count) - 1), self.bit_count))
+    }
+
+    /// Drop count bits, which must be available
+    pub(crate) fn consume_bits(&mut self, count: u32) {
+        self.bits >>= count;
+        self.bit_count -= count;
+    }
+
+    /// Read and consume count bits, up to 32
+    pub(crate) fn read_bits(&mut self, count: u32) -> Result<u32, String> {
+        let (bits, available) = self.peek_bits(count)?;
+        if available < count {
+            return Err(format!("unexpected end of stream at byte {}", self.consumed()));
+        }
+        self.consume_bits(count);
+        Ok(bits as u32)
+    }
+
+    /// Drop the remaining bits of the current byte
+    pub(crate) fn align_to_byte(&mut self) {
+        self.consume_bits(self.bit_count % 8);
+    }
+
+    /// The number of bytes consumed since the start of the stream, including
+    /// the partially consumed byte
+    pub(crate) fn consumed(&self) -> u64 {
+        self.bytes_used - (self.bit_count / 8) as u64
+    }
+}
+
+/// Keeps the last decompressed bytes for back references, and writes the
+/// decompressed data to the output
+pub(crate) struct OutputWindow<'a, W: Write> {
+    /// Where to write the decompressed data
+    output: &'a mut W,
+    /// The last decompressed bytes
+    data: Vec<u8>,
+    /// The position in data of the first byte not yet written to output
+    unwritten: usize,
+    /// The number of bytes decompressed since the start of the stream
+    produced: u64,
+    /// The maximum distance of a back reference
+    window_size: usize,
+}
+
+impl<'a, W: Write> OutputWindow<'a, W> {
+    /// Create a window of the given size writing to the given output
+    pub(crate) fn new(output: &'a mut W, window_size: usize) -> OutputWindow<'a, W> {
+        OutputWindow {
+            output,
+            data: Vec::with_capacity(window_size * 4),
+            unwritten: 0,
+            produced: 0,
+            window_size,
+        }
+    }
+
+    /// Add a decompressed byte
+    pub(crate) fn push(&mut self, byte: u8) -> Result<(), String> {
+        self.data.push(byte);
+        self.produced += 1;
+        if self.data.len() >= self.window_size * 4 {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Copy length bytes starting distance bytes back
+    pub(crate) fn copy(&mut self, distance: usize, length: usize) -> Result<(), String> {
+        if distance > self.data.len() {
+            return Err(format!("back reference distance {} goes before the start of the data", distance));
+        }
+        for _ in 0..length {
+            // The source may overlap the bytes being copied, and push() may
+            // drop the oldest bytes, so the source is computed from the end
+            self.push(self.data[self.data.len() - distance])?;
+        }
+        Ok(())
+    }
+
+    /// Copy length bytes starting distance bytes back, the bytes before the
+    /// start of the data being zeros, as with the legacy ZIP methods
+    pub(crate) fn copy_or_zeros(&mut self, distance: usize, length: usize) -> Result<(), String> {
+        for _ in 0..length {
+            let byte = if distance > self.data.len() { 0 } else { self.data[self.data.len() - distance] };
+            self.push(byte)?;
+        }
+        Ok(())
+    }
+
+    /// Write the pending bytes to the output, and keep only the last bytes
+    /// needed for back references
+    pub(crate) fn flush(&mut self) -> Result<(), String> {
+        self.output.write_all(&self.data[self.unwritten..])
+            .map_err(|e| format!("unable to write the decompressed data: {}", e))?;
+        let keep_from = self.data.len().saturating_sub(self.window_size);
+        self.data.drain(..keep_from);
+        self.unwritten = self.data.len();
+        Ok(())
+    }
+
+    /// Write all the pending bytes to the output
+    pub(crate) fn flush_all(&mut self) -> Result<(), String> {
+        self.flush()?;
+        self.output.flush()
+            .map_err(|e| format!("unable to write the decompressed data: {}", e))
+    }
+}
+
+/// The text compressed in the squares.* test fixtures
+#[cfg(test)]
+pub(crate) fn squares() -> Vec<u8> {
+    (0..300).map(|i| format!("{} squared is {}\n", i, i * i)).collect::<String>().into_bytes()
+}
+
+/// The text compressed in the runs.* test fixtures
+#[cfg(test)]
+pub(crate) fn runs() -> Vec<u8> {
+    [b"a".repeat(1000), b"b".repeat(300), b"abc".repeat(5)].concat()
+}
+
+/// Decompress a stream, and check that the whole stream was read
+#[cfg(test)]
+pub(crate) fn decompress_all<'a, F>(data: &'a [u8], decompress: F) -> Result<Vec<u8>, String>
+where
+    F: FnOnce(&'a [u8], &mut Vec<u8>) -> Result<DecompressionSummary, String>,
+{
+    let mut output = Vec::new();
+    let summary = decompress(data, &mut output)?;
+    assert_eq!(summary.consumed, data.len() as u64);
+    assert_eq!(summary.produced, output.len() as u64);
+    Ok(output)
+}
+
+/// Decompress a stream of the squares followed by other data, and check that
+/// only the bytes of the stream were read
+#[cfg(test)]
+pub(crate) fn assert_stops_at_end_of_stream<F>(stream: &[u8], decompress: F)
+where
+    F: FnOnce(std::io::Cursor<Vec<u8>>, &mut Vec<u8>) -> Result<DecompressionSummary, String>,
+{
+    let data = [stream, b"trailing data"].concat();
+    let mut output = Vec::new();
+    let summary = decompress(std::io::Cursor::new(data), &mut output).unwrap();
+    assert_eq!(summary.consumed, stream.len() as u64);
+    assert_eq!(output, squares());
+}
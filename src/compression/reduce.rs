@@ -0,0 +1,164 @@
+//! This module implements the Reduce decompression algorithm, used by PKZIP 0.9.
+//! Specification: APPNOTE.TXT section 5.3
+//! The data is first expanded from follower sets: each byte is either stored
+//! on 8 bits, or as an index in the set of the bytes likely to follow the
+//! previous byte. The expanded bytes are literals, and back references
+//! introduced by the byte 144 (DLE).
+
+use std::io::{Read, Write};
+use super::{BitReader, DecompressionSummary, OutputWindow};
+
+/// The byte introducing a back reference
+const DLE: u8 = 144;
+
+/// The maximum size of a follower set
+const MAX_FOLLOWER_SET_SIZE: u32 = 32;
+
+/// The size of the window, larger than the maximum distance of 4096 bytes
+const WINDOW_SIZE: usize = 8192;
+
+/// The state of the expansion of the bytes
+enum State {
+    /// The byte is a literal, or DLE
+    Literal,
+    /// The byte follows DLE: 0 for a literal DLE, otherwise the length and
+    /// the high bits of the distance
+    Escaped,
+    /// The byte is added to the length
+    Length { value: u8, length: usize },
+    /// The byte is the low bits of the distance
+    Distance { value: u8, length: usize },
+}
+
+/// Decompress a reduced stream read from input, and write the decompressed
+/// data to output.
+/// The factor, from 1 to 4, is the compression method minus 1. The stream has
+/// no end marker: it ends when the uncompressed size is reached.
+pub fn decompress<R: Read, W: Write>(input: R, output: &mut W, factor: u8, uncompressed_size: u64) -> Result<DecompressionSummary, String> {
+    if !(1..=4).contains(&factor) {
+        return Err(format!("invalid compression factor {}", factor));
+    }
+    let length_mask = 0xff >> factor;
+    let mut reader = BitReader::new(input);
+
+    // The follower sets are stored from the one of the byte 255 to the one of 0
+    let mut follower_sets = vec![Vec::new(); 256];
+    for set in follower_sets.iter_mut().rev() {
+        let size = reader.read_bits(6)?;
+        if size > MAX_FOLLOWER_SET_SIZE {
+            return Err(format!("follower set of {} bytes, the maximum is {}", size, MAX_FOLLOWER_SET_SIZE));
+        }
+        for _ in 0..size {
+            set.push(reader.read_bits(8)? as u8);
+        }
+    }
+
+    let mut window = OutputWindow::new(output, WINDOW_SIZE);
+    let mut last_byte = 0;
+    let mut state = State::Literal;
+    while window.produced < uncompressed_size {
+        let set: &Vec<u8> = &follower_sets[last_byte as usize];
+        let byte = if set.is_empty() || reader.read_bits(1)? == 1 {
+            reader.read_bits(8)? as u8
+        } else {
+            let index_bits = (u32::BITS - (set.len() as u32 - 1).leading_zeros()).max(1);
+            let index = reader.read_bits(index_bits)? as usize;
+            *set.get(index)
+                .ok_or(format!("follower index {} out of a set of {} bytes at byte {}", index, set.len(), reader.consumed()))?
+        };
+        last_byte = byte;
+
+        state = match state {
+            State::Literal if byte == DLE => State::Escaped,
+            State::Literal => {
+                window.push(byte)?;
+                State::Literal
+            },
+            State::Escaped if byte == 0 => {
+                window.push(DLE)?;
+                State::Literal
+            },
+            State::Escaped if byte & length_mask == length_mask => State::Length { value: byte, length: length_mask as usize },
+            State::Escaped => State::Distance { value: byte, length: (byte & length_mask) as usize },
+            State::Length { value, length } => State::Distance { value, length: length + byte as usize },
+            State::Distance { value, length } => {
+                let distance = ((value >> (8 - factor)) as usize) * 256 + byte as usize + 1;
+                window.copy_or_zeros(distance, length + 3)?;
+                State::Literal
+            },
+        };
+    }
+
+    window.flush_all()?;
+    Ok(DecompressionSummary {
+        consumed: reader.consumed(),
+        produced: window.produced,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::squares;
+
+    // No Reduce compressor is freely available: the fixtures were created
+    // with a small encoder written from APPNOTE.TXT, with follower sets of
+    // various sizes
+
+    /// Decompress a stream, and check that the decompression stopped at the
+    /// uncompressed size
+    fn decompress_all(data: &[u8], factor: u8, uncompressed_size: u64) -> Result<Vec<u8>, String> {
+        let mut output = Vec::new();
+        let summary = decompress(data, &mut output, factor, uncompressed_size)?;
+        assert_eq!(summary.produced, uncompressed_size);
+        Ok(output)
+    }
+
+    #[test]
+    fn decompresses_streams_of_each_factor() {
+        let streams: [&[u8]; 4] = [
+            include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares.reduce1")),
+            include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares.reduce2")),
+            include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares.reduce3")),
+            include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares.reduce4")),
+        ];
+        for (factor, data) in (1..).zip(streams) {
+            assert_eq!(decompress_all(data, factor, squares().len() as u64).unwrap(), squares(), "factor {}", factor);
+        }
+    }
+
+    #[test]
+    fn decompresses_literal_dle_bytes() {
+        // The byte 144 (DLE) is stored as 144 followed by 0
+        let data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/cycle.reduce4"));
+        let expected: Vec<u8> = (0..=255).cycle().take(2000).collect();
+        assert_eq!(decompress_all(data, 4, 2000).unwrap(), expected);
+    }
+
+    #[test]
+    fn decompresses_stream_assembled_from_specification() {
+        // No PKZIP 0.9 archive was at hand to check the fixture encoder: this
+        // stream was assembled bit by bit from APPNOTE.TXT section 5.3, with
+        // the factor 2. The bits are read from the least significant one.
+        // The only follower set is the one of 'a', {'b'}: its size 1 is at
+        // the bit 948, after the empty sets of the bytes 255 to 98, followed
+        // by 'b' and the empty sets of the bytes 96 to 0.
+        let mut data = vec![0u8; 193];
+        data[118..121].copy_from_slice(&[0x10, 0x88, 0x01]);
+        // 'a' on 8 bits, 'b' as the flag 0 and the index 0 on 1 bit, then on
+        // 8 bits: DLE 0x04 0x01 copies 4 + 3 bytes at the distance 1 + 1,
+        // DLE 0x00 is a literal DLE, and DLE 0x3f 0x02 0x00 copies
+        // 63 + 2 + 3 bytes at the distance 0 + 1
+        data.extend_from_slice(&[0x61, 0x40, 0x12, 0x04, 0x40, 0x02, 0x40, 0xfe, 0x08, 0x00, 0x00]);
+        let expected = [b"ababababa".to_vec(), vec![DLE; 69]].concat();
+        let mut output = Vec::new();
+        let summary = decompress(&data[..], &mut output, 2, expected.len() as u64).unwrap();
+        assert_eq!(summary.consumed, data.len() as u64);
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn rejects_invalid_factor() {
+        assert!(decompress(&[0u8; 16][..], &mut Vec::new(), 5, 16).is_err());
+    }
+}
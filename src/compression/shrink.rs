@@ -0,0 +1,201 @@
+//! This module implements the Shrink decompression algorithm, used by PKZIP 1.x.
+//! Specification: APPNOTE.TXT section 5.2
+//! Shrink is a dynamic LZW with codes of 9 to 13 bits. The code 256 followed
+//! by 1 increases the code size, and followed by 2 partially clears the table:
+//! the codes which aren't the prefix of another code become free, and are
+//! reused by increasing value.
+
+use std::io::{Read, Write};
+use super::{BitReader, DecompressionSummary, OutputWindow};
+
+/// The code introducing a control code
+const CONTROL_CODE: u32 = 256;
+
+/// The first code which isn't a literal or the control code
+const FIRST_FREE_CODE: usize = 257;
+
+/// The number of codes, with the maximum code size
+const CODE_COUNT: usize = 1 << MAX_CODE_SIZE;
+
+/// The code size at the start of the stream
+const INITIAL_CODE_SIZE: u32 = 9;
+
+/// The maximum code size
+const MAX_CODE_SIZE: u32 = 13;
+
+/// Decompress a shrunk stream read from input, and write the decompressed data
+/// to output.
+/// The stream has no end marker: it ends with the input, or when the
+/// uncompressed size is reached.
+pub fn decompress<R: Read, W: Write>(input: R, output: &mut W, uncompressed_size: u64) -> Result<DecompressionSummary, String> {
+    let mut reader = BitReader::new(input);
+    let mut window = OutputWindow::new(output, CODE_COUNT);
+    let mut table = CodeTable::new();
+    let mut free_codes = table.free_codes().into_iter().peekable();
+    let mut code_size = INITIAL_CODE_SIZE;
+    let mut previous_code = None;
+
+    while window.produced < uncompressed_size {
+        let (_, available) = reader.peek_bits(code_size)?;
+        if available < code_size {
+            break;
+        }
+        let code = reader.read_bits(code_size)?;
+        if code == CONTROL_CODE {
+            match reader.read_bits(code_size)? {
+                1 if code_size < MAX_CODE_SIZE => code_size += 1,
+                2 => {
+                    table.partial_clear();
+                    free_codes = table.free_codes().into_iter().peekable();
+                },
+                control => return Err(format!("invalid control code {} at byte {}", control, reader.consumed())),
+            }
+            continue;
+        }
+        let code = code as usize;
+
+        let Some(previous) = previous_code else {
+            // The first code is a literal
+            if code >= CONTROL_CODE as usize {
+                return Err(format!("the stream starts with the code {}, not a literal", code));
+            }
+            window.push(code as u8)?;
+            previous_code = Some(code);
+            continue;
+        };
+
+        // The code may be the one being defined: the previous string followed
+        // by its first byte
+        let string = if table.entries[code].is_none() && free_codes.peek() == Some(&code) {
+            let mut string = table.string(previous)?;
+            string.push(string[0]);
+            string
+        } else {
+            table.string(code)
+                .map_err(|e| format!("{} at byte {}", e, reader.consumed()))?
+        };
+
+        // The previous string followed by the first byte of this one is a new code
+        if let Some(new_code) = free_codes.next() {
+            table.entries[new_code] = Some(Entry {
+                prefix: Some(previous),
+                byte: string[0],
+            });
+        }
+        for byte in string {
+            window.push(byte)?;
+        }
+        previous_code = Some(code);
+    }
+
+    window.flush_all()?;
+    Ok(DecompressionSummary {
+        consumed: reader.consumed(),
+        produced: window.produced,
+    })
+}
+
+/// A code of the table: a prefix code followed by a byte
+#[derive(Clone)]
+struct Entry {
+    /// The code of the string before the byte, None for the literals
+    prefix: Option<usize>,
+    /// The last byte of the string
+    byte: u8,
+}
+
+/// The strings of the codes, None for the free codes
+struct CodeTable {
+    /// The entry of each code
+    entries: Vec<Option<Entry>>,
+}
+
+impl CodeTable {
+    /// Create a table with only the literals
+    fn new() -> CodeTable {
+        let mut entries = vec![None; CODE_COUNT];
+        for (byte, entry) in entries.iter_mut().take(256).enumerate() {
+            *entry = Some(Entry {
+                prefix: None,
+                byte: byte as u8,
+            });
+        }
+        CodeTable {
+            entries,
+        }
+    }
+
+    /// Get the string of a code
+    fn string(&self, code: usize) -> Result<Vec<u8>, String> {
+        let mut string = Vec::new();
+        let mut current = code;
+        loop {
+            let entry = self.entries[current].as_ref()
+                .ok_or(format!("unknown code {}", code))?;
+            string.push(entry.byte);
+            match entry.prefix {
+                Some(prefix) => current = prefix,
+                None => break,
+            }
+            if string.len() > CODE_COUNT {
+                return Err(format!("the code {} loops", code));
+            }
+        }
+        string.reverse();
+        Ok(string)
+    }
+
+    /// Free the codes which aren't the prefix of another code
+    fn partial_clear(&mut self) {
+        let mut is_prefix = vec![false; CODE_COUNT];
+        for entry in self.entries[FIRST_FREE_CODE..].iter().flatten() {
+            if let Some(prefix) = entry.prefix {
+                is_prefix[prefix] = true;
+            }
+        }
+        for (entry, is_prefix) in self.entries.iter_mut().zip(is_prefix).skip(FIRST_FREE_CODE) {
+            if !is_prefix {
+                *entry = None;
+            }
+        }
+    }
+
+    /// The free codes, by increasing value
+    fn free_codes(&self) -> Vec<usize> {
+        (FIRST_FREE_CODE..CODE_COUNT)
+            .filter(|code| self.entries[*code].is_none())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::squares;
+
+    // No Shrink compressor is freely available: the fixtures were created
+    // with a small encoder written from APPNOTE.TXT, and checked against
+    // Info-ZIP UnZip 6.00
+
+    /// Decompress a stream, and check that the whole stream was read
+    fn decompress_all(data: &[u8], uncompressed_size: u64) -> Result<Vec<u8>, String> {
+        let mut output = Vec::new();
+        let summary = decompress(data, &mut output, uncompressed_size)?;
+        assert_eq!(summary.consumed, data.len() as u64);
+        assert_eq!(summary.produced, output.len() as u64);
+        Ok(output)
+    }
+
+    #[test]
+    fn decompresses_stream_with_code_size_increases() {
+        let data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares.shrink"));
+        assert_eq!(decompress_all(data, squares().len() as u64).unwrap(), squares());
+    }
+
+    #[test]
+    fn decompresses_stream_with_partial_clears() {
+        // The table is partially cleared every 300 codes
+        let data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares_cleared.shrink"));
+        assert_eq!(decompress_all(data, squares().len() as u64).unwrap(), squares());
+    }
+}
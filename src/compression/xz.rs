@@ -0,0 +1,396 @@
+//! This module implements the decompression of the XZ format, a container of
+//! LZMA2 data.
+//! Specification: https://tukaani.org/xz/xz-file-format.txt
+//! Only the LZMA2 filter is supported, the other filters (BCJ, delta) are
+//! reported as unsupported.
+
+use std::io::{Read, Write};
+use crate::checksum::{crc32, Crc32, Crc64};
+use crate::crypto::sha256::Sha256;
+use super::lzma::decode_lzma2;
+use super::{ByteReader, DecompressionSummary};
+
+/// The magic bytes at the start of a stream
+const STREAM_HEADER_MAGIC: [u8; 6] = [0xfd, b'7', b'z', b'X', b'Z', 0x00];
+
+/// The magic bytes at the end of a stream
+const STREAM_FOOTER_MAGIC: [u8; 2] = [b'Y', b'Z'];
+
+/// The ID of the LZMA2 filter
+const FILTER_LZMA2: u64 = 0x21;
+
+/// Decompress a XZ stream read from input, and write the decompressed data to
+/// output.
+/// The header, the blocks, the index and the footer are checked, and so is the
+/// integrity check of each block. Concatenated streams, separated by stream
+/// padding, are decompressed as a single one.
+/// The input may contain data after the end of the stream: the number of bytes
+/// belonging to the stream is returned. Since the input is read by chunks, more
+/// bytes than that may have been read from it.
+pub fn decompress<R: Read, W: Write>(input: R, output: &mut W) -> Result<DecompressionSummary, String> {
+    let mut reader = ByteReader::new(input);
+    let mut produced = 0;
+
+    loop {
+        let stream_flags = read_stream_header(&mut reader)?;
+        let check_type = stream_flags[1];
+
+        // The unpadded size and the uncompressed size of each block, as in the index
+        let mut blocks = Vec::new();
+        loop {
+            let header_size = reader.read_byte()?;
+            if header_size == 0x00 {
+                break;
+            }
+            let block = read_block(&mut reader, header_size, check_type, output)
+                .map_err(|e| format!("block {}: {}", blocks.len() + 1, e))?;
+            produced += block.1;
+            blocks.push(block);
+        }
+
+        let index_size = read_index(&mut reader, &blocks)?;
+        read_stream_footer(&mut reader, stream_flags, index_size)?;
+
+        // Stream padding, then maybe another stream
+        let mut padding: u64 = 0;
+        while reader.peek_byte()? == Some(0x00) {
+            reader.read_byte()?;
+            padding += 1;
+        }
+        if !padding.is_multiple_of(4) {
+            return Err(format!("stream padding of {} bytes is not a multiple of 4, at byte {}", padding, reader.consumed()));
+        }
+        if reader.peek_byte()? != Some(STREAM_HEADER_MAGIC[0]) {
+            break;
+        }
+    }
+
+    Ok(DecompressionSummary {
+        consumed: reader.consumed(),
+        produced,
+    })
+}
+
+/// Read the header of a stream, and return its flags
+fn read_stream_header<R: Read>(reader: &mut ByteReader<R>) -> Result<[u8; 2], String> {
+    let magic = reader.read_bytes(STREAM_HEADER_MAGIC.len())?;
+    if magic != STREAM_HEADER_MAGIC {
+        return Err(format!("invalid stream header magic bytes {:02x?}", magic));
+    }
+    let stream_flags = [reader.read_byte()?, reader.read_byte()?];
+    if reader.read_u32_le()? != crc32(&stream_flags) {
+        return Err(format!("CRC-32 mismatch in the stream header at byte {}", reader.consumed()));
+    }
+    if stream_flags[0] != 0 || stream_flags[1] & 0xf0 != 0 {
+        return Err(format!("unsupported stream flags {:02x?} at byte {}", stream_flags, reader.consumed()));
+    }
+    Ok(stream_flags)
+}
+
+/// Decompress a block, whose header size byte was already read.
+/// Returns the unpadded size of the block and its uncompressed size.
+fn read_block<R: Read, W: Write>(reader: &mut ByteReader<R>, header_size_byte: u8, check_type: u8, output: &mut W) -> Result<(u64, u64), String> {
+    let header_size = (header_size_byte as usize + 1) * 4;
+    let mut header = vec![header_size_byte];
+    header.extend(reader.read_bytes(header_size - 1)?);
+    let (header, stored_crc32) = header.split_at(header_size - 4);
+    if u32::from_le_bytes(stored_crc32.try_into().unwrap()) != crc32(header) {
+        return Err(format!("CRC-32 mismatch in the block header at byte {}", reader.consumed()));
+    }
+
+    let flags = header[1];
+    if flags & 0x3c != 0 {
+        return Err(format!("unsupported block flags {:#04x}", flags));
+    }
+    let mut position = 2;
+    let compressed_size = if flags & 0x40 != 0 { Some(read_varint(header, &mut position)?) } else { None };
+    let uncompressed_size = if flags & 0x80 != 0 { Some(read_varint(header, &mut position)?) } else { None };
+
+    let filter_count = (flags & 0x03) + 1;
+    let mut dictionary_size = None;
+    for _ in 0..filter_count {
+        let filter_id = read_varint(header, &mut position)?;
+        let properties_size = read_varint(header, &mut position)? as usize;
+        let properties = header.get(position..position + properties_size)
+            .ok_or("filter properties beyond the block header".to_string())?;
+        position += properties_size;
+        if filter_id != FILTER_LZMA2 || filter_count > 1 {
+            return Err(format!("unsupported filter {:#x}, only a single LZMA2 filter is supported", filter_id));
+        }
+        if properties.len() != 1 || properties[0] > 40 {
+            return Err(format!("invalid LZMA2 properties {:02x?}", properties));
+        }
+        dictionary_size = Some(lzma2_dictionary_size(properties[0]));
+    }
+    if header[position..].iter().any(|byte| *byte != 0) {
+        return Err("non-null padding in the block header".to_string());
+    }
+
+    let start = reader.consumed();
+    let mut check = Check::new(check_type);
+    let mut checked_output = CheckedOutput { output, check: &mut check };
+    let produced = decode_lzma2(reader, &mut checked_output, dictionary_size.unwrap())?;
+    let used = reader.consumed() - start;
+    if compressed_size.is_some_and(|size| size != used) {
+        return Err(format!("compressed size of {} bytes announced, {} used", compressed_size.unwrap(), used));
+    }
+    if uncompressed_size.is_some_and(|size| size != produced) {
+        return Err(format!("uncompressed size of {} bytes announced, {} produced", uncompressed_size.unwrap(), produced));
+    }
+
+    // Block padding, to a multiple of 4 bytes
+    for _ in 0..(4 - used % 4) % 4 {
+        if reader.read_byte()? != 0 {
+            return Err(format!("non-null block padding at byte {}", reader.consumed()));
+        }
+    }
+
+    let stored_check = reader.read_bytes(check.size())?;
+    if let Some(computed_check) = check.value() {
+        if stored_check != computed_check {
+            return Err(format!("{} mismatch: stored {:02x?}, computed {:02x?}", check.name(), stored_check, computed_check));
+        }
+    }
+    Ok((header_size as u64 + used + check.size() as u64, produced))
+}
+
+/// Read the index, whose indicator byte was already read, and check it
+/// against the blocks read.
+/// Returns the size of the index.
+fn read_index<R: Read>(reader: &mut ByteReader<R>, blocks: &[(u64, u64)]) -> Result<u64, String> {
+    let start = reader.consumed() - 1;
+    let mut crc = Crc32::new();
+    crc.update(&[0x00]);
+    let mut read_index_varint = |reader: &mut ByteReader<R>| -> Result<u64, String> {
+        let mut bytes = Vec::new();
+        loop {
+            let byte = reader.read_byte()?;
+            bytes.push(byte);
+            if byte & 0x80 == 0 || bytes.len() == 9 {
+                break;
+            }
+        }
+        crc.update(&bytes);
+        let mut position = 0;
+        read_varint(&bytes, &mut position)
+    };
+
+    let record_count = read_index_varint(reader)?;
+    if record_count != blocks.len() as u64 {
+        return Err(format!("the index lists {} blocks, {} found", record_count, blocks.len()));
+    }
+    for (number, (unpadded_size, uncompressed_size)) in blocks.iter().enumerate() {
+        let indexed = (read_index_varint(reader)?, read_index_varint(reader)?);
+        if indexed != (*unpadded_size, *uncompressed_size) {
+            return Err(format!(
+                "the index lists the block {} with an unpadded size of {} and an uncompressed size of {}, found {} and {}",
+                number + 1, indexed.0, indexed.1, unpadded_size, uncompressed_size,
+            ));
+        }
+    }
+
+    while !(reader.consumed() - start).is_multiple_of(4) {
+        let byte = reader.read_byte()?;
+        if byte != 0 {
+            return Err(format!("non-null index padding at byte {}", reader.consumed()));
+        }
+        crc.update(&[byte]);
+    }
+    if reader.read_u32_le()? != crc.value() {
+        return Err(format!("CRC-32 mismatch in the index at byte {}", reader.consumed()));
+    }
+    Ok(reader.consumed() - start)
+}
+
+/// Read the footer of a stream, and check it against the header and the index
+fn read_stream_footer<R: Read>(reader: &mut ByteReader<R>, stream_flags: [u8; 2], index_size: u64) -> Result<(), String> {
+    let stored_crc32 = reader.read_u32_le()?;
+    let fields = reader.read_bytes(6)?;
+    if stored_crc32 != crc32(&fields) {
+        return Err(format!("CRC-32 mismatch in the stream footer at byte {}", reader.consumed()));
+    }
+    let backward_size = (u32::from_le_bytes(fields[..4].try_into().unwrap()) as u64 + 1) * 4;
+    if backward_size != index_size {
+        return Err(format!("the stream footer announces an index of {} bytes, found {}", backward_size, index_size));
+    }
+    if fields[4..] != stream_flags {
+        return Err("the stream flags of the footer don't match the header".to_string());
+    }
+    if reader.read_bytes(2)? != STREAM_FOOTER_MAGIC {
+        return Err(format!("invalid stream footer magic bytes at byte {}", reader.consumed()));
+    }
+    Ok(())
+}
+
+/// Get the dictionary size encoded in the LZMA2 properties byte
+fn lzma2_dictionary_size(properties: u8) -> u32 {
+    if properties == 40 {
+        return 0xffffffff;
+    }
+    (2 | (properties as u32 & 1)) << (properties / 2 + 11)
+}
+
+/// The integrity check of the blocks, computed on the uncompressed data
+enum Check {
+    None,
+    Crc32(Crc32),
+    Crc64(Crc64),
+    Sha256(Sha256),
+    /// A reserved check type, skipped since it can't be computed
+    Unknown(u8),
+}
+
+impl Check {
+    /// Create the check of the given type
+    fn new(check_type: u8) -> Check {
+        match check_type {
+            0x00 => Check::None,
+            0x01 => Check::Crc32(Crc32::new()),
+            0x04 => Check::Crc64(Crc64::new()),
+            0x0a => Check::Sha256(Sha256::new()),
+            _ => Check::Unknown(check_type),
+        }
+    }
+
+    /// The name of the check, for error messages
+    fn name(&self) -> &'static str {
+        match self {
+            Check::None => "no check",
+            Check::Crc32(_) => "CRC-32",
+            Check::Crc64(_) => "CRC-64",
+            Check::Sha256(_) => "SHA-256",
+            Check::Unknown(_) => "reserved check",
+        }
+    }
+
+    /// The size of the check stored after the block
+    fn size(&self) -> usize {
+        match self {
+            Check::None => 0,
+            Check::Crc32(_) => 4,
+            Check::Crc64(_) => 8,
+            Check::Sha256(_) => 32,
+            // The size of reserved checks depends on their type
+            Check::Unknown(check_type) => match check_type {
+                0x01..=0x03 => 4,
+                0x04..=0x06 => 8,
+                0x07..=0x09 => 16,
+                0x0a..=0x0c => 32,
+                _ => 64,
+            },
+        }
+    }
+
+    /// Add decompressed data to the check
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Check::Crc32(crc) => crc.update(data),
+            Check::Crc64(crc) => crc.update(data),
+            Check::Sha256(sha256) => sha256.update(data),
+            Check::None | Check::Unknown(_) => (),
+        }
+    }
+
+    /// The value of the check, as stored after the block, or None if it can't
+    /// be computed
+    fn value(&self) -> Option<Vec<u8>> {
+        match self {
+            Check::None => Some(Vec::new()),
+            Check::Crc32(crc) => Some(crc.value().to_le_bytes().to_vec()),
+            Check::Crc64(crc) => Some(crc.value().to_le_bytes().to_vec()),
+            Check::Sha256(sha256) => Some(sha256.clone().finalize().to_vec()),
+            Check::Unknown(_) => None,
+        }
+    }
+}
+
+/// Writes the decompressed data to the output, and adds it to the check
+struct CheckedOutput<'a, W: Write> {
+    /// Where to write the decompressed data
+    output: &'a mut W,
+    /// The check of the block
+    check: &'a mut Check,
+}
+
+impl<W: Write> Write for CheckedOutput<'_, W> {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        let written = self.output.write(data)?;
+        self.check.update(&data[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.output.flush()
+    }
+}
+
+/// Read a variable-length integer from a buffer: 7 bits per byte, least
+/// significant first, the high bit telling if another byte follows
+fn read_varint(buffer: &[u8], position: &mut usize) -> Result<u64, String> {
+    let mut value = 0;
+    for index in 0..9 {
+        let byte = *buffer.get(*position)
+            .ok_or("truncated variable-length integer".to_string())?;
+        *position += 1;
+        value |= ((byte & 0x7f) as u64) << (7 * index);
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("variable-length integer longer than 9 bytes".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::{assert_stops_at_end_of_stream, decompress_all, runs, squares};
+
+    // The fixtures were created with XZ Utils 5.8.2
+    const SQUARES: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares.xz"));
+    const RUNS: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/runs.xz"));
+
+    #[test]
+    fn decompresses_stream_with_crc64() {
+        assert_eq!(decompress_all(SQUARES, decompress).unwrap(), squares());
+    }
+
+    #[test]
+    fn decompresses_blocks_with_sha256() {
+        // Blocks of 4000 bytes (xz --block-size=4000 -C sha256)
+        let data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares_blocks.xz"));
+        assert_eq!(decompress_all(data, decompress).unwrap(), squares());
+    }
+
+    #[test]
+    fn decompresses_stream_without_check() {
+        assert_eq!(decompress_all(RUNS, decompress).unwrap(), runs());
+    }
+
+    #[test]
+    fn decompresses_concatenated_streams() {
+        let data = [SQUARES, &[0; 4], RUNS].concat();
+        assert_eq!(decompress_all(&data, decompress).unwrap(), [squares(), runs()].concat());
+    }
+
+    #[test]
+    fn counts_only_the_bytes_of_the_stream() {
+        assert_stops_at_end_of_stream(SQUARES, decompress);
+    }
+
+    #[test]
+    fn rejects_unsupported_filter() {
+        // The x86 BCJ filter followed by LZMA2, with a CRC32 check
+        let data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares_x86.xz"));
+        assert!(decompress(&data[..], &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn rejects_wrong_check() {
+        // The CRC64 is stored in the 8 bytes before the index
+        let mut data = SQUARES.to_vec();
+        let index_size = ((data[data.len() - 8] as usize) + 1) * 4;
+        let check_offset = data.len() - 12 - index_size - 8;
+        data[check_offset] ^= 0x01;
+        assert!(decompress(&data[..], &mut Vec::new()).is_err());
+    }
+}
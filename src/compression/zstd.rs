@@ -0,0 +1,911 @@
+//! This module implements the Zstandard decompression algorithm.
+//! Specification: https://www.rfc-editor.org/rfc/rfc8878
+//! A stream is made of frames, each made of blocks. The literals of a block
+//! are compressed with Huffman coding, and the sequences (literal length,
+//! match length, offset) with finite state entropy (FSE) coding.
+//! Dictionaries are not supported.
+
+use std::io::{Read, Write};
+use crate::checksum::Xxh64;
+use super::{ByteReader, DecompressionSummary};
+
+/// The magic number at the start of a frame
+const FRAME_MAGIC: u32 = 0xfd2fb528;
+
+/// The magic number of skippable frames, the 4 low bits are free
+const SKIPPABLE_FRAME_MAGIC: u32 = 0x184d2a50;
+
+/// The maximum size of a block, before and after decompression
+const MAX_BLOCK_SIZE: usize = 128 * 1024;
+
+/// The maximum length of a Huffman code of the literals
+const MAX_HUFFMAN_BITS: u32 = 11;
+
+/// The baseline and the number of extra bits of the literal length codes
+const LITERAL_LENGTH_CODES: [(u32, u32); 36] = [
+    (0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (5, 0), (6, 0), (7, 0),
+    (8, 0), (9, 0), (10, 0), (11, 0), (12, 0), (13, 0), (14, 0), (15, 0),
+    (16, 1), (18, 1), (20, 1), (22, 1), (24, 2), (28, 2), (32, 3), (40, 3),
+    (48, 4), (64, 6), (128, 7), (256, 8), (512, 9), (1024, 10), (2048, 11), (4096, 12),
+    (8192, 13), (16384, 14), (32768, 15), (65536, 16),
+];
+
+/// The baseline and the number of extra bits of the match length codes
+const MATCH_LENGTH_CODES: [(u32, u32); 53] = [
+    (3, 0), (4, 0), (5, 0), (6, 0), (7, 0), (8, 0), (9, 0), (10, 0),
+    (11, 0), (12, 0), (13, 0), (14, 0), (15, 0), (16, 0), (17, 0), (18, 0),
+    (19, 0), (20, 0), (21, 0), (22, 0), (23, 0), (24, 0), (25, 0), (26, 0),
+    (27, 0), (28, 0), (29, 0), (30, 0), (31, 0), (32, 0), (33, 0), (34, 0),
+    (35, 1), (37, 1), (39, 1), (41, 1), (43, 2), (47, 2), (51, 3), (59, 3),
+    (67, 4), (83, 4), (99, 5), (131, 7), (259, 8), (515, 9), (1027, 10), (2051, 11),
+    (4099, 12), (8195, 13), (16387, 14), (32771, 15), (65539, 16),
+];
+
+/// The number of offset codes, a code being the number of extra bits of the offset
+const OFFSET_CODE_COUNT: usize = 32;
+
+/// The predefined distribution of the literal length codes, with its accuracy log
+const LITERAL_LENGTH_DEFAULT_DISTRIBUTION: ([i16; 36], u32) = ([
+    4, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1,
+    2, 2, 2, 2, 2, 2, 2, 2, 2, 3, 2, 1, 1, 1, 1, 1,
+    -1, -1, -1, -1,
+], 6);
+
+/// The predefined distribution of the match length codes, with its accuracy log
+const MATCH_LENGTH_DEFAULT_DISTRIBUTION: ([i16; 53], u32) = ([
+    1, 4, 3, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1,
+    -1, -1, -1, -1, -1,
+], 6);
+
+/// The predefined distribution of the offset codes, with its accuracy log
+const OFFSET_DEFAULT_DISTRIBUTION: ([i16; 29], u32) = ([
+    1, 1, 1, 1, 1, 1, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1,
+], 5);
+
+/// Decompress a Zstandard stream read from input, and write the decompressed
+/// data to output.
+/// The content size and the checksum of the frames are checked when present.
+/// Concatenated frames are decompressed as a single stream, and skippable
+/// frames are ignored.
+/// The input may contain data after the end of the stream: the number of bytes
+/// belonging to the stream is returned. Since the input is read by chunks, more
+/// bytes than that may have been read from it.
+pub fn decompress<R: Read, W: Write>(input: R, output: &mut W) -> Result<DecompressionSummary, String> {
+    let mut reader = ByteReader::new(input);
+    let mut produced = 0;
+    let mut frame_number = 0;
+
+    loop {
+        let magic = reader.read_u32_le()?;
+        if magic & 0xfffffff0 == SKIPPABLE_FRAME_MAGIC {
+            let size = reader.read_u32_le()?;
+            for _ in 0..size {
+                reader.read_byte()?;
+            }
+        } else if magic == FRAME_MAGIC {
+            frame_number += 1;
+            produced += decompress_frame(&mut reader, output)
+                .map_err(|e| format!("frame {}: {}", frame_number, e))?;
+        } else {
+            return Err(format!("invalid frame magic number {:#010x} at byte {}", magic, reader.consumed() - 4));
+        }
+
+        // Another frame may follow
+        let next = reader.peek_bytes(4)?;
+        if next.len() < 4 {
+            break;
+        }
+        let magic = u32::from_le_bytes(next.try_into().unwrap());
+        if magic != FRAME_MAGIC && magic & 0xfffffff0 != SKIPPABLE_FRAME_MAGIC {
+            break;
+        }
+    }
+
+    output.flush()
+        .map_err(|e| format!("unable to write the decompressed data: {}", e))?;
+    Ok(DecompressionSummary {
+        consumed: reader.consumed(),
+        produced,
+    })
+}
+
+/// Decompress a frame, after its magic number, and return the number of bytes
+/// decompressed
+fn decompress_frame<R: Read, W: Write>(reader: &mut ByteReader<R>, output: &mut W) -> Result<u64, String> {
+    let descriptor = reader.read_byte()?;
+    if descriptor & 0x08 != 0 {
+        return Err(format!("reserved bit set in the frame header descriptor {:#04x}", descriptor));
+    }
+    let single_segment = descriptor & 0x20 != 0;
+    let has_checksum = descriptor & 0x04 != 0;
+
+    let mut window_size = 0;
+    if !single_segment {
+        let window_descriptor = reader.read_byte()?;
+        let base = 1u64 << (10 + (window_descriptor >> 3));
+        window_size = base + (base / 8) * (window_descriptor & 0x07) as u64;
+    }
+
+    let dictionary_id_size = [0, 1, 2, 4][(descriptor & 0x03) as usize];
+    let dictionary_id = read_le(reader, dictionary_id_size)?;
+    if dictionary_id != 0 {
+        return Err(format!("the frame needs the dictionary {}, dictionaries are not supported", dictionary_id));
+    }
+
+    let content_size_size = match descriptor >> 6 {
+        0 if single_segment => 1,
+        0 => 0,
+        1 => 2,
+        2 => 4,
+        _ => 8,
+    };
+    let content_size = match content_size_size {
+        0 => None,
+        2 => Some(read_le(reader, 2)? + 256),
+        size => Some(read_le(reader, size)?),
+    };
+    if single_segment {
+        window_size = content_size.unwrap_or(0);
+    }
+    let window_size = usize::try_from(window_size).unwrap_or(usize::MAX);
+    let max_block_size = window_size.min(MAX_BLOCK_SIZE);
+
+    let mut state = FrameState::new();
+    let mut history = Vec::new();
+    let mut checksum = Xxh64::new(0);
+    let mut produced = 0;
+    let mut block_number = 0;
+    loop {
+        block_number += 1;
+        let header = read_le(reader, 3)? as usize;
+        let last_block = header & 1 == 1;
+        let block_size = header >> 3;
+        if block_size > max_block_size {
+            return Err(format!("block {} of {} bytes is larger than the maximum of {} bytes", block_number, block_size, max_block_size));
+        }
+
+        let start = history.len();
+        match (header >> 1) & 0x03 {
+            0 => history.extend(reader.read_bytes(block_size)?),
+            1 => {
+                let byte = reader.read_byte()?;
+                history.resize(history.len() + block_size, byte);
+            },
+            2 => {
+                let block = reader.read_bytes(block_size)?;
+                decompress_block(&block, &mut state, &mut history, max_block_size)
+                    .map_err(|e| format!("block {}: {}", block_number, e))?;
+            },
+            _ => return Err(format!("block {} has the reserved type 3", block_number)),
+        }
+
+        output.write_all(&history[start..])
+            .map_err(|e| format!("unable to write the decompressed data: {}", e))?;
+        checksum.update(&history[start..]);
+        produced += (history.len() - start) as u64;
+
+        // Keep only the window, needed by the matches of the next blocks
+        if history.len() > window_size.saturating_mul(2).max(MAX_BLOCK_SIZE) {
+            history.drain(..history.len() - window_size);
+        }
+        if last_block {
+            break;
+        }
+    }
+
+    if let Some(content_size) = content_size {
+        if content_size != produced {
+            return Err(format!("content size of {} bytes announced, {} produced", content_size, produced));
+        }
+    }
+    if has_checksum {
+        let stored = reader.read_u32_le()?;
+        let computed = checksum.value() as u32;
+        if stored != computed {
+            return Err(format!("checksum mismatch: stored {:#010x}, computed {:#010x}", stored, computed));
+        }
+    }
+    Ok(produced)
+}
+
+/// The state kept from a block to the next in a frame
+struct FrameState {
+    /// The Huffman table of the previous literals, reused by treeless literals
+    huffman: Option<HuffmanTable>,
+    /// The FSE table of the literal lengths, reused by the repeat mode
+    literal_lengths: Option<FseTable>,
+    /// The FSE table of the offsets, reused by the repeat mode
+    offsets: Option<FseTable>,
+    /// The FSE table of the match lengths, reused by the repeat mode
+    match_lengths: Option<FseTable>,
+    /// The last 3 offsets, the most recent first
+    repeated_offsets: [usize; 3],
+}
+
+impl FrameState {
+    /// Create the state at the start of a frame
+    fn new() -> FrameState {
+        FrameState {
+            huffman: None,
+            literal_lengths: None,
+            offsets: None,
+            match_lengths: None,
+            repeated_offsets: [1, 4, 8],
+        }
+    }
+}
+
+/// A sequence: literals to copy, then a match
+struct Sequence {
+    /// The number of literals to copy
+    literal_length: usize,
+    /// The length of the match
+    match_length: usize,
+    /// The offset value, either an offset + 3 or a repeated offset from 1 to 3
+    offset_value: usize,
+}
+
+/// Decompress a compressed block, and add its content to history
+fn decompress_block(block: &[u8], state: &mut FrameState, history: &mut Vec<u8>, max_block_size: usize) -> Result<(), String> {
+    let (literals, literals_size) = read_literals(block, state)?;
+    let sequences = read_sequences(&block[literals_size..], state)?;
+
+    let start = history.len();
+    let mut literals = &literals[..];
+    for sequence in sequences {
+        if sequence.literal_length > literals.len() {
+            return Err(format!("sequence of {} literals, {} left", sequence.literal_length, literals.len()));
+        }
+        history.extend_from_slice(&literals[..sequence.literal_length]);
+        literals = &literals[sequence.literal_length..];
+
+        let offset = resolve_offset(&mut state.repeated_offsets, sequence.offset_value, sequence.literal_length)?;
+        if offset > history.len() {
+            return Err(format!("match offset {} goes before the start of the data", offset));
+        }
+        for _ in 0..sequence.match_length {
+            history.push(history[history.len() - offset]);
+        }
+    }
+    history.extend_from_slice(literals);
+
+    if history.len() - start > max_block_size {
+        return Err(format!("{} bytes decompressed, more than the maximum block size", history.len() - start));
+    }
+    Ok(())
+}
+
+/// Get the offset of a match from its offset value, and update the repeated
+/// offsets
+fn resolve_offset(repeated_offsets: &mut [usize; 3], offset_value: usize, literal_length: usize) -> Result<usize, String> {
+    if offset_value > 3 {
+        let offset = offset_value - 3;
+        *repeated_offsets = [offset, repeated_offsets[0], repeated_offsets[1]];
+        return Ok(offset);
+    }
+
+    // Without literals, the repeated offsets are shifted by one
+    let index = offset_value - if literal_length == 0 { 0 } else { 1 };
+    let [first, second, third] = *repeated_offsets;
+    let offset = match index {
+        0 => return Ok(first),
+        1 => second,
+        2 => third,
+        _ => first - 1,
+    };
+    if offset == 0 {
+        return Err("repeated offset of 0".to_string());
+    }
+    *repeated_offsets = if index == 1 { [second, first, third] } else { [offset, first, second] };
+    Ok(offset)
+}
+
+/// Read the literals section of a block.
+/// Returns the literals and the size of the section.
+fn read_literals(block: &[u8], state: &mut FrameState) -> Result<(Vec<u8>, usize), String> {
+    let header = *block.first().ok_or("empty block".to_string())?;
+    let literals_type = header & 0x03;
+    let size_format = (header >> 2) & 0x03;
+
+    // Raw and RLE literals
+    if literals_type < 2 {
+        let (header_size, size) = match size_format {
+            0 | 2 => (1, (header >> 3) as usize),
+            1 => (2, (read_le_slice(block, 2)? >> 4) as usize),
+            _ => (3, (read_le_slice(block, 3)? >> 4) as usize),
+        };
+        if size > MAX_BLOCK_SIZE {
+            return Err(format!("{} literals, more than the maximum block size", size));
+        }
+        if literals_type == 0 {
+            let literals = block.get(header_size..header_size + size)
+                .ok_or("truncated raw literals".to_string())?;
+            return Ok((literals.to_vec(), header_size + size));
+        }
+        let byte = *block.get(header_size).ok_or("truncated RLE literals".to_string())?;
+        return Ok((vec![byte; size], header_size + 1));
+    }
+
+    // Huffman compressed literals, in 1 or 4 streams
+    let (header_size, stream_count, size_bits) = match size_format {
+        0 => (3, 1, 10),
+        1 => (3, 4, 10),
+        2 => (4, 4, 14),
+        _ => (5, 4, 18),
+    };
+    let sizes = read_le_slice(block, header_size)? >> 4;
+    let size_mask = (1 << size_bits) - 1;
+    let regenerated_size = (sizes & size_mask) as usize;
+    let compressed_size = ((sizes >> size_bits) & size_mask) as usize;
+    if regenerated_size > MAX_BLOCK_SIZE {
+        return Err(format!("{} literals, more than the maximum block size", regenerated_size));
+    }
+    let mut data = block.get(header_size..header_size + compressed_size)
+        .ok_or("truncated compressed literals".to_string())?;
+
+    if literals_type == 2 {
+        let (table, table_size) = HuffmanTable::read(data)?;
+        state.huffman = Some(table);
+        data = &data[table_size..];
+    }
+    let table = state.huffman.as_ref()
+        .ok_or("treeless literals without previous Huffman table".to_string())?;
+
+    let mut literals = Vec::with_capacity(regenerated_size);
+    if stream_count == 1 {
+        table.decode_stream(data, regenerated_size, &mut literals)?;
+    } else {
+        // A jump table gives the size of the first 3 streams
+        if data.len() < 6 {
+            return Err("truncated jump table".to_string());
+        }
+        let mut stream_sizes: Vec<usize> = data[..6].chunks_exact(2)
+            .map(|size| u16::from_le_bytes([size[0], size[1]]) as usize)
+            .collect();
+        let data = &data[6..];
+        let first_streams_size: usize = stream_sizes.iter().sum();
+        if first_streams_size > data.len() {
+            return Err("the jump table points beyond the literals".to_string());
+        }
+        stream_sizes.push(data.len() - first_streams_size);
+
+        let stream_regenerated_size = regenerated_size.div_ceil(4);
+        if stream_regenerated_size * 3 > regenerated_size {
+            return Err(format!("{} literals can't be split in 4 streams", regenerated_size));
+        }
+        let mut position = 0;
+        for (index, stream_size) in stream_sizes.iter().enumerate() {
+            let count = if index < 3 { stream_regenerated_size } else { regenerated_size - 3 * stream_regenerated_size };
+            table.decode_stream(&data[position..position + stream_size], count, &mut literals)
+                .map_err(|e| format!("literals stream {}: {}", index + 1, e))?;
+            position += stream_size;
+        }
+    }
+    Ok((literals, header_size + compressed_size))
+}
+
+/// Read and decode the sequences section of a block
+fn read_sequences(data: &[u8], state: &mut FrameState) -> Result<Vec<Sequence>, String> {
+    let byte = |index: usize| data.get(index).map(|byte| *byte as usize)
+        .ok_or("truncated sequences section header".to_string());
+    let (sequence_count, mut position) = match byte(0)? {
+        0 => return Ok(Vec::new()),
+        count @ 1..=127 => (count, 1),
+        count @ 128..=254 => (((count - 128) << 8) + byte(1)?, 2),
+        _ => (byte(1)? + (byte(2)? << 8) + 0x7f00, 3),
+    };
+
+    let modes = byte(position)?;
+    position += 1;
+    if modes & 0x03 != 0 {
+        return Err(format!("reserved bits set in the compression modes {:#04x}", modes));
+    }
+    let literal_length_distribution = (&LITERAL_LENGTH_DEFAULT_DISTRIBUTION.0[..], LITERAL_LENGTH_DEFAULT_DISTRIBUTION.1);
+    read_sequence_table(data, &mut position, (modes >> 6) as u8, literal_length_distribution, LITERAL_LENGTH_CODES.len(), 9, &mut state.literal_lengths)
+        .map_err(|e| format!("literal lengths table: {}", e))?;
+    let offset_distribution = (&OFFSET_DEFAULT_DISTRIBUTION.0[..], OFFSET_DEFAULT_DISTRIBUTION.1);
+    read_sequence_table(data, &mut position, ((modes >> 4) & 0x03) as u8, offset_distribution, OFFSET_CODE_COUNT, 8, &mut state.offsets)
+        .map_err(|e| format!("offsets table: {}", e))?;
+    let match_length_distribution = (&MATCH_LENGTH_DEFAULT_DISTRIBUTION.0[..], MATCH_LENGTH_DEFAULT_DISTRIBUTION.1);
+    read_sequence_table(data, &mut position, ((modes >> 2) & 0x03) as u8, match_length_distribution, MATCH_LENGTH_CODES.len(), 9, &mut state.match_lengths)
+        .map_err(|e| format!("match lengths table: {}", e))?;
+
+    let literal_lengths = state.literal_lengths.as_ref().unwrap();
+    let offsets = state.offsets.as_ref().unwrap();
+    let match_lengths = state.match_lengths.as_ref().unwrap();
+    let mut reader = BackwardBitReader::new(&data[position..])?;
+    let mut literal_length_state = reader.read(literal_lengths.accuracy_log) as usize;
+    let mut offset_state = reader.read(offsets.accuracy_log) as usize;
+    let mut match_length_state = reader.read(match_lengths.accuracy_log) as usize;
+
+    let mut sequences = Vec::with_capacity(sequence_count);
+    for index in 0..sequence_count {
+        let offset_code = offsets.symbol(offset_state);
+        let match_length_code = match_lengths.symbol(match_length_state) as usize;
+        let literal_length_code = literal_lengths.symbol(literal_length_state) as usize;
+        if offset_code as usize >= OFFSET_CODE_COUNT || match_length_code >= MATCH_LENGTH_CODES.len() || literal_length_code >= LITERAL_LENGTH_CODES.len() {
+            return Err(format!("invalid code in the sequence {}", index + 1));
+        }
+
+        // The extra bits are read in this order: offset, match length, literal length
+        let offset_value = (1 << offset_code) + reader.read(offset_code as u32) as usize;
+        let (baseline, extra_bits) = MATCH_LENGTH_CODES[match_length_code];
+        let match_length = (baseline + reader.read(extra_bits) as u32) as usize;
+        let (baseline, extra_bits) = LITERAL_LENGTH_CODES[literal_length_code];
+        let literal_length = (baseline + reader.read(extra_bits) as u32) as usize;
+        sequences.push(Sequence {
+            literal_length,
+            match_length,
+            offset_value,
+        });
+
+        if index + 1 < sequence_count {
+            literal_length_state = literal_lengths.next_state(literal_length_state, &mut reader);
+            match_length_state = match_lengths.next_state(match_length_state, &mut reader);
+            offset_state = offsets.next_state(offset_state, &mut reader);
+        }
+    }
+    if !reader.is_finished() {
+        return Err("the sequences bitstream isn't fully consumed".to_string());
+    }
+    Ok(sequences)
+}
+
+/// Set the FSE table of a sequence field, according to its compression mode:
+/// predefined distribution, single symbol (RLE), FSE compressed distribution,
+/// or the table of the previous block
+fn read_sequence_table(data: &[u8], position: &mut usize, mode: u8, default_distribution: (&[i16], u32), symbol_count: usize, max_accuracy_log: u32, table: &mut Option<FseTable>) -> Result<(), String> {
+    match mode {
+        0 => *table = Some(FseTable::from_distribution(default_distribution.0, default_distribution.1)?),
+        1 => {
+            let symbol = *data.get(*position).ok_or("truncated RLE symbol".to_string())?;
+            if symbol as usize >= symbol_count {
+                return Err(format!("invalid RLE symbol {}", symbol));
+            }
+            *position += 1;
+            *table = Some(FseTable::rle(symbol));
+        },
+        2 => {
+            let (fse_table, size) = FseTable::read(&data[*position..], max_accuracy_log, symbol_count)?;
+            *position += size;
+            *table = Some(fse_table);
+        },
+        _ => {
+            if table.is_none() {
+                return Err("repeat mode without previous table".to_string());
+            }
+        },
+    }
+    Ok(())
+}
+
+/// A finite state entropy decoding table
+struct FseTable {
+    /// The number of bits of the states
+    accuracy_log: u32,
+    /// For each state: the symbol, the number of bits to read for the next
+    /// state, and the base of the next state
+    states: Vec<(u8, u32, usize)>,
+}
+
+impl FseTable {
+    /// Read a distribution from the start of data, and build its table.
+    /// Returns the table and the size of the distribution.
+    fn read(data: &[u8], max_accuracy_log: u32, max_symbols: usize) -> Result<(FseTable, usize), String> {
+        let mut reader = ForwardBitReader::new(data);
+        let accuracy_log = reader.read(4)? + 5;
+        if accuracy_log > max_accuracy_log {
+            return Err(format!("accuracy log {} greater than {}", accuracy_log, max_accuracy_log));
+        }
+
+        // The probabilities are stored with a variable number of bits,
+        // depending on the sum of the remaining probabilities
+        let mut remaining: i32 = 1 << accuracy_log;
+        let mut distribution: Vec<i16> = Vec::new();
+        while remaining > 0 {
+            if distribution.len() >= max_symbols {
+                return Err("too many symbols in the distribution".to_string());
+            }
+            let bits = 32 - (remaining as u32 + 1).leading_zeros();
+            let lower_mask = (1 << (bits - 1)) - 1;
+            let threshold = (1 << bits) - 1 - (remaining as u32 + 1);
+            let mut value = reader.read(bits)?;
+            if value & lower_mask < threshold {
+                reader.rewind(1);
+                value &= lower_mask;
+            } else if value > lower_mask {
+                value -= threshold;
+            }
+
+            let probability = value as i16 - 1;
+            remaining -= probability.abs() as i32;
+            distribution.push(probability);
+            // Zero probabilities are followed by the number of following zeros, on 2 bits
+            if probability == 0 {
+                loop {
+                    let repeat = reader.read(2)?;
+                    distribution.extend(std::iter::repeat_n(0, repeat as usize));
+                    if repeat != 3 {
+                        break;
+                    }
+                }
+            }
+        }
+        if remaining != 0 || distribution.len() > max_symbols {
+            return Err("invalid distribution".to_string());
+        }
+
+        Ok((FseTable::from_distribution(&distribution, accuracy_log)?, reader.bytes_consumed()))
+    }
+
+    /// Build the table of a distribution, -1 being a probability lower than 1
+    fn from_distribution(distribution: &[i16], accuracy_log: u32) -> Result<FseTable, String> {
+        let size = 1 << accuracy_log;
+        let mut symbols = vec![0u8; size];
+
+        // The symbols with a probability lower than 1 take the last states
+        let mut high_threshold = size;
+        for (symbol, probability) in distribution.iter().enumerate() {
+            if *probability == -1 {
+                high_threshold -= 1;
+                symbols[high_threshold] = symbol as u8;
+            }
+        }
+
+        // The other symbols are spread over the remaining states
+        let step = (size >> 1) + (size >> 3) + 3;
+        let mask = size - 1;
+        let mut position = 0;
+        for (symbol, probability) in distribution.iter().enumerate() {
+            for _ in 0..(*probability).max(0) {
+                symbols[position] = symbol as u8;
+                loop {
+                    position = (position + step) & mask;
+                    if position < high_threshold {
+                        break;
+                    }
+                }
+            }
+        }
+        if position != 0 {
+            return Err("invalid distribution".to_string());
+        }
+
+        let mut next_states: Vec<usize> = distribution.iter()
+            .map(|probability| if *probability == -1 { 1 } else { (*probability).max(0) as usize })
+            .collect();
+        let states = symbols.iter()
+            .map(|symbol| {
+                let next_state = next_states[*symbol as usize];
+                next_states[*symbol as usize] += 1;
+                let bits = accuracy_log - (usize::BITS - 1 - next_state.leading_zeros());
+                (*symbol, bits, (next_state << bits) - size)
+            })
+            .collect();
+        Ok(FseTable {
+            accuracy_log,
+            states,
+        })
+    }
+
+    /// Build the table of a single symbol
+    fn rle(symbol: u8) -> FseTable {
+        FseTable {
+            accuracy_log: 0,
+            states: vec![(symbol, 0, 0)],
+        }
+    }
+
+    /// The symbol of a state
+    fn symbol(&self, state: usize) -> u8 {
+        self.states[state].0
+    }
+
+    /// Read the next state
+    fn next_state(&self, state: usize, reader: &mut BackwardBitReader) -> usize {
+        let (_, bits, base) = self.states[state];
+        base + reader.read(bits) as usize
+    }
+}
+
+/// A Huffman decoding table, indexed by the next max_bits bits of the stream
+struct HuffmanTable {
+    /// The length of the longest code
+    max_bits: u32,
+    /// For each value of the next max_bits bits, the symbol and the length of its code
+    entries: Vec<(u8, u32)>,
+}
+
+impl HuffmanTable {
+    /// Read the description of a Huffman table: the weights of the symbols,
+    /// either FSE compressed or stored on 4 bits.
+    /// Returns the table and the size of the description.
+    fn read(data: &[u8]) -> Result<(HuffmanTable, usize), String> {
+        let header = *data.first().ok_or("truncated Huffman table".to_string())? as usize;
+        let mut weights = Vec::new();
+        let size;
+        if header < 128 {
+            size = 1 + header;
+            let data = data.get(1..size).ok_or("truncated Huffman table".to_string())?;
+            let (table, table_size) = FseTable::read(data, 6, 256)?;
+            let mut reader = BackwardBitReader::new(&data[table_size..])?;
+
+            // Two interleaved states share the same table
+            let mut states = [reader.read(table.accuracy_log) as usize, reader.read(table.accuracy_log) as usize];
+            'decoding: loop {
+                for index in 0..2 {
+                    weights.push(table.symbol(states[index]));
+                    states[index] = table.next_state(states[index], &mut reader);
+                    if reader.is_overflowed() {
+                        weights.push(table.symbol(states[1 - index]));
+                        break 'decoding;
+                    }
+                }
+                if weights.len() > 255 {
+                    return Err("too many Huffman weights".to_string());
+                }
+            }
+        } else {
+            let count = header - 127;
+            size = 1 + count.div_ceil(2);
+            let data = data.get(1..size).ok_or("truncated Huffman table".to_string())?;
+            for byte in data {
+                weights.extend([byte >> 4, byte & 0x0f]);
+            }
+            weights.truncate(count);
+        }
+        Ok((HuffmanTable::from_weights(&mut weights)?, size))
+    }
+
+    /// Build the table from the weights of the symbols but the last, whose
+    /// weight is deduced
+    fn from_weights(weights: &mut Vec<u8>) -> Result<HuffmanTable, String> {
+        if weights.len() > 255 || weights.iter().any(|weight| *weight as u32 > MAX_HUFFMAN_BITS) {
+            return Err("invalid Huffman weights".to_string());
+        }
+        let sum: u32 = weights.iter()
+            .filter(|weight| **weight > 0)
+            .map(|weight| 1 << (weight - 1))
+            .sum();
+        if sum == 0 {
+            return Err("no Huffman weight".to_string());
+        }
+        let max_bits = 32 - sum.leading_zeros();
+        let left = (1 << max_bits) - sum;
+        if !left.is_power_of_two() || max_bits > MAX_HUFFMAN_BITS {
+            return Err("invalid Huffman weights".to_string());
+        }
+        weights.push(left.trailing_zeros() as u8 + 1);
+
+        // The codes are assigned by increasing weight, then increasing symbol.
+        // The longest codes come first in the table.
+        let bits: Vec<u32> = weights.iter()
+            .map(|weight| if *weight > 0 { max_bits + 1 - *weight as u32 } else { 0 })
+            .collect();
+        let mut next_index = [0usize; MAX_HUFFMAN_BITS as usize + 2];
+        for length in (1..=max_bits as usize).rev() {
+            let count = bits.iter().filter(|bits| **bits as usize == length).count();
+            next_index[length - 1] = next_index[length] + count * (1 << (max_bits as usize - length));
+        }
+        let mut entries = vec![(0u8, 0u32); 1 << max_bits];
+        for (symbol, length) in bits.iter().enumerate() {
+            if *length == 0 {
+                continue;
+            }
+            let start = next_index[*length as usize];
+            let count = 1 << (max_bits - length);
+            entries[start..start + count].fill((symbol as u8, *length));
+            next_index[*length as usize] += count;
+        }
+        Ok(HuffmanTable {
+            max_bits,
+            entries,
+        })
+    }
+
+    /// Decode count literals from a stream
+    fn decode_stream(&self, data: &[u8], count: usize, literals: &mut Vec<u8>) -> Result<(), String> {
+        let mut reader = BackwardBitReader::new(data)?;
+        for _ in 0..count {
+            let (symbol, bits) = self.entries[reader.peek(self.max_bits) as usize];
+            reader.consume(bits);
+            literals.push(symbol);
+        }
+        if !reader.is_finished() {
+            return Err("the Huffman stream isn't fully consumed".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Reads a bitstream from its end to its start, the last byte ending with a
+/// padding made of a 1 followed by zeros.
+/// Reading beyond the start gives zeros, which is detected with is_overflowed().
+struct BackwardBitReader<'a> {
+    /// The bitstream
+    data: &'a [u8],
+    /// The number of bits not read yet, negative after an overflow
+    position: i64,
+}
+
+impl<'a> BackwardBitReader<'a> {
+    /// Create a reader of data, after its padding
+    fn new(data: &'a [u8]) -> Result<BackwardBitReader<'a>, String> {
+        let last = *data.last().ok_or("empty bitstream".to_string())?;
+        if last == 0 {
+            return Err("invalid bitstream padding".to_string());
+        }
+        Ok(BackwardBitReader {
+            data,
+            position: 8 * data.len() as i64 - last.leading_zeros() as i64 - 1,
+        })
+    }
+
+    /// Get the next count bits, up to 32, without consuming them
+    fn peek(&self, count: u32) -> u64 {
+        // Once overflowed, only zeros are left. Don't shift them by the
+        // overflow, which can be 64 bits or more.
+        if count == 0 || self.position <= 0 {
+            return 0;
+        }
+        let start = self.position - count as i64;
+        if start < 0 {
+            // Only the bits before the position are real
+            return self.bits_at(0, self.position as u32) << -start;
+        }
+        self.bits_at(start as usize, count)
+    }
+
+    /// Get count bits starting at the given bit, least significant first
+    fn bits_at(&self, start: usize, count: u32) -> u64 {
+        if count == 0 {
+            return 0;
+        }
+        let mut bytes = [0u8; 8];
+        let first = start / 8;
+        let last = (first + 8).min(self.data.len());
+        bytes[..last - first].copy_from_slice(&self.data[first..last]);
+        (u64::from_le_bytes(bytes) >> (start % 8)) & ((1 << count) - 1)
+    }
+
+    /// Drop count bits
+    fn consume(&mut self, count: u32) {
+        self.position -= count as i64;
+    }
+
+    /// Read and consume count bits, up to 32
+    fn read(&mut self, count: u32) -> u64 {
+        let bits = self.peek(count);
+        self.consume(count);
+        bits
+    }
+
+    /// Whether more bits were read than available
+    fn is_overflowed(&self) -> bool {
+        self.position < 0
+    }
+
+    /// Whether all the bits were read exactly
+    fn is_finished(&self) -> bool {
+        self.position == 0
+    }
+}
+
+/// Reads a bitstream from its start, least significant bit first
+struct ForwardBitReader<'a> {
+    /// The bitstream
+    data: &'a [u8],
+    /// The number of bits read
+    position: usize,
+}
+
+impl<'a> ForwardBitReader<'a> {
+    /// Create a reader of data
+    fn new(data: &'a [u8]) -> ForwardBitReader<'a> {
+        ForwardBitReader {
+            data,
+            position: 0,
+        }
+    }
+
+    /// Read and consume count bits, up to 16
+    fn read(&mut self, count: u32) -> Result<u32, String> {
+        let mut value = 0;
+        for index in 0..count {
+            let byte = self.data.get(self.position / 8)
+                .ok_or("truncated FSE table description".to_string())?;
+            value |= (((byte >> (self.position % 8)) & 1) as u32) << index;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+
+    /// Go back count bits
+    fn rewind(&mut self, count: u32) {
+        self.position -= count as usize;
+    }
+
+    /// The number of bytes read, including the partially read byte
+    fn bytes_consumed(&self) -> usize {
+        self.position.div_ceil(8)
+    }
+}
+
+/// Read a little-endian value of size bytes, up to 8
+fn read_le<R: Read>(reader: &mut ByteReader<R>, size: usize) -> Result<u64, String> {
+    read_le_slice(&reader.read_bytes(size)?, size)
+}
+
+/// Read a little-endian value from the first size bytes of data, up to 8
+fn read_le_slice(data: &[u8], size: usize) -> Result<u64, String> {
+    let bytes = data.get(..size).ok_or("truncated header".to_string())?;
+    Ok(bytes.iter().rev().fold(0, |value, byte| (value << 8) | *byte as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::{assert_stops_at_end_of_stream, decompress_all, runs, squares};
+
+    // The fixtures were created with the zstd CLI 1.5.7
+    const SQUARES: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares.zst"));
+    const RUNS: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/runs.zst"));
+
+    #[test]
+    fn decompresses_frame_with_checksum() {
+        // Compressed with the level 19, with the content size and a checksum
+        assert_eq!(decompress_all(SQUARES, decompress).unwrap(), squares());
+    }
+
+    #[test]
+    fn decompresses_frame_without_content_size() {
+        // Compressed from the standard input, so without the content size
+        let data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/squares_stream.zst"));
+        assert_eq!(decompress_all(data, decompress).unwrap(), squares());
+    }
+
+    #[test]
+    fn decompresses_frame_without_checksum() {
+        assert_eq!(decompress_all(RUNS, decompress).unwrap(), runs());
+    }
+
+    #[test]
+    fn decompresses_several_blocks() {
+        // 300 000 bytes, more than the maximum size of a block
+        let data = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/digits.zst"));
+        assert_eq!(decompress_all(data, decompress).unwrap(), b"0123456789".repeat(30000));
+    }
+
+    #[test]
+    fn decompresses_concatenated_and_skippable_frames() {
+        let mut skippable_frame = (SKIPPABLE_FRAME_MAGIC + 5).to_le_bytes().to_vec();
+        skippable_frame.extend_from_slice(&3u32.to_le_bytes());
+        skippable_frame.extend_from_slice(b"abc");
+        let data = [SQUARES, &skippable_frame, RUNS].concat();
+        assert_eq!(decompress_all(&data, decompress).unwrap(), [squares(), runs()].concat());
+    }
+
+    #[test]
+    fn counts_only_the_bytes_of_the_stream() {
+        assert_stops_at_end_of_stream(SQUARES, decompress);
+    }
+
+    #[test]
+    fn rejects_wrong_checksum() {
+        let mut data = SQUARES.to_vec();
+        *data.last_mut().unwrap() ^= 0x01;
+        assert!(decompress(&data[..], &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn reports_damaged_frames_without_panicking() {
+        for position in 0..SQUARES.len() {
+            let mut data = SQUARES.to_vec();
+            data[position] ^= 0x55;
+            let _ = decompress(&data[..], &mut Vec::new());
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! This module provides the AES block cipher (FIPS 197), with keys of 128,
+//! 192 or 256 bits.
+//! Only the encryption is implemented, since the counter mode used by ZIP
+//! archives only needs it, even to decrypt.
+
+/// The size of an AES block, in bytes
+pub const BLOCK_SIZE: usize = 16;
+
+/// The substitution box, used by SubBytes and by the key expansion
+const SBOX: [u8; 256] = sbox();
+
+/// Build the substitution box at compile time.
+/// Each byte is replaced by its multiplicative inverse in GF(2^8), followed
+/// by an affine transformation. The inverses are found by walking the field
+/// with the generator 3 and its inverse at the same time.
+const fn sbox() -> [u8; 256] {
+    let mut sbox = [0u8; 256];
+    let mut p: u8 = 1;
+    let mut q: u8 = 1;
+    loop {
+        // Multiply p by 3
+        p = p ^ (p << 1) ^ if p & 0x80 != 0 { 0x1b } else { 0 };
+        // Divide q by 3
+        q ^= q << 1;
+        q ^= q << 2;
+        q ^= q << 4;
+        if q & 0x80 != 0 {
+            q ^= 0x09;
+        }
+        let affine = q ^ q.rotate_left(1) ^ q.rotate_left(2) ^ q.rotate_left(3) ^ q.rotate_left(4);
+        sbox[p as usize] = affine ^ 0x63;
+        if p == 1 {
+            break;
+        }
+    }
+    // 0 has no inverse
+    sbox[0] = 0x63;
+    sbox
+}
+
+/// Multiply a byte by 2 in GF(2^8)
+fn xtime(byte: u8) -> u8 {
+    (byte << 1) ^ if byte & 0x80 != 0 { 0x1b } else { 0 }
+}
+
+/// The AES block cipher, with its expanded key
+pub struct Aes {
+    /// The round keys, one more than the number of rounds
+    round_keys: Vec<[u8; BLOCK_SIZE]>,
+}
+
+impl Aes {
+    /// Expand a key of 16, 24 or 32 bytes
+    pub fn new(key: &[u8]) -> Result<Aes, String> {
+        if ![16, 24, 32].contains(&key.len()) {
+            return Err(format!("invalid AES key size: {} bytes", key.len()));
+        }
+        let key_words = key.len() / 4;
+        let rounds = key_words + 6;
+
+        let mut words: Vec<[u8; 4]> = key.chunks_exact(4)
+            .map(|chunk| chunk.try_into().unwrap())
+            .collect();
+        let mut round_constant = 1;
+        for i in key_words..4 * (rounds + 1) {
+            let mut word = words[i - 1];
+            if i % key_words == 0 {
+                word.rotate_left(1);
+                word = word.map(|byte| SBOX[byte as usize]);
+                word[0] ^= round_constant;
+                round_constant = xtime(round_constant);
+            } else if key_words > 6 && i % key_words == 4 {
+                word = word.map(|byte| SBOX[byte as usize]);
+            }
+            let previous = words[i - key_words];
+            words.push([0, 1, 2, 3].map(|j| previous[j] ^ word[j]));
+        }
+
+        let round_keys = words.chunks_exact(4)
+            .map(|chunk| {
+                let mut round_key = [0u8; BLOCK_SIZE];
+                for (j, word) in chunk.iter().enumerate() {
+                    round_key[4 * j..4 * j + 4].copy_from_slice(word);
+                }
+                round_key
+            })
+            .collect();
+        Ok(Aes { round_keys })
+    }
+
+    /// Encrypt a block in place.
+    /// The state is stored column by column, as the bytes of the block.
+    pub fn encrypt_block(&self, block: &mut [u8; BLOCK_SIZE]) {
+        let rounds = self.round_keys.len() - 1;
+        add_round_key(block, &self.round_keys[0]);
+        for round in 1..=rounds {
+            for byte in block.iter_mut() {
+                *byte = SBOX[*byte as usize];
+            }
+            shift_rows(block);
+            if round != rounds {
+                mix_columns(block);
+            }
+            add_round_key(block, &self.round_keys[round]);
+        }
+    }
+}
+
+/// XOR the state with a round key
+fn add_round_key(state: &mut [u8; BLOCK_SIZE], round_key: &[u8; BLOCK_SIZE]) {
+    for (byte, key_byte) in state.iter_mut().zip(round_key) {
+        *byte ^= key_byte;
+    }
+}
+
+/// Shift the row r of the state of r bytes to the left
+fn shift_rows(state: &mut [u8; BLOCK_SIZE]) {
+    let previous = *state;
+    for row in 1..4 {
+        for column in 0..4 {
+            state[row + 4 * column] = previous[row + 4 * ((column + row) % 4)];
+        }
+    }
+}
+
+/// Mix the bytes of each column of the state
+fn mix_columns(state: &mut [u8; BLOCK_SIZE]) {
+    for column in state.chunks_exact_mut(4) {
+        let [a0, a1, a2, a3] = [column[0], column[1], column[2], column[3]];
+        let all = a0 ^ a1 ^ a2 ^ a3;
+        column[0] ^= all ^ xtime(a0 ^ a1);
+        column[1] ^= all ^ xtime(a1 ^ a2);
+        column[2] ^= all ^ xtime(a2 ^ a3);
+        column[3] ^= all ^ xtime(a3 ^ a0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::from_hex;
+
+    /// Encrypt the plaintext of the FIPS-197 appendix C examples with the key
+    /// 000102...
+    fn encrypt_fips_197_example(key_size: u8) -> Vec<u8> {
+        let key: Vec<u8> = (0..key_size).collect();
+        let mut block: [u8; BLOCK_SIZE] = from_hex("00112233445566778899aabbccddeeff").try_into().unwrap();
+        Aes::new(&key).unwrap().encrypt_block(&mut block);
+        block.to_vec()
+    }
+
+    #[test]
+    fn encrypts_fips_197_aes_128_example() {
+        assert_eq!(encrypt_fips_197_example(16), from_hex("69c4e0d86a7b0430d8cdb78070b4c55a"));
+    }
+
+    #[test]
+    fn encrypts_fips_197_aes_192_example() {
+        assert_eq!(encrypt_fips_197_example(24), from_hex("dda97ca4864cdfe06eaf70a0ec0d7191"));
+    }
+
+    #[test]
+    fn encrypts_fips_197_aes_256_example() {
+        assert_eq!(encrypt_fips_197_example(32), from_hex("8ea2b7ca516745bfeafc49904b496089"));
+    }
+
+    #[test]
+    fn rejects_invalid_key_size() {
+        assert!(Aes::new(&[0; 20]).is_err());
+    }
+}
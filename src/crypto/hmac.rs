@@ -0,0 +1,100 @@
+//! This module provides the HMAC message authentication code (RFC 2104),
+//! with SHA-1 as hash function
+
+use super::sha1::{self, Sha1, BLOCK_SIZE, DIGEST_SIZE};
+
+/// Computes a HMAC-SHA1 incrementally, for data read in several chunks.
+/// It can be cloned once the key is set, to authenticate several messages
+/// with the same key without hashing the key again.
+#[derive(Clone)]
+pub struct HmacSha1 {
+    /// The hash of the inner padded key and of the data added so far
+    inner: Sha1,
+    /// The hash of the outer padded key
+    outer: Sha1,
+}
+
+impl HmacSha1 {
+    /// Create a new HMAC-SHA1 computation with a key
+    pub fn new(key: &[u8]) -> HmacSha1 {
+        // Keys longer than a block are hashed first
+        let mut block_key = [0u8; BLOCK_SIZE];
+        if key.len() > BLOCK_SIZE {
+            block_key[..DIGEST_SIZE].copy_from_slice(&sha1::sha1(key));
+        } else {
+            block_key[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner = Sha1::new();
+        inner.update(&block_key.map(|byte| byte ^ 0x36));
+        let mut outer = Sha1::new();
+        outer.update(&block_key.map(|byte| byte ^ 0x5c));
+        HmacSha1 { inner, outer }
+    }
+
+    /// Add data to the computation
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    /// Get the authentication code of the data added
+    pub fn finalize(self) -> [u8; DIGEST_SIZE] {
+        let mut outer = self.outer;
+        outer.update(&self.inner.finalize());
+        outer.finalize()
+    }
+}
+
+/// Computes the HMAC-SHA1 of some data
+pub fn hmac_sha1(key: &[u8], data: &[u8]) -> [u8; DIGEST_SIZE] {
+    let mut hmac = HmacSha1::new(key);
+    hmac.update(data);
+    hmac.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::from_hex;
+
+    // Test cases of RFC 2202, section 3
+
+    #[test]
+    fn authenticates_rfc_2202_test_case_1() {
+        assert_eq!(hmac_sha1(&[0x0b; 20], b"Hi There").to_vec(), from_hex("b617318655057264e28bc0b6fb378c8ef146be00"));
+    }
+
+    #[test]
+    fn authenticates_rfc_2202_test_case_2() {
+        assert_eq!(hmac_sha1(b"Jefe", b"what do ya want for nothing?").to_vec(), from_hex("effcdf6ae5eb2fa2d27416d5f184df9c259a7c79"));
+    }
+
+    #[test]
+    fn authenticates_rfc_2202_test_case_3() {
+        assert_eq!(hmac_sha1(&[0xaa; 20], &[0xdd; 50]).to_vec(), from_hex("125d7342b9ac11cd91a39af48aa17b4f63f175d3"));
+    }
+
+    #[test]
+    fn authenticates_rfc_2202_test_case_4() {
+        let key: Vec<u8> = (1..=25).collect();
+        assert_eq!(hmac_sha1(&key, &[0xcd; 50]).to_vec(), from_hex("4c9007f4026250c6bc8414f9bf50c86c2d7235da"));
+    }
+
+    #[test]
+    fn authenticates_rfc_2202_test_case_5() {
+        let code = hmac_sha1(&[0x0c; 20], b"Test With Truncation");
+        assert_eq!(code[..12].to_vec(), from_hex("4c1a03424b55e07fe7f27be1"));
+    }
+
+    #[test]
+    fn authenticates_rfc_2202_test_case_6() {
+        let code = hmac_sha1(&[0xaa; 80], b"Test Using Larger Than Block-Size Key - Hash Key First");
+        assert_eq!(code.to_vec(), from_hex("aa4ae5e15272d00e95705637ce8a3b55ed402112"));
+    }
+
+    #[test]
+    fn authenticates_rfc_2202_test_case_7() {
+        let code = hmac_sha1(&[0xaa; 80], b"Test Using Larger Than Block-Size Key and Larger Than One Block-Size Data");
+        assert_eq!(code.to_vec(), from_hex("e8e99d0f45237d786d6bbaa7965c7808bbff1a91"));
+    }
+}
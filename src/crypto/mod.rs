@@ -0,0 +1,10 @@
+//! This module contains the cryptographic primitives used by encrypted archives.
+//! They are implemented in the crate, without external dependencies, and are
+//! meant to read archives, not to protect secrets: no care is taken against
+//! side channels.
+
+pub mod aes;
+pub mod hmac;
+pub mod pbkdf2;
+pub mod sha1;
+pub mod sha256;
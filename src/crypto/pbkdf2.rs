@@ -0,0 +1,68 @@
+//! This module provides the PBKDF2 key derivation function (RFC 8018),
+//! with HMAC-SHA1 as pseudorandom function
+
+use super::hmac::HmacSha1;
+
+/// Derive a key of the given length from a password and a salt
+pub fn pbkdf2_hmac_sha1(password: &[u8], salt: &[u8], iterations: u32, length: usize) -> Vec<u8> {
+    let keyed = HmacSha1::new(password);
+    let mut key = Vec::with_capacity(length);
+
+    let mut block_index: u32 = 1;
+    while key.len() < length {
+        let mut hmac = keyed.clone();
+        hmac.update(salt);
+        hmac.update(&block_index.to_be_bytes());
+        let mut value = hmac.finalize();
+        let mut block = value;
+
+        for _ in 1..iterations {
+            let mut hmac = keyed.clone();
+            hmac.update(&value);
+            value = hmac.finalize();
+            for (byte, value_byte) in block.iter_mut().zip(value) {
+                *byte ^= value_byte;
+            }
+        }
+
+        let missing = (length - key.len()).min(block.len());
+        key.extend_from_slice(&block[..missing]);
+        block_index += 1;
+    }
+
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::from_hex;
+
+    // Test vectors of RFC 6070
+
+    #[test]
+    fn derives_rfc_6070_one_iteration() {
+        assert_eq!(pbkdf2_hmac_sha1(b"password", b"salt", 1, 20), from_hex("0c60c80f961f0e71f3a9b524af6012062fe037a6"));
+    }
+
+    #[test]
+    fn derives_rfc_6070_two_iterations() {
+        assert_eq!(pbkdf2_hmac_sha1(b"password", b"salt", 2, 20), from_hex("ea6c014dc72d6f8ccd1ed92ace1d41f0d8de8957"));
+    }
+
+    #[test]
+    fn derives_rfc_6070_4096_iterations() {
+        assert_eq!(pbkdf2_hmac_sha1(b"password", b"salt", 4096, 20), from_hex("4b007901b765489abead49d926f721d065a429c1"));
+    }
+
+    #[test]
+    fn derives_rfc_6070_key_longer_than_a_digest() {
+        let key = pbkdf2_hmac_sha1(b"passwordPASSWORDpassword", b"saltSALTsaltSALTsaltSALTsaltSALTsalt", 4096, 25);
+        assert_eq!(key, from_hex("3d2eec4fe41c849b80c8d83662c0e44a8b291a964cf2f07038"));
+    }
+
+    #[test]
+    fn derives_rfc_6070_key_shorter_than_a_digest() {
+        assert_eq!(pbkdf2_hmac_sha1(b"pass\0word", b"sa\0lt", 4096, 16), from_hex("56fa6aa75548099dcc37d7f03425e0c3"));
+    }
+}
@@ -0,0 +1,166 @@
+//! This module provides the SHA-1 hash function (FIPS 180-4)
+
+/// The size of a SHA-1 digest, in bytes
+pub const DIGEST_SIZE: usize = 20;
+
+/// The size of the blocks processed by SHA-1, in bytes
+pub const BLOCK_SIZE: usize = 64;
+
+/// Computes a SHA-1 digest incrementally, for data read in several chunks
+#[derive(Clone)]
+pub struct Sha1 {
+    /// The current hash value
+    state: [u32; 5],
+    /// The bytes not processed yet, less than a block
+    buffer: Vec<u8>,
+    /// The number of bytes added so far
+    length: u64,
+}
+
+impl Sha1 {
+    /// Create a new SHA-1 computation
+    pub fn new() -> Sha1 {
+        Sha1 {
+            state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0],
+            buffer: Vec::with_capacity(BLOCK_SIZE),
+            length: 0,
+        }
+    }
+
+    /// Add data to the computation
+    pub fn update(&mut self, data: &[u8]) {
+        self.length += data.len() as u64;
+        let mut data = data;
+
+        // Complete the block started by the previous call
+        if !self.buffer.is_empty() {
+            let missing = (BLOCK_SIZE - self.buffer.len()).min(data.len());
+            self.buffer.extend_from_slice(&data[..missing]);
+            data = &data[missing..];
+            if self.buffer.len() < BLOCK_SIZE {
+                return;
+            }
+            let block: [u8; BLOCK_SIZE] = self.buffer[..].try_into().unwrap();
+            self.compress(&block);
+            self.buffer.clear();
+        }
+
+        let mut blocks = data.chunks_exact(BLOCK_SIZE);
+        for block in &mut blocks {
+            self.compress(block.try_into().unwrap());
+        }
+        self.buffer.extend_from_slice(blocks.remainder());
+    }
+
+    /// Get the digest of the data added
+    pub fn finalize(mut self) -> [u8; DIGEST_SIZE] {
+        // Pad with a 1 bit, zeros, then the length in bits, to a multiple of the block size
+        let bit_length = self.length.wrapping_mul(8);
+        let padding_length = if self.buffer.len() < 56 { 56 - self.buffer.len() } else { 120 - self.buffer.len() };
+        let mut padding = vec![0u8; padding_length + 8];
+        padding[0] = 0x80;
+        padding[padding_length..].copy_from_slice(&bit_length.to_be_bytes());
+        self.update(&padding);
+
+        let mut digest = [0u8; DIGEST_SIZE];
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    /// Process a block of data
+    fn compress(&mut self, block: &[u8; BLOCK_SIZE]) {
+        let mut schedule = [0u32; 80];
+        for (word, chunk) in schedule.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_be_bytes(chunk.try_into().unwrap());
+        }
+        for i in 16..80 {
+            schedule[i] = (schedule[i - 3] ^ schedule[i - 8] ^ schedule[i - 14] ^ schedule[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = self.state;
+        for (i, word) in schedule.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let temp = a.rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+}
+
+impl Default for Sha1 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Computes the SHA-1 digest of some data
+pub fn sha1(data: &[u8]) -> [u8; DIGEST_SIZE] {
+    let mut sha1 = Sha1::new();
+    sha1.update(data);
+    sha1.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::from_hex;
+
+    // Test cases of RFC 3174, section 7.3
+
+    #[test]
+    fn hashes_rfc_3174_test_1() {
+        assert_eq!(sha1(b"abc").to_vec(), from_hex("a9993e364706816aba3e25717850c26c9cd0d89d"));
+    }
+
+    #[test]
+    fn hashes_rfc_3174_test_2() {
+        let data = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+        assert_eq!(sha1(data).to_vec(), from_hex("84983e441c3bd26ebaae4aa1f95129e5e54670f1"));
+    }
+
+    #[test]
+    fn hashes_rfc_3174_test_3() {
+        let mut hasher = Sha1::new();
+        for _ in 0..1000 {
+            hasher.update(&[b'a'; 1000]);
+        }
+        assert_eq!(hasher.finalize().to_vec(), from_hex("34aa973cd4c4daa4f61eeb2bdbad27316534016f"));
+    }
+
+    #[test]
+    fn hashes_rfc_3174_test_4() {
+        let mut hasher = Sha1::new();
+        for _ in 0..80 {
+            hasher.update(b"01234567");
+        }
+        assert_eq!(hasher.finalize().to_vec(), from_hex("dea356a2cddd90c7a7ecedc5ebb563934f460452"));
+    }
+
+    #[test]
+    fn hashes_the_same_data_given_in_parts() {
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let mut hasher = Sha1::new();
+        for part in data.chunks(37) {
+            hasher.update(part);
+        }
+        assert_eq!(hasher.finalize(), sha1(&data));
+    }
+}
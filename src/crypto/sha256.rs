@@ -0,0 +1,139 @@
+//! This module provides the SHA-256 hash function (FIPS 180-4)
+
+/// The size of a SHA-256 digest, in bytes
+pub const DIGEST_SIZE: usize = 32;
+
+/// The size of the blocks processed by SHA-256, in bytes
+pub const BLOCK_SIZE: usize = 64;
+
+/// The round constants, the first 32 bits of the fractional parts of the cube
+/// roots of the first 64 primes
+const ROUND_CONSTANTS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// Computes a SHA-256 digest incrementally, for data read in several chunks
+#[derive(Clone)]
+pub struct Sha256 {
+    /// The current hash value
+    state: [u32; 8],
+    /// The bytes not processed yet, less than a block
+    buffer: Vec<u8>,
+    /// The number of bytes added so far
+    length: u64,
+}
+
+impl Sha256 {
+    /// Create a new SHA-256 computation
+    pub fn new() -> Sha256 {
+        Sha256 {
+            state: [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19],
+            buffer: Vec::with_capacity(BLOCK_SIZE),
+            length: 0,
+        }
+    }
+
+    /// Add data to the computation
+    pub fn update(&mut self, data: &[u8]) {
+        self.length += data.len() as u64;
+        let mut data = data;
+
+        // Complete the block started by the previous call
+        if !self.buffer.is_empty() {
+            let missing = (BLOCK_SIZE - self.buffer.len()).min(data.len());
+            self.buffer.extend_from_slice(&data[..missing]);
+            data = &data[missing..];
+            if self.buffer.len() < BLOCK_SIZE {
+                return;
+            }
+            let block: [u8; BLOCK_SIZE] = self.buffer[..].try_into().unwrap();
+            self.compress(&block);
+            self.buffer.clear();
+        }
+
+        let mut blocks = data.chunks_exact(BLOCK_SIZE);
+        for block in &mut blocks {
+            self.compress(block.try_into().unwrap());
+        }
+        self.buffer.extend_from_slice(blocks.remainder());
+    }
+
+    /// Get the digest of the data added
+    pub fn finalize(mut self) -> [u8; DIGEST_SIZE] {
+        // Same padding as SHA-1
+        let bit_length = self.length.wrapping_mul(8);
+        let padding_length = if self.buffer.len() < 56 { 56 - self.buffer.len() } else { 120 - self.buffer.len() };
+        let mut padding = vec![0u8; padding_length + 8];
+        padding[0] = 0x80;
+        padding[padding_length..].copy_from_slice(&bit_length.to_be_bytes());
+        self.update(&padding);
+
+        let mut digest = [0u8; DIGEST_SIZE];
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    /// Process a block of data
+    fn compress(&mut self, block: &[u8; BLOCK_SIZE]) {
+        let mut schedule = [0u32; 64];
+        for (word, chunk) in schedule.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_be_bytes(chunk.try_into().unwrap());
+        }
+        for i in 16..64 {
+            let s0 = schedule[i - 15].rotate_right(7) ^ schedule[i - 15].rotate_right(18) ^ (schedule[i - 15] >> 3);
+            let s1 = schedule[i - 2].rotate_right(17) ^ schedule[i - 2].rotate_right(19) ^ (schedule[i - 2] >> 10);
+            schedule[i] = schedule[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(schedule[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for (word, constant) in schedule.iter().zip(ROUND_CONSTANTS) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let temp1 = h
+                .wrapping_add(s1)
+                .wrapping_add(choice)
+                .wrapping_add(constant)
+                .wrapping_add(*word);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(majority);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(temp1);
+            d = c;
+            c = b;
+            b = a;
+            a = temp1.wrapping_add(temp2);
+        }
+
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Computes the SHA-256 digest of some data
+pub fn sha256(data: &[u8]) -> [u8; DIGEST_SIZE] {
+    let mut sha256 = Sha256::new();
+    sha256.update(data);
+    sha256.finalize()
+}
@@ -1,5 +1,10 @@
 //! This module contains error types used in the application
 
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
 /// An error used when reading a number from bytes
 #[derive(Debug)]
 pub enum ReadNumberFromBytesError {
@@ -7,4 +12,191 @@ pub enum ReadNumberFromBytesError {
     NotEnoughBytes,
     /// There are too many bytes to read the number
     TooManyBytes,
-}
\ No newline at end of file
+}
+
+/// The parts of an archive, to tell where a ParseError happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Structure {
+    /// The archive itself, when moving in it or searching a part
+    Archive,
+    /// A volume of a split archive
+    Volume,
+    LocalFileHeader,
+    /// The data of a stored file, compressed and maybe encrypted
+    FileData,
+    DataDescriptor,
+    DecryptionHeader,
+    ArchiveDecryptionHeader,
+    ArchiveExtraDataRecord,
+    CentralDirectory,
+    CentralDirectoryFileHeader,
+    DigitalSignature,
+    Zip64EndOfCentralDirectoryRecord,
+    Zip64EndOfCentralDirectoryLocator,
+    EndOfCentralDirectoryRecord,
+    Zip64ExtendedInformation,
+    NtfsTimes,
+    ExtendedTimestamp,
+    InfoZipUnix,
+    InfoZipUnicode,
+    StrongEncryptionHeader,
+    AesExtraField,
+}
+
+impl fmt::Display for Structure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Structure::Archive => "archive",
+            Structure::Volume => "volume",
+            Structure::LocalFileHeader => "local file header",
+            Structure::FileData => "file data",
+            Structure::DataDescriptor => "data descriptor",
+            Structure::DecryptionHeader => "decryption header",
+            Structure::ArchiveDecryptionHeader => "archive decryption header",
+            Structure::ArchiveExtraDataRecord => "archive extra data record",
+            Structure::CentralDirectory => "central directory",
+            Structure::CentralDirectoryFileHeader => "central directory file header",
+            Structure::DigitalSignature => "digital signature",
+            Structure::Zip64EndOfCentralDirectoryRecord => "zip64 end of central directory record",
+            Structure::Zip64EndOfCentralDirectoryLocator => "zip64 end of central directory locator",
+            Structure::EndOfCentralDirectoryRecord => "end of central directory record",
+            Structure::Zip64ExtendedInformation => "zip64 extended information",
+            Structure::NtfsTimes => "NTFS extra field",
+            Structure::ExtendedTimestamp => "extended timestamp",
+            Structure::InfoZipUnix => "Info-ZIP Unix extra field",
+            Structure::InfoZipUnicode => "Info-ZIP Unicode extra field",
+            Structure::StrongEncryptionHeader => "strong encryption header",
+            Structure::AesExtraField => "AES extra field",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// What went wrong in a ParseError
+#[derive(Debug)]
+pub enum ParseErrorKind {
+    /// The underlying reader failed
+    Io(io::Error),
+    /// The data ends before the end of the field
+    Truncated,
+    /// The field holds an invalid value, described by the string
+    InvalidValue(String),
+    /// The structure wasn't found where it was expected
+    NotFound,
+    /// The structure uses a feature which isn't supported, described by the string
+    Unsupported(String),
+    /// The file data can't be decompressed, for the reason in the string
+    Decompression(String),
+    /// The file is encrypted and no password was given
+    PasswordRequired,
+    /// The password doesn't match the one used to encrypt the file
+    WrongPassword,
+    /// The file data can't be decrypted, for the reason in the string
+    Decryption(String),
+    /// The volumes of a split archive are missing, the archive can't be read without them
+    MissingVolumes(Vec<PathBuf>),
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorKind::Io(e) => write!(f, "I/O error: {}", e),
+            ParseErrorKind::Truncated => write!(f, "truncated"),
+            ParseErrorKind::InvalidValue(reason) => write!(f, "{}", reason),
+            ParseErrorKind::NotFound => write!(f, "not found"),
+            ParseErrorKind::Unsupported(feature) => write!(f, "unsupported {}", feature),
+            ParseErrorKind::Decompression(reason) => write!(f, "unable to decompress: {}", reason),
+            ParseErrorKind::PasswordRequired => write!(f, "the file is encrypted, a password is needed"),
+            ParseErrorKind::WrongPassword => write!(f, "wrong password"),
+            ParseErrorKind::Decryption(reason) => write!(f, "unable to decrypt: {}", reason),
+            ParseErrorKind::MissingVolumes(paths) => {
+                let paths: Vec<String> = paths.iter().map(|path| path.display().to_string()).collect();
+                write!(f, "missing volumes: {}", paths.join(", "))
+            },
+        }
+    }
+}
+
+/// An error raised while reading an archive.
+/// It tells what went wrong, in which structure, and where when it's known:
+/// the field being read and its offset from the start of the archive.
+#[derive(Debug)]
+pub struct ParseError {
+    /// What went wrong
+    pub kind: ParseErrorKind,
+    /// The structure being read
+    pub structure: Structure,
+    /// The field being read, if the error is about a field
+    pub field: Option<&'static str>,
+    /// The offset of the field, or else of the structure, from the start of
+    /// the archive, if known
+    pub offset: Option<u64>,
+}
+
+impl ParseError {
+    /// Create an error about a structure, without field nor offset
+    pub fn new(kind: ParseErrorKind, structure: Structure) -> ParseError {
+        ParseError {
+            kind,
+            structure,
+            field: None,
+            offset: None,
+        }
+    }
+
+    /// Create an error for a failure of the underlying reader
+    pub fn io(error: io::Error, structure: Structure) -> ParseError {
+        Self::new(ParseErrorKind::Io(error), structure)
+    }
+
+    /// Create an error for a field whose data is truncated
+    pub fn truncated(structure: Structure, field: &'static str) -> ParseError {
+        Self::new(ParseErrorKind::Truncated, structure).with_field(field)
+    }
+
+    /// Create an error for a field holding an invalid value
+    pub fn invalid_value(structure: Structure, field: &'static str, reason: String) -> ParseError {
+        Self::new(ParseErrorKind::InvalidValue(reason), structure).with_field(field)
+    }
+
+    /// Set the field being read
+    pub fn with_field(mut self, field: &'static str) -> ParseError {
+        self.field = Some(field);
+        self
+    }
+
+    /// Set the offset of the error, from the start of the archive
+    pub fn at(mut self, offset: u64) -> ParseError {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Set the offset of the error if it's not known yet, as when a structure
+    /// read from a buffer is stored at offset in the archive
+    pub fn or_at(mut self, offset: u64) -> ParseError {
+        self.offset.get_or_insert(offset);
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Unable to read {}", self.structure)?;
+        if let Some(field) = self.field {
+            write!(f, ", {}", field)?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " at offset {}", offset)?;
+        }
+        write!(f, ": {}", self.kind)
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.kind {
+            ParseErrorKind::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
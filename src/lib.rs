@@ -1,3 +1,6 @@
+pub mod checksum;
+pub mod compression;
+pub mod crypto;
 pub mod errors;
 pub mod util;
 pub mod zip;
\ No newline at end of file
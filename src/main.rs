@@ -1,81 +1,443 @@
-use archive_analyzer::zip::reader;
+use archive_analyzer::errors::ParseError;
+use archive_analyzer::zip::consistency::check_consistency;
+use archive_analyzer::zip::constants;
+use archive_analyzer::zip::decompression::summarize_stored_file;
+use archive_analyzer::zip::extraction;
+use archive_analyzer::zip::model::{self, CentralDirectory, DecryptionHeader, DosDateTime, ExtraField, ExtraFieldContent, StoredFile, Timestamp, VolumeIssue, ZipFile};
+use archive_analyzer::zip::reader::{self, ParsingStrategy};
+use archive_analyzer::zip::split::SplitArchive;
+use archive_analyzer::zip::verification::verify_crc32;
 
 use std::env;
 use std::fs;
+use std::io::{self, Read, Seek};
+use std::path::Path;
+use std::process;
+
+const USAGE: &str = "Usage: archive-analyzer [verify | extract <directory>] [--strategy <sequential|central-directory>] [--split] [--password <password>] <zipFilename | ->";
+
+/// What to do with the archive
+enum Command {
+    /// Print everything found in the archive
+    Analyze,
+    /// Only check the CRC-32 of the files
+    Verify,
+    /// Extract the files to a directory
+    Extract(String),
+}
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    if args.len() < 2 {
-        panic!("Usage: archive-analyzer <zipFilename>");
+    let (command, mut i) = match args.get(1).map(|arg| arg.as_str()) {
+        Some("verify") => (Command::Verify, 2),
+        Some("extract") => (Command::Extract(args.get(2).cloned().unwrap_or_else(|| exit_with_usage())), 3),
+        _ => (Command::Analyze, 1),
+    };
+    let mut strategy = ParsingStrategy::Sequential;
+    let mut split = false;
+    let mut password = None;
+    let mut zip_filename = None;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--strategy" => {
+                i += 1;
+                strategy = match args.get(i).map(|value| value.as_str()) {
+                    Some("sequential") => ParsingStrategy::Sequential,
+                    Some("central-directory") => ParsingStrategy::CentralDirectoryFirst,
+                    _ => exit_with_usage(),
+                };
+            },
+            "--split" => split = true,
+            "--password" => {
+                i += 1;
+                password = Some(args.get(i).cloned().unwrap_or_else(|| exit_with_usage()));
+            },
+            _ if zip_filename.is_none() => zip_filename = Some(args[i].clone()),
+            _ => exit_with_usage(),
+        }
+        i += 1;
+    }
+    let zip_filename = zip_filename.unwrap_or_else(|| exit_with_usage());
+
+    let password = password.as_deref();
+    if zip_filename == "-" {
+        // The standard input can't seek: the archive is read as it arrives
+        if split || strategy != ParsingStrategy::Sequential {
+            eprintln!("The standard input can only be read sequentially, without --split");
+            process::exit(1);
+        }
+        // The stream isn't read again: the content of the files is summarized
+        // as it's read, unless it's extracted or decrypted afterwards, which
+        // needs the data kept in memory
+        let keep_data = matches!(command, Command::Extract(_)) || password.is_some();
+        let zip_file = reader::ZipFileReader::read_stream(io::stdin().lock(), keep_data);
+        run(&mut io::empty(), zip_file, command, &zip_filename, password);
+    } else if split {
+        let mut archive = SplitArchive::open(Path::new(&zip_filename)).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        });
+        let zip_file = reader::ZipFileReader::read_split(&mut archive, strategy);
+        run(&mut archive, zip_file, command, &zip_filename, password);
+    } else {
+        let mut file = fs::File::open(&zip_filename).unwrap_or_else(|e| {
+            eprintln!("Unable to open {}: {}", zip_filename, e);
+            process::exit(1);
+        });
+        let zip_file = reader::ZipFileReader::read_with_strategy(&mut file, strategy);
+        run(&mut file, zip_file, command, &zip_filename, password);
     }
+}
 
-    let zip_filename = &args[1];    
-    let mut file = fs::File::open(zip_filename).unwrap();
-    let zip_file_result = reader::ZipFileReader::read(&mut file);
+/// Run the command on a ZIP file read from archive, whose files are read
+/// again when their content is needed
+fn run<R: Read + Seek>(archive: &mut R, zip_file: Result<ZipFile, ParseError>, command: Command, zip_filename: &str, password: Option<&str>) {
+    match zip_file {
+        Ok(zip_file) => match command {
+            Command::Analyze => print_zip_file(archive, zip_file, password),
+            Command::Verify => process::exit(verify_zip_file(archive, &zip_file, password)),
+            Command::Extract(directory) => process::exit(extract_zip_file(archive, &zip_file, Path::new(&directory), password)),
+        },
+        Err(e) => {
+            eprintln!("{}: {}", zip_filename, e);
+            process::exit(1);
+        },
+    }
+}
+
+/// Print the usage and exit with an error code
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+/// Verify the CRC-32 of the files of a ZIP file, and print the result for
+/// each file.
+/// Returns the exit code: 1 if a file can't be decompressed or has a wrong
+/// CRC-32, 0 otherwise.
+fn verify_zip_file<R: Read + Seek>(archive: &mut R, zip_file: &ZipFile, password: Option<&str>) -> i32 {
+    let mut failures = 0;
+    for stored_file in &zip_file.stored_files {
+        let filename = &stored_file.local_file_header.filename;
+        let summarized;
+        let content_summary = match &stored_file.content_summary {
+            Some(content_summary) => content_summary,
+            None => {
+                summarized = summarize_stored_file(archive, stored_file, password.map(|password| password.as_bytes()));
+                &summarized
+            },
+        };
+        let content_summary = match content_summary {
+            Ok(content_summary) => content_summary,
+            Err(e) => {
+                println!("{}: FAILED: {}", filename, e);
+                failures += 1;
+                continue;
+            },
+        };
+        match verify_crc32(stored_file, zip_file.central_directory.as_ref(), content_summary.crc32) {
+            None => println!("{}: OK (no CRC32 stored, authenticated with AE-2)", filename),
+            Some(verification) if verification.is_valid() => println!("{}: OK (CRC32 {:08x})", filename, verification.computed),
+            Some(verification) => {
+                let mismatches: Vec<String> = verification.mismatches.iter()
+                    .map(|mismatch| format!("{} CRC wrong (stored {:08x})", mismatch.source, mismatch.stored))
+                    .collect();
+                println!("{}: FAILED: {}, computed {:08x}", filename, mismatches.join(", "), verification.computed);
+                failures += 1;
+            },
+        }
+    }
 
+    println!("{} files verified, {} failed", zip_file.stored_files.len(), failures);
+    if failures > 0 { 1 } else { 0 }
+}
+
+/// Extract the files of a ZIP file to a directory, and print the files
+/// rejected.
+/// Returns the exit code: 1 if a file was rejected, 0 otherwise.
+fn extract_zip_file<R: Read + Seek>(archive: &mut R, zip_file: &ZipFile, directory: &Path, password: Option<&str>) -> i32 {
+    let report = match extraction::extract(archive, zip_file, directory, password.map(|password| password.as_bytes())) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        },
+    };
+    for path in &report.extracted {
+        println!("Extracted: {}", path.display());
+    }
+    for rejected in &report.rejected {
+        println!("REJECTED: {}: {}", rejected.filename, rejected.reason);
+    }
+    println!("{} files extracted, {} rejected", report.extracted.len(), report.rejected.len());
+    if report.rejected.is_empty() { 0 } else { 1 }
+}
+
+/// Print the content of a ZIP file.
+/// The files are decompressed, and the encrypted ones are decrypted with the
+/// password, if any.
+fn print_zip_file<R: Read + Seek>(archive: &mut R, zip_file: ZipFile, password: Option<&str>) {
     println!("\n\n\n\n\n\n\n");
+    if !zip_file.volumes.is_empty() {
+        println!("Volumes");
+        for volume in &zip_file.volumes {
+            println!("\tDisk {}: {} ({} bytes, at offset {})", volume.disk_number, volume.path.display(), volume.size, volume.offset_in_archive);
+        }
+        for issue in &zip_file.volume_issues {
+            match issue {
+                VolumeIssue::Missing { disk_number, path } => println!("\tMISSING: disk {} ({})", disk_number, path.display()),
+                VolumeIssue::OutOfOrder { disk_number, found_in_disk_number } => println!(
+                    "\tOUT OF ORDER: the content of disk {} was found in the volume of disk {}", disk_number, found_in_disk_number),
+            }
+        }
+        println!("\n");
+    }
+
+    if let Some(prefix) = &zip_file.prefix {
+        println!("Data before the archive: {} bytes (stub type: {:?})", prefix.size, prefix.stub_type);
+        println!("\n");
+    }
+
     println!("Files stored in archive");
 
-    if let Ok(zip_file) = zip_file_result {
-        for stored_file in zip_file.stored_files {
-            println!("----------{}----------", stored_file.local_file_header.filename);
-            println!("\tPosition in archive: {}", stored_file.position);
-            println!("\tMinimum version to extract: {}", stored_file.local_file_header.minimum_version);
-            println!("\tGeneral purpose flag: {}", stored_file.local_file_header.general_purpose_flag);
-            println!("\tCompression method: {}", stored_file.local_file_header.compression_method);
-            println!("\tFile last modification time: {}", stored_file.local_file_header.file_last_modification_time);
-            println!("\tFile last modification date: {}", stored_file.local_file_header.file_last_modification_date);
-            println!("\tCRC32: {}", stored_file.local_file_header.crc32);
-            println!("\tCompressed size: {}", stored_file.local_file_header.compressed_size);
-            println!("\tUncompressed size: {}", stored_file.local_file_header.uncompressed_size);
-            println!("\tFilename: {}", stored_file.local_file_header.filename);
-            println!("\tFound in central directory: {}", stored_file.found_in_central_directory);
-            println!("\tOffset from start of archive: {}", stored_file.offset_in_archive);
-
-            println!("\n")
+    for stored_file in zip_file.stored_files {
+        println!("----------{}----------", stored_file.local_file_header.filename);
+        println!("\tPosition in archive: {}", stored_file.position);
+        println!("\tMinimum version to extract: {}", stored_file.local_file_header.minimum_version);
+        println!("\tGeneral purpose flag: {}", stored_file.local_file_header.general_purpose_flag);
+        println!("\tCompression method: {}", stored_file.local_file_header.compression_method);
+        println!("\tFile last modification time: {}", stored_file.local_file_header.file_last_modification_time);
+        println!("\tFile last modification date: {}", stored_file.local_file_header.file_last_modification_date);
+        print_last_modification(&stored_file.local_file_header.last_modification_dos(), &stored_file.local_file_header.last_modification());
+        println!("\tCRC32: {}", stored_file.local_file_header.crc32);
+        println!("\tCompressed size: {}", stored_file.local_file_header.compressed_size);
+        println!("\tUncompressed size: {}", stored_file.local_file_header.uncompressed_size);
+        println!("\tFilename: {}", stored_file.local_file_header.filename);
+        println!("\tFilename encoding: {:?}", stored_file.local_file_header.filename_encoding);
+        println!("\tRaw filename: {}", to_hex(&stored_file.local_file_header.filename_raw));
+        println!("\tZip64: {}", stored_file.local_file_header.zip64_extended_information.is_some());
+        println!("\tStrong encryption: {}", stored_file.local_file_header.uses_strong_encryption());
+        println!("\tMasked header: {}", stored_file.local_file_header.is_masked());
+        print_extra_field(&stored_file.local_file_header.extra_field);
+        if let Some(decryption_header) = &stored_file.decryption_header {
+            println!("\tDecryption header:");
+            print_decryption_header(decryption_header);
         }
-
-        println!("Central directory");
-        if zip_file.central_directory.is_none() {
-            println!("\t No central directory found");
-        } else {
-            let central_directory = zip_file.central_directory.unwrap();
-
-            println!("\tHas a digital signature: {}", central_directory.digital_signature.is_some());
-            println!("\tNumber of central directory records on this disk: {}", central_directory.end_of_central_directory_record.central_directory_records_number_on_disk);
-            println!("\tTotal number of central directory records: {}", central_directory.end_of_central_directory_record.central_directory_records_total_number);
-            println!("\tSize of central directory: {}", central_directory.end_of_central_directory_record.central_directory_size);
-            println!("\tNumber of disks: {}", central_directory.end_of_central_directory_record.disk_number);
-            println!("\tDisk on which starts the central directory: {}", central_directory.end_of_central_directory_record.disk_start_central_directory);
-            println!("\tOffset of the central directory, relative to the start of archive: {}", central_directory.end_of_central_directory_record.offset_start_central_directory);
-
-            for central_directory_file_headers in central_directory.file_headers {
-                println!("\n");
-                println!("----------{}----------", central_directory_file_headers.filename);
-                if central_directory_file_headers.position.is_some() {
-                    println!("\tPosition in central directory: {}", central_directory_file_headers.position.unwrap());
+        println!("\tData size: {} (from {:?})", stored_file.data_size, stored_file.data_size_source);
+        if let Some(data_descriptor) = &stored_file.data_descriptor {
+            println!("\tData descriptor:");
+            println!("\t\tSignature present: {}", data_descriptor.signature_present);
+            println!("\t\tCRC32: {}", data_descriptor.crc32);
+            println!("\t\tCompressed size: {}", data_descriptor.compressed_size);
+            println!("\t\tUncompressed size: {}", data_descriptor.uncompressed_size);
+        }
+        print_content(archive, &stored_file, zip_file.central_directory.as_ref(), password);
+        println!("\tFound in central directory: {}", stored_file.found_in_central_directory);
+        let mismatches = zip_file.central_directory.as_ref()
+            .and_then(|central_directory| check_consistency(&stored_file, central_directory));
+        match mismatches {
+            Some(mismatches) if mismatches.is_empty() => println!("\tConsistent with the central directory: yes"),
+            Some(mismatches) => {
+                println!("\tConsistent with the central directory: no");
+                for mismatch in mismatches {
+                    println!("\t\tMISMATCH {}: {} in the local file header, {} in the central directory",
+                        mismatch.field, mismatch.local_file_header, mismatch.central_directory);
                 }
-                println!("\tCompressed size: {}", central_directory_file_headers.compressed_size);
-                println!("\tCompression method: {}", central_directory_file_headers.compression_method);
-                println!("\tCRC32: {}", central_directory_file_headers.crc32);
-                println!("\tDisk where the archive starts: {}", central_directory_file_headers.disk_start);
-                println!("\tExternal file attributes: {}", central_directory_file_headers.external_file_attributes);
-                println!("\tInternal file attributes: {}", central_directory_file_headers.internal_file_attributes);
-                println!("\tFile last modification time: {}", central_directory_file_headers.file_last_modification_time);
-                println!("\tFile last modification date: {}", central_directory_file_headers.file_last_modification_date);
-                println!("\tGeneral purpose flag: {}", central_directory_file_headers.general_purpose_flag);
-                //println!("\tExtra field: {}", central_directory_file_headers.extra_field);
-                println!("\tFile comment: {}", central_directory_file_headers.file_comment);
-                println!("\tFilename: {}", central_directory_file_headers.filename);
+            },
+            None => (),
+        }
+        println!("\tOffset from start of archive: {}", stored_file.offset_in_archive);
+        if let Some(offset) = stored_file.offset_from_central_directory {
+            println!("\tOffset announced by the central directory: {}", offset);
+        }
+
+        println!("\n")
+    }
+
+    if let Some(archive_decryption_header) = &zip_file.archive_decryption_header {
+        println!("Archive decryption header");
+        println!("\tOffset from start of archive: {}", archive_decryption_header.offset_from_start_of_archive);
+        print_decryption_header(&archive_decryption_header.decryption_header);
+        println!("\tHas an archive extra data record: {}", archive_decryption_header.archive_extra_data_record.is_some());
+        println!("\tSize of encrypted central directory: {}", archive_decryption_header.encrypted_central_directory.len());
+        println!("\n");
+    }
+
+    println!("Central directory");
+    if let Some(central_directory) = zip_file.central_directory {
+        println!("\tHas a digital signature: {}", central_directory.digital_signature.is_some());
+        println!("\tNumber of central directory records on this disk: {}", central_directory.end_of_central_directory_record.central_directory_records_number_on_disk);
+        println!("\tTotal number of central directory records: {}", central_directory.end_of_central_directory_record.central_directory_records_total_number);
+        println!("\tSize of central directory: {}", central_directory.end_of_central_directory_record.central_directory_size);
+        println!("\tNumber of disks: {}", central_directory.end_of_central_directory_record.disk_number);
+        println!("\tDisk on which starts the central directory: {}", central_directory.end_of_central_directory_record.disk_start_central_directory);
+        println!("\tOffset of the central directory, relative to the start of archive: {}", central_directory.end_of_central_directory_record.offset_start_central_directory);
+        println!("\tOffset of the central directory in the file: {}", central_directory.offset_from_start_of_archive);
+        println!("\tBytes prepended to the archive: {}", central_directory.base_offset);
+        println!("\tComment: {}", central_directory.end_of_central_directory_record.comment);
+        if let Some(zip64_record) = &central_directory.zip64_end_of_central_directory_record {
+            println!("\tZip64 end of central directory record:");
+            println!("\t\tVersion made by: {}", zip64_record.version_made_by);
+            println!("\t\tMinimum version to extract: {}", zip64_record.minimum_version);
+            println!("\t\tSize of extensible data sector: {}", zip64_record.extensible_data_sector.len());
+            if let Some(encryption) = &zip64_record.central_directory_encryption {
+                println!("\t\tCentral directory compression method: {}", encryption.compression_method);
+                println!("\t\tCentral directory compressed size: {}", encryption.compressed_size);
+                println!("\t\tCentral directory uncompressed size: {}", encryption.uncompressed_size);
+                println!("\t\tCentral directory encryption algorithm: {} ({} bits)", model::encryption_algorithm_name(encryption.algorithm_id), encryption.bit_length);
+                println!("\t\tCentral directory hash algorithm: {}", encryption.hash_id);
             }
         }
+        if let Some(zip64_locator) = &central_directory.zip64_end_of_central_directory_locator {
+            println!("\tZip64 end of central directory locator:");
+            println!("\t\tDisk on which starts the zip64 end of central directory: {}", zip64_locator.disk_start_zip64_end_of_central_directory);
+            println!("\t\tOffset of the zip64 end of central directory, relative to the start of archive: {}", zip64_locator.offset_zip64_end_of_central_directory_record);
+            println!("\t\tTotal number of disks: {}", zip64_locator.total_number_of_disks);
+        }
 
+        for central_directory_file_headers in central_directory.file_headers {
+            println!("\n");
+            println!("----------{}----------", central_directory_file_headers.filename);
+            if let Some(position) = central_directory_file_headers.position {
+                println!("\tPosition in central directory: {}", position);
+            }
+            println!("\tCompressed size: {}", central_directory_file_headers.compressed_size);
+            println!("\tCompression method: {}", central_directory_file_headers.compression_method);
+            println!("\tCRC32: {}", central_directory_file_headers.crc32);
+            println!("\tDisk where the archive starts: {}", central_directory_file_headers.disk_start);
+            println!("\tExternal file attributes: {}", central_directory_file_headers.external_file_attributes);
+            println!("\tInternal file attributes: {}", central_directory_file_headers.internal_file_attributes);
+            println!("\tFile last modification time: {}", central_directory_file_headers.file_last_modification_time);
+            println!("\tFile last modification date: {}", central_directory_file_headers.file_last_modification_date);
+            print_last_modification(&central_directory_file_headers.last_modification_dos(), &central_directory_file_headers.last_modification());
+            println!("\tGeneral purpose flag: {}", central_directory_file_headers.general_purpose_flag);
+            print_extra_field(&central_directory_file_headers.extra_field);
+            println!("\tFile comment: {}", central_directory_file_headers.file_comment);
+            println!("\tFile comment encoding: {:?}", central_directory_file_headers.file_comment_encoding);
+            println!("\tFilename: {}", central_directory_file_headers.filename);
+            println!("\tFilename encoding: {:?}", central_directory_file_headers.filename_encoding);
+            println!("\tRaw filename: {}", to_hex(&central_directory_file_headers.filename_raw));
+            println!("\tOffset of local file header: {}", central_directory_file_headers.local_file_header_offset);
+            match central_directory_file_headers.offset_in_archive {
+                Some(offset_in_archive) => println!("\tOffset of local file header, from the start of archive: {}", offset_in_archive),
+                None => println!("\tOffset of local file header, from the start of archive: overflows"),
+            }
+            println!("\tZip64: {}", central_directory_file_headers.zip64_extended_information.is_some());
+        }
+    } else {
+        println!("\t No central directory found");
+    }
 
+    println!("\n");
+    println!("Diagnostics");
+    if zip_file.diagnostics.is_empty() {
+        println!("\tNo problem found");
+    }
+    for diagnostic in &zip_file.diagnostics {
+        match diagnostic.offset {
+            Some(offset) => println!("\t{} [{}] at offset {}: {}", diagnostic.severity, diagnostic.code, offset, diagnostic.message),
+            None => println!("\t{} [{}]: {}", diagnostic.severity, diagnostic.code, diagnostic.message),
+        }
     }
+
     println!("\n\n\n\n\n\n\n");
 
 }
 
+/// Print the records of an extra field
+fn print_extra_field(extra_field: &ExtraField) {
+    println!("\tExtra field: {} bytes", extra_field.raw.len());
+    for record in &extra_field.records {
+        let description = match &record.content {
+            ExtraFieldContent::Zip64ExtendedInformation(zip64) => format!(
+                "Zip64 extended information (uncompressed size: {:?}, compressed size: {:?}, offset: {:?}, disk: {:?})",
+                zip64.uncompressed_size, zip64.compressed_size, zip64.local_file_header_offset, zip64.disk_start),
+            ExtraFieldContent::Ntfs(ntfs) => format!(
+                "NTFS times (modification: {:?}, access: {:?}, creation: {:?})",
+                ntfs.modification_time, ntfs.access_time, ntfs.creation_time),
+            ExtraFieldContent::ExtendedTimestamp(timestamp) => format!(
+                "Extended timestamp (modification: {:?}, access: {:?}, creation: {:?})",
+                timestamp.modification_time, timestamp.access_time, timestamp.creation_time),
+            ExtraFieldContent::InfoZipUnix(unix) => format!("Info-ZIP Unix (UID: {}, GID: {})", unix.uid, unix.gid),
+            ExtraFieldContent::InfoZipUnicodePath(unicode) => format!("Info-ZIP Unicode path: {}", unicode.value),
+            ExtraFieldContent::InfoZipUnicodeComment(unicode) => format!("Info-ZIP Unicode comment: {}", unicode.value),
+            ExtraFieldContent::StrongEncryptionHeader(header) => format!(
+                "Strong encryption header (algorithm: {}, bit length: {}, flags: {})",
+                model::encryption_algorithm_name(header.algorithm_id), header.bit_length, header.flags),
+            ExtraFieldContent::Aes(aes) => format!(
+                "WinZip AES (vendor version: {}, strength: {}, compression method: {})",
+                aes.vendor_version, aes.strength, aes.compression_method),
+            ExtraFieldContent::Unknown => "Unknown".to_string(),
+            ExtraFieldContent::Malformed(reason) => format!("MALFORMED: {}", reason),
+        };
+        println!("\t\t0x{:04x} ({} bytes at offset {}): {}", record.header_id, record.data_size, record.offset, description);
+    }
+}
+
+/// Decompress a file, decrypting it first with the password if it's encrypted,
+/// and check its CRC-32. The content isn't kept.
+fn print_content<R: Read + Seek>(archive: &mut R, stored_file: &StoredFile, central_directory: Option<&CentralDirectory>, password: Option<&str>) {
+    let local_file_header = &stored_file.local_file_header;
+    let encrypted = local_file_header.general_purpose_flag & constants::GENERAL_PURPOSE_FLAG_ENCRYPTED != 0;
+    if encrypted && password.is_none() {
+        println!("\tContent: encrypted, a password is needed");
+        return;
+    }
+
+    // The content of the files of a stream may be summarized already
+    let summarized;
+    let content_summary = match &stored_file.content_summary {
+        Some(content_summary) => content_summary,
+        None => {
+            summarized = summarize_stored_file(archive, stored_file, password.map(|password| password.as_bytes()));
+            &summarized
+        },
+    };
+    let content_summary = match content_summary {
+        Ok(content_summary) => content_summary,
+        Err(e) => {
+            println!("\tContent: {}", e);
+            return;
+        },
+    };
+    println!("\tDecompressed size: {}", content_summary.size);
+    println!("\tCompressed bytes used: {} of {}", content_summary.consumed, content_summary.compressed_size);
+
+    // AE-2 files don't store their CRC-32, the authentication code replaces it
+    let Some(verification) = verify_crc32(stored_file, central_directory, content_summary.crc32) else {
+        println!("\tCRC32 of content: not stored (AE-2)");
+        return;
+    };
+    let status = if verification.is_valid() { "OK" } else { "MISMATCH" };
+    println!("\tCRC32 of content: {} ({})", verification.computed, status);
+    for mismatch in &verification.mismatches {
+        println!("\t\t{} CRC wrong: {}", mismatch.source, mismatch.stored);
+    }
+}
+
+/// Print the fields of a decryption header
+fn print_decryption_header(decryption_header: &DecryptionHeader) {
+    println!("\t\tEncryption algorithm: {} ({} bits)", model::encryption_algorithm_name(decryption_header.algorithm_id), decryption_header.bit_length);
+    println!("\t\tFlags: {}", decryption_header.flags);
+    println!("\t\tIV: {}", to_hex(&decryption_header.iv));
+    println!("\t\tNumber of recipients: {}", decryption_header.recipient_count);
+    println!("\t\tPassword validation data: {} bytes", decryption_header.password_validation_data.len());
+}
 
+/// Print the decoded MS-DOS last modification time, its errors if any, and
+/// the most precise last modification time available
+fn print_last_modification(dos: &DosDateTime, timestamp: &Timestamp) {
+    let errors = dos.errors();
+    if errors.is_empty() {
+        println!("\tFile last modification (MS-DOS): {}", dos);
+    } else {
+        println!("\tFile last modification (MS-DOS): {} (INVALID: {})", dos, errors.join(", "));
+    }
+    println!("\tFile last modification: {} (from {:?})", timestamp, timestamp.source);
+}
 
+/// Format bytes as a hexadecimal string
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -1,20 +1,27 @@
 //! This module provides some common functions
 
-use crate::errors::ReadNumberFromBytesError;
+use crate::errors::{ParseError, ReadNumberFromBytesError, Structure};
 use crate::zip::constants;
-use std::fs::{self, File};
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{self, Read, Seek, SeekFrom};
 
 /**
  * Read a chunk of the file.
  */
-pub fn read_chunk(file: &mut fs::File, chunk_size: usize) -> Vec<u8> {
-    let mut chunk = Vec::with_capacity(chunk_size);
-    let _n = file.by_ref()
-                .take(chunk_size as u64)
-                .read_to_end(&mut chunk)
-                .unwrap();
-    return chunk;
+pub fn read_chunk<R: Read>(file: &mut R, chunk_size: usize) -> Vec<u8> {
+    try_read_chunk(file, chunk_size).unwrap()
+}
+
+/// Read a chunk of the file, as read_chunk(), returning the error of the
+/// reader instead of panicking.
+/// The chunk is shorter than chunk_size if the end of the file is reached.
+/// chunk_size often comes from the archive itself, so the memory is allocated
+/// as the bytes are read rather than up front.
+pub fn try_read_chunk<R: Read>(file: &mut R, chunk_size: usize) -> io::Result<Vec<u8>> {
+    let mut chunk = Vec::new();
+    file.by_ref()
+        .take(chunk_size as u64)
+        .read_to_end(&mut chunk)?;
+    Ok(chunk)
 }
 
 /**
@@ -26,7 +33,7 @@ pub fn check_signature(chunk: Vec<u8>) -> String {
     if number == constants::SIGNATURE_HEADER_LOCAL_FILE {
         return "zip".to_string();
     }
-    return signature.to_string();
+    signature.to_string()
 }
 
 /// Compare the 4 next bytes of file to the given signature.
@@ -36,8 +43,9 @@ pub fn check_signature(chunk: Vec<u8>) -> String {
 /// Otherwise, the cursor is reset to its previous position.
 ///
 /// Note: see compare_signature_raw() which does most of the job.
-pub fn compare_signature(file: &mut File, signature: u32) -> Result<bool, String> {
-    let chunk = read_chunk(file, 4);
+pub fn compare_signature<R: Read + Seek>(file: &mut R, signature: u32) -> Result<bool, ParseError> {
+    let chunk = try_read_chunk(file, 4)
+        .map_err(|e| ParseError::io(e, Structure::Archive))?;
     compare_signature_raw(file, &chunk, signature, true)
 }
 
@@ -55,9 +63,9 @@ pub fn compare_signature(file: &mut File, signature: u32) -> Result<bool, String
 /// Note: in case of error, the file cursor is not reset. Usually not a problem
 /// since the Err is usually returned by the caller in order to stop operations on
 /// the file.
-pub fn compare_signature_raw(file: &mut File, signature_1: &[u8], signature_2: u32, rewind_on_mismatch: bool) -> Result<bool, String> {
-    let value = read_u32_le(&signature_1)
-        .or(Err("Unable to compare signature"))?;
+pub fn compare_signature_raw<R: Read + Seek>(file: &mut R, signature_1: &[u8], signature_2: u32, rewind_on_mismatch: bool) -> Result<bool, ParseError> {
+    let value = read_u32_le(signature_1)
+        .or(Err(ParseError::truncated(Structure::Archive, "signature")))?;
 
     let signature_match = value == signature_2;
     // The bytes of the signature have already been read by the caller
@@ -70,29 +78,30 @@ pub fn compare_signature_raw(file: &mut File, signature_1: &[u8], signature_2: u
 
 /// Check if a file has enough bytes remaining to read
 /// It's a helper function to detect if we're at the end of the file
-pub fn file_has_remaining_space(file: &mut File, number_of_bytes: u32) -> Result<bool, String> {
+pub fn file_has_remaining_space<R: Read + Seek>(file: &mut R, number_of_bytes: u32) -> Result<bool, ParseError> {
     let current_offset = file.stream_position()
-        .or(Err("Unable to read current position in archive"))?;
+        .map_err(|e| ParseError::io(e, Structure::Archive))?;
 
-    let end_of_file = file.seek(SeekFrom::End(0)).or(Err("Unable to move cursor to end of archive"))?;
+    let end_of_file = file.seek(SeekFrom::End(0))
+        .map_err(|e| ParseError::io(e, Structure::Archive))?;
 
     // Reset the cursor as its original position
     file.seek(SeekFrom::Start(current_offset))
-        .or(Err("Unable to move cursor in archive"))?;
+        .map_err(|e| ParseError::io(e, Structure::Archive).at(current_offset))?;
 
     Ok(end_of_file - current_offset > number_of_bytes as u64)
 }
 
 /// Rewind the cursor of file of number_of_bytes bytes.
-/// Returns true if it worked, false if an error occured
-pub fn rewind_file_cursor(file: &mut File, number_of_bytes: u64) -> Result<(), String> {
+/// Returns an error if the cursor can't be moved
+pub fn rewind_file_cursor<R: Read + Seek>(file: &mut R, number_of_bytes: u64) -> Result<(), ParseError> {
     let current_offset = file.stream_position()
-        .or(Err("Unable to read current position in archive"))?;
+        .map_err(|e| ParseError::io(e, Structure::Archive))?;
 
     file.seek(SeekFrom::Start(current_offset-number_of_bytes))
-        .or(Err("Unable to move the cursor in the archive"))?;
+        .map_err(|e| ParseError::io(e, Structure::Archive).at(current_offset - number_of_bytes))?;
 
-    return Ok(());
+    Ok(())
 }
 
 /// Reads a u32 from little indian bytes
@@ -116,11 +125,77 @@ pub fn read_u16_le(chunk: &[u8]) -> Result<u16, ReadNumberFromBytesError> {
 }
 
 /// Reads a string from bytes
-/// The bytes must be ASCII codes
+/// The bytes must be ASCII codes. For text stored in an archive, see
+/// decode_cp437() and decode_utf8().
 pub fn read_string_bytes(chunk: &[u8]) -> String {
     let mut s = "".to_string();
     for item in chunk {
         s.push(char::from_u32(*item as u32).unwrap());
     }
-    return s;
-}
\ No newline at end of file
+    s
+}
+/// Reads a u64 from little indian bytes
+pub fn read_u64_le(chunk: &[u8]) -> Result<u64, ReadNumberFromBytesError> {
+    if chunk.len() > 8 {
+        return Err(ReadNumberFromBytesError::TooManyBytes);
+    } else if chunk.len() < 8 {
+        return Err(ReadNumberFromBytesError::NotEnoughBytes);
+    }
+    Ok(u64::from_le_bytes(chunk.try_into().unwrap()))
+}
+
+/// The characters of the upper half (0x80-0xff) of IBM Code Page 437.
+/// The lower half is identical to ASCII.
+const CP437_UPPER_HALF: [char; 128] = [
+    '\u{00c7}', '\u{00fc}', '\u{00e9}', '\u{00e2}', '\u{00e4}', '\u{00e0}', '\u{00e5}', '\u{00e7}', '\u{00ea}', '\u{00eb}', '\u{00e8}', '\u{00ef}', '\u{00ee}', '\u{00ec}', '\u{00c4}', '\u{00c5}',
+    '\u{00c9}', '\u{00e6}', '\u{00c6}', '\u{00f4}', '\u{00f6}', '\u{00f2}', '\u{00fb}', '\u{00f9}', '\u{00ff}', '\u{00d6}', '\u{00dc}', '\u{00a2}', '\u{00a3}', '\u{00a5}', '\u{20a7}', '\u{0192}',
+    '\u{00e1}', '\u{00ed}', '\u{00f3}', '\u{00fa}', '\u{00f1}', '\u{00d1}', '\u{00aa}', '\u{00ba}', '\u{00bf}', '\u{2310}', '\u{00ac}', '\u{00bd}', '\u{00bc}', '\u{00a1}', '\u{00ab}', '\u{00bb}',
+    '\u{2591}', '\u{2592}', '\u{2593}', '\u{2502}', '\u{2524}', '\u{2561}', '\u{2562}', '\u{2556}', '\u{2555}', '\u{2563}', '\u{2551}', '\u{2557}', '\u{255d}', '\u{255c}', '\u{255b}', '\u{2510}',
+    '\u{2514}', '\u{2534}', '\u{252c}', '\u{251c}', '\u{2500}', '\u{253c}', '\u{255e}', '\u{255f}', '\u{255a}', '\u{2554}', '\u{2569}', '\u{2566}', '\u{2560}', '\u{2550}', '\u{256c}', '\u{2567}',
+    '\u{2568}', '\u{2564}', '\u{2565}', '\u{2559}', '\u{2558}', '\u{2552}', '\u{2553}', '\u{256b}', '\u{256a}', '\u{2518}', '\u{250c}', '\u{2588}', '\u{2584}', '\u{258c}', '\u{2590}', '\u{2580}',
+    '\u{03b1}', '\u{00df}', '\u{0393}', '\u{03c0}', '\u{03a3}', '\u{03c3}', '\u{00b5}', '\u{03c4}', '\u{03a6}', '\u{0398}', '\u{03a9}', '\u{03b4}', '\u{221e}', '\u{03c6}', '\u{03b5}', '\u{2229}',
+    '\u{2261}', '\u{00b1}', '\u{2265}', '\u{2264}', '\u{2320}', '\u{2321}', '\u{00f7}', '\u{2248}', '\u{00b0}', '\u{2219}', '\u{00b7}', '\u{221a}', '\u{207f}', '\u{00b2}', '\u{25a0}', '\u{00a0}',
+];
+
+/// Decodes bytes encoded with IBM Code Page 437, the legacy encoding of
+/// filenames and comments in a ZIP file
+pub fn decode_cp437(chunk: &[u8]) -> String {
+    chunk.iter()
+        .map(|byte| match byte {
+            0x00..=0x7f => *byte as char,
+            _ => CP437_UPPER_HALF[(*byte - 0x80) as usize],
+        })
+        .collect()
+}
+
+/// Decodes UTF-8 bytes.
+/// Invalid sequences are replaced by U+FFFD, so that a damaged name is still
+/// readable.
+pub fn decode_utf8(chunk: &[u8]) -> String {
+    String::from_utf8_lossy(chunk).into_owned()
+}
+
+/// Converts a number of days since 1970-01-01 into a (year, month, day) date
+/// of the proleptic Gregorian calendar.
+/// Algorithm from http://howardhinnant.github.io/date_algorithms.html
+pub fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let z = days + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month as u8, day as u8)
+}
+
+/// Decodes a hexadecimal string, used to write the expected values of tests
+#[cfg(test)]
+pub(crate) fn from_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
@@ -0,0 +1,225 @@
+//! This module compares the local file header of a file with its central
+//! directory file header. They store the same values, and when they disagree
+//! the tools reading the archive from its start and the ones reading its
+//! central directory don't see the same files.
+
+use std::fmt;
+use super::constants;
+use super::model::{CentralDirectory, StoredFile};
+
+/// A value stored both in the local file header and in the central directory
+/// file header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// The minimum version of the specification needed to extract the file
+    MinimumVersion,
+    /// The general purpose bit flag
+    GeneralPurposeFlag,
+    /// The compression method
+    CompressionMethod,
+    /// The last modification time, in the MS-DOS format
+    LastModificationTime,
+    /// The last modification date, in the MS-DOS format
+    LastModificationDate,
+    /// The CRC32 of the uncompressed content
+    Crc32,
+    /// The size of the compressed data
+    CompressedSize,
+    /// The size of the uncompressed content
+    UncompressedSize,
+    /// The filename, as stored
+    Filename,
+    /// Whether the filename and the comment are encoded in UTF-8 (bit 11 of
+    /// the general purpose flag)
+    Utf8Flag,
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Field::MinimumVersion => "minimum version",
+            Field::GeneralPurposeFlag => "general purpose flag",
+            Field::CompressionMethod => "compression method",
+            Field::LastModificationTime => "last modification time",
+            Field::LastModificationDate => "last modification date",
+            Field::Crc32 => "CRC32",
+            Field::CompressedSize => "compressed size",
+            Field::UncompressedSize => "uncompressed size",
+            Field::Filename => "filename",
+            Field::Utf8Flag => "UTF-8 flag",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The value of a Field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldValue {
+    /// A numeric value, like a size or a compression method
+    Number(u64),
+    /// A flag, set or not
+    Flag(bool),
+    /// Bytes compared as stored, shown decoded as UTF-8 with their
+    /// hexadecimal value, since different bytes may be decoded to the same text
+    Bytes(Vec<u8>),
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldValue::Number(number) => write!(f, "{}", number),
+            FieldValue::Flag(flag) => write!(f, "{}", if *flag { "set" } else { "not set" }),
+            FieldValue::Bytes(bytes) => {
+                let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+                write!(f, "{:?} ({})", String::from_utf8_lossy(bytes), hex)
+            },
+        }
+    }
+}
+
+/// A field whose value differs between the local file header and the central
+/// directory file header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldMismatch {
+    /// The field which differs
+    pub field: Field,
+    /// The value in the local file header
+    pub local_file_header: FieldValue,
+    /// The value in the central directory file header
+    pub central_directory: FieldValue,
+}
+
+/// Compare the values of the local file header of a file with the ones of its
+/// central directory file header, field by field.
+/// The filenames are compared as stored, with the UTF-8 flag which tells their
+/// encoding.
+/// The values that the local file header legitimately doesn't store aren't
+/// compared: the CRC32 and sizes set to 0 because they are in the data
+/// descriptor, and the values masked because the central directory is encrypted.
+/// Returns None when the file has no central directory file header.
+pub fn check_consistency(stored_file: &StoredFile, central_directory: &CentralDirectory) -> Option<Vec<FieldMismatch>> {
+    let header = stored_file.central_directory_file_header(central_directory)?;
+    let local_file_header = &stored_file.local_file_header;
+    let masked = local_file_header.is_masked();
+    let has_data_descriptor = local_file_header.general_purpose_flag & constants::GENERAL_PURPOSE_FLAG_DATA_DESCRIPTOR != 0;
+    // A value of the local file header is deferred to the data descriptor if it's 0
+    let stored = |value: u64| !masked && (!has_data_descriptor || value != 0);
+
+    let mut mismatches = Vec::new();
+    let mut compare = |field: Field, local_value: FieldValue, central_value: FieldValue| {
+        if local_value != central_value {
+            mismatches.push(FieldMismatch {
+                field,
+                local_file_header: local_value,
+                central_directory: central_value,
+            });
+        }
+    };
+    compare(Field::MinimumVersion, FieldValue::Number(local_file_header.minimum_version as u64), FieldValue::Number(header.minimum_version as u64));
+    compare(Field::GeneralPurposeFlag, FieldValue::Number(local_file_header.general_purpose_flag as u64), FieldValue::Number(header.general_purpose_flag as u64));
+    compare(Field::CompressionMethod, FieldValue::Number(local_file_header.compression_method as u64), FieldValue::Number(header.compression_method as u64));
+    compare(Field::LastModificationTime, FieldValue::Number(local_file_header.file_last_modification_time as u64), FieldValue::Number(header.file_last_modification_time as u64));
+    compare(Field::LastModificationDate, FieldValue::Number(local_file_header.file_last_modification_date as u64), FieldValue::Number(header.file_last_modification_date as u64));
+    if stored(local_file_header.crc32 as u64) {
+        compare(Field::Crc32, FieldValue::Number(local_file_header.crc32 as u64), FieldValue::Number(header.crc32 as u64));
+    }
+    if stored(local_file_header.compressed_size) {
+        compare(Field::CompressedSize, FieldValue::Number(local_file_header.compressed_size), FieldValue::Number(header.compressed_size));
+    }
+    if stored(local_file_header.uncompressed_size) {
+        compare(Field::UncompressedSize, FieldValue::Number(local_file_header.uncompressed_size), FieldValue::Number(header.uncompressed_size));
+    }
+    if !masked {
+        // The raw filenames are compared, their decoding may hide a difference
+        compare(Field::Filename, FieldValue::Bytes(local_file_header.filename_raw.clone()), FieldValue::Bytes(header.filename_raw.clone()));
+        let is_utf8 = |general_purpose_flag: u16| general_purpose_flag & constants::GENERAL_PURPOSE_FLAG_UTF8 != 0;
+        compare(Field::Utf8Flag, FieldValue::Flag(is_utf8(local_file_header.general_purpose_flag)), FieldValue::Flag(is_utf8(header.general_purpose_flag)));
+    }
+    Some(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zip::model::ZipFile;
+    use crate::zip::read_fixture;
+    use crate::zip::reader::ParsingStrategy;
+
+    /// Check the consistency of the file at the given position
+    fn check(zip_file: &ZipFile, position: usize) -> Option<Vec<FieldMismatch>> {
+        check_consistency(&zip_file.stored_files[position], zip_file.central_directory.as_ref().unwrap())
+    }
+
+    /// The fields of the mismatches
+    fn fields(mismatches: Option<Vec<FieldMismatch>>) -> Vec<Field> {
+        mismatches.unwrap().iter().map(|mismatch| mismatch.field).collect()
+    }
+
+    #[test]
+    fn finds_no_mismatch_for_matching_headers() {
+        let zip_file = read_fixture("consistency.zip", ParsingStrategy::Sequential);
+        assert_eq!(check(&zip_file, 0), Some(Vec::new()));
+    }
+
+    #[test]
+    fn reports_size_mismatch() {
+        let zip_file = read_fixture("consistency.zip", ParsingStrategy::Sequential);
+        let mismatches = check(&zip_file, 1).unwrap();
+        assert_eq!(mismatches, vec![
+            FieldMismatch {
+                field: Field::CompressedSize,
+                local_file_header: FieldValue::Number(11),
+                central_directory: FieldValue::Number(12),
+            },
+            FieldMismatch {
+                field: Field::UncompressedSize,
+                local_file_header: FieldValue::Number(11),
+                central_directory: FieldValue::Number(12),
+            },
+        ]);
+    }
+
+    #[test]
+    fn reports_filename_mismatch() {
+        let zip_file = read_fixture("consistency.zip", ParsingStrategy::Sequential);
+        let mismatches = check(&zip_file, 2).unwrap();
+        assert_eq!(mismatches, vec![FieldMismatch {
+            field: Field::Filename,
+            local_file_header: FieldValue::Bytes(b"a.txt".to_vec()),
+            central_directory: FieldValue::Bytes(b"b.txt".to_vec()),
+        }]);
+    }
+
+    #[test]
+    fn reports_filenames_decoded_to_the_same_text() {
+        // The invalid UTF-8 bytes ff and fe are both decoded to U+FFFD
+        let zip_file = read_fixture("consistency.zip", ParsingStrategy::Sequential);
+        let central_directory = zip_file.central_directory.as_ref().unwrap();
+        let stored_file = &zip_file.stored_files[3];
+        let header = stored_file.central_directory_file_header(central_directory).unwrap();
+        assert_eq!(stored_file.local_file_header.filename, header.filename);
+        assert_eq!(fields(check(&zip_file, 3)), vec![Field::Filename]);
+    }
+
+    #[test]
+    fn reports_utf8_flag_mismatch() {
+        let zip_file = read_fixture("consistency.zip", ParsingStrategy::Sequential);
+        assert_eq!(fields(check(&zip_file, 4)), vec![Field::GeneralPurposeFlag, Field::Utf8Flag]);
+    }
+
+    #[test]
+    fn ignores_values_deferred_to_the_data_descriptor() {
+        // Bit 3 is set, the CRC32 and the sizes are 0 in the local file header
+        let zip_file = read_fixture("consistency.zip", ParsingStrategy::Sequential);
+        assert_eq!(zip_file.stored_files[5].local_file_header.crc32, 0);
+        assert_eq!(check(&zip_file, 5), Some(Vec::new()));
+    }
+
+    #[test]
+    fn checks_nothing_for_unreferenced_local_file_header() {
+        // The first of two files named x.txt isn't referenced by the central directory
+        let zip_file = read_fixture("unreferenced.zip", ParsingStrategy::Sequential);
+        assert_eq!(check(&zip_file, 0), None);
+        assert_eq!(check(&zip_file, 1), Some(Vec::new()));
+    }
+}
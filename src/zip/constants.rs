@@ -18,3 +18,134 @@ pub const SIGNATURE_END_OF_CENTRAL_DIRECTORY_RECORD: u32 = 101010256; // 0x06054
 /// The signature of a end of central directory record in a zip64
 pub const SIGNATURE_ZIP64_END_OF_CENTRAL_DIRECTORY_RECORD: u32 = 101075792; // 0x06064b50 (LE)
 
+
+/// The signature of the zip64 end of central directory locator
+pub const SIGNATURE_ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR: u32 = 117853008; // 0x07064b50 (LE)
+
+/// The header ID of the zip64 extended information extra field
+pub const EXTRA_FIELD_ZIP64_EXTENDED_INFORMATION: u16 = 0x0001;
+
+/// The value stored in a 2-bytes field when the real value is in a zip64 structure
+pub const ZIP64_PLACEHOLDER_U16: u16 = 0xffff;
+
+/// The value stored in a 4-bytes field when the real value is in a zip64 structure
+pub const ZIP64_PLACEHOLDER_U32: u32 = 0xffffffff;
+
+/// The header ID of the NTFS extra field, storing the file times
+pub const EXTRA_FIELD_NTFS: u16 = 0x000a;
+
+/// The header ID of the extended timestamp extra field
+pub const EXTRA_FIELD_EXTENDED_TIMESTAMP: u16 = 0x5455;
+
+/// The header ID of the Info-ZIP Unix extra field, storing UID and GID
+pub const EXTRA_FIELD_INFO_ZIP_UNIX: u16 = 0x7875;
+
+/// The header ID of the Info-ZIP Unicode path extra field
+pub const EXTRA_FIELD_INFO_ZIP_UNICODE_PATH: u16 = 0x7075;
+
+/// The header ID of the Info-ZIP Unicode comment extra field
+pub const EXTRA_FIELD_INFO_ZIP_UNICODE_COMMENT: u16 = 0x6375;
+
+/// The header ID of the WinZip AES extra field
+pub const EXTRA_FIELD_AES: u16 = 0x9901;
+
+/// Bit 11 of the general purpose flag: filename and comment are UTF-8
+pub const GENERAL_PURPOSE_FLAG_UTF8: u16 = 0x0800;
+
+/// The optional signature of a data descriptor
+pub const SIGNATURE_DATA_DESCRIPTOR: u32 = 134695760; // 0x08074b50 (LE)
+
+/// Bit 3 of the general purpose flag: sizes and CRC32 are in a data descriptor
+pub const GENERAL_PURPOSE_FLAG_DATA_DESCRIPTOR: u16 = 0x0008;
+
+/// Bit 0 of the general purpose flag: the file is encrypted
+pub const GENERAL_PURPOSE_FLAG_ENCRYPTED: u16 = 0x0001;
+
+/// The compression method of files stored without compression
+pub const COMPRESSION_METHOD_STORED: u16 = 0;
+
+/// The compression method of shrunk files
+pub const COMPRESSION_METHOD_SHRUNK: u16 = 1;
+
+/// The compression method of files reduced with the compression factor 1
+pub const COMPRESSION_METHOD_REDUCED_1: u16 = 2;
+
+/// The compression method of files reduced with the compression factor 4
+pub const COMPRESSION_METHOD_REDUCED_4: u16 = 5;
+
+/// The compression method of imploded files
+pub const COMPRESSION_METHOD_IMPLODED: u16 = 6;
+
+/// The compression method of deflated files
+pub const COMPRESSION_METHOD_DEFLATED: u16 = 8;
+
+/// The compression method of files compressed with Deflate64 (enhanced deflating)
+pub const COMPRESSION_METHOD_DEFLATE64: u16 = 9;
+
+/// The compression method of files compressed with BZIP2
+pub const COMPRESSION_METHOD_BZIP2: u16 = 12;
+
+/// The compression method of files compressed with LZMA
+pub const COMPRESSION_METHOD_LZMA: u16 = 14;
+
+/// The compression method of files compressed with Zstandard
+pub const COMPRESSION_METHOD_ZSTANDARD: u16 = 93;
+
+/// The compression method of files compressed with XZ
+pub const COMPRESSION_METHOD_XZ: u16 = 95;
+
+/// Bit 1 of the general purpose flag, for LZMA: the compressed data ends with
+/// an end marker
+pub const GENERAL_PURPOSE_FLAG_LZMA_END_MARKER: u16 = 0x0002;
+
+/// Bit 1 of the general purpose flag, for Implode: the window is 8 KiB instead
+/// of 4 KiB
+pub const GENERAL_PURPOSE_FLAG_IMPLODE_8K_WINDOW: u16 = 0x0002;
+
+/// Bit 2 of the general purpose flag, for Implode: the literals are coded with
+/// a Shannon-Fano tree, so 3 trees are used instead of 2
+pub const GENERAL_PURPOSE_FLAG_IMPLODE_3_TREES: u16 = 0x0004;
+
+/// Bit 6 of the general purpose flag: the file uses strong encryption
+pub const GENERAL_PURPOSE_FLAG_STRONG_ENCRYPTION: u16 = 0x0040;
+
+/// Bit 13 of the general purpose flag: the values of the local file header are
+/// masked because the central directory is encrypted
+pub const GENERAL_PURPOSE_FLAG_MASKED_HEADER: u16 = 0x2000;
+
+/// The header ID of the strong encryption header extra field
+pub const EXTRA_FIELD_STRONG_ENCRYPTION_HEADER: u16 = 0x0017;
+
+/// The signature at the start of the first volume of a split archive.
+/// Same value as the data descriptor signature
+pub const SIGNATURE_SPLIT_ARCHIVE: u32 = 134695760; // 0x08074b50 (LE)
+
+/// The size of a local file header, signature included, without its file name
+/// and extra field
+pub const LOCAL_FILE_HEADER_SIZE: u64 = 30;
+
+/// The size of a central directory file header, signature included, without
+/// its file name, extra field and file comment
+pub const CENTRAL_DIRECTORY_FILE_HEADER_SIZE: u64 = 46;
+
+/// The size of a zip64 end of central directory record, signature included,
+/// without its extensible data sector
+pub const ZIP64_END_OF_CENTRAL_DIRECTORY_RECORD_SIZE: u64 = 56;
+
+/// The compression method of files encrypted with WinZip AES, the real
+/// compression method is in the AES extra field
+pub const COMPRESSION_METHOD_AES: u16 = 99;
+
+/// The size of the encryption header preceding the data of files encrypted
+/// with the traditional PKWARE encryption
+pub const ZIP_CRYPTO_HEADER_SIZE: usize = 12;
+
+/// The size of the password verification value of files encrypted with WinZip AES
+pub const WINZIP_AES_PASSWORD_VERIFICATION_SIZE: usize = 2;
+
+/// The size of the authentication code following the data of files encrypted
+/// with WinZip AES
+pub const WINZIP_AES_AUTHENTICATION_CODE_SIZE: usize = 10;
+
+/// The number of PBKDF2 iterations used to derive the keys of WinZip AES
+pub const WINZIP_AES_KEY_DERIVATION_ITERATIONS: u32 = 1000;
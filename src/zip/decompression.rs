@@ -0,0 +1,227 @@
+//! This module provides the decompression of the data of stored files,
+//! according to their compression method.
+
+use crate::compression::bzip2;
+use crate::compression::deflate::{inflate, inflate64};
+use crate::compression::implode;
+use crate::compression::lzma::{self, LzmaProperties};
+use crate::compression::reduce;
+use crate::compression::shrink;
+use crate::compression::xz;
+use crate::compression::zstd;
+use crate::checksum::Crc32;
+use crate::compression::DecompressionSummary;
+use crate::errors::{ParseError, ParseErrorKind, Structure};
+use crate::util::read_chunk;
+use super::constants;
+use super::encryption;
+use super::model::StoredFile;
+use std::io::{self, Read, Seek, Write};
+
+/// The content of a stored file, once decompressed
+pub struct DecompressedData {
+    /// The decompressed content
+    pub content: Vec<u8>,
+    /// The number of compressed bytes used by the decompression.
+    /// It may be lower than compressed_size if data follows the compressed
+    /// stream, or if the stream ends early.
+    pub consumed: u64,
+    /// The number of compressed bytes available
+    pub compressed_size: u64,
+}
+
+impl DecompressedData {
+    /// The number of compressed bytes left after the end of the compressed stream
+    pub fn unused_bytes(&self) -> u64 {
+        self.compressed_size.saturating_sub(self.consumed)
+    }
+}
+
+/// The content of a stored file, summarized while it's decompressed instead of
+/// being kept in memory
+pub struct ContentSummary {
+    /// The CRC-32 of the decompressed content
+    pub crc32: u32,
+    /// The size of the decompressed content
+    pub size: u64,
+    /// The number of compressed bytes used by the decompression
+    pub consumed: u64,
+    /// The number of compressed bytes available
+    pub compressed_size: u64,
+}
+
+/// Computes the CRC-32 and the size of the content written to it, and passes
+/// the content on to another writer
+struct SummaryWriter<W: Write> {
+    output: W,
+    crc32: Crc32,
+    size: u64,
+}
+
+impl<W: Write> SummaryWriter<W> {
+    /// Create a writer to which nothing was written yet
+    fn new(output: W) -> SummaryWriter<W> {
+        SummaryWriter {
+            output,
+            crc32: Crc32::new(),
+            size: 0,
+        }
+    }
+
+    /// Build the summary of the content written
+    fn finish(self, consumed: u64, compressed_size: u64) -> ContentSummary {
+        ContentSummary {
+            crc32: self.crc32.value(),
+            size: self.size,
+            consumed,
+            compressed_size,
+        }
+    }
+}
+
+impl<W: Write> Write for SummaryWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.output.write(buf)?;
+        self.crc32.update(&buf[..written]);
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+/// Decompress the data of a stored file, read from the archive it was read
+/// from unless it was kept in memory.
+/// Encrypted files are decrypted first with the password, which is required
+/// for them. Their data is loaded in memory, the others are decompressed while
+/// their data is read.
+pub fn decompress_stored_file<R: Read + Seek>(archive: &mut R, stored_file: &StoredFile, password: Option<&[u8]>) -> Result<DecompressedData, ParseError> {
+    let mut content = Vec::new();
+    let (consumed, compressed_size) = decompress_stored_file_to(archive, stored_file, password, &mut content)?;
+    Ok(DecompressedData { content, consumed, compressed_size })
+}
+
+/// Decompress the data of a stored file as decompress_stored_file(), and
+/// summarize its content without keeping it in memory
+pub fn summarize_stored_file<R: Read + Seek>(archive: &mut R, stored_file: &StoredFile, password: Option<&[u8]>) -> Result<ContentSummary, ParseError> {
+    write_stored_file(archive, stored_file, password, io::sink())
+}
+
+/// Decompress the data of a stored file as decompress_stored_file(), write
+/// its content to output as it's decompressed, and summarize it
+pub fn write_stored_file<R: Read + Seek, W: Write>(archive: &mut R, stored_file: &StoredFile, password: Option<&[u8]>, output: W) -> Result<ContentSummary, ParseError> {
+    let mut writer = SummaryWriter::new(output);
+    let (consumed, compressed_size) = decompress_stored_file_to(archive, stored_file, password, &mut writer)?;
+    Ok(writer.finish(consumed, compressed_size))
+}
+
+/// Decompress the data of a stored file to output.
+/// Returns the number of compressed bytes used and available.
+fn decompress_stored_file_to<R: Read + Seek, W: Write>(archive: &mut R, stored_file: &StoredFile, password: Option<&[u8]>, output: &mut W) -> Result<(u64, u64), ParseError> {
+    let local_file_header = &stored_file.local_file_header;
+    let uncompressed_size = match &stored_file.data_descriptor {
+        Some(data_descriptor) => data_descriptor.uncompressed_size,
+        None => local_file_header.uncompressed_size,
+    };
+    let flag = local_file_header.general_purpose_flag;
+    if flag & constants::GENERAL_PURPOSE_FLAG_ENCRYPTED == 0 {
+        let data = stored_file.data_reader(archive)?;
+        let consumed = decompress_to(data, output, local_file_header.compression_method, flag, uncompressed_size)
+            .map_err(|e| e.or_at(stored_file.data_offset))?;
+        return Ok((consumed, stored_file.data_size));
+    }
+
+    let password = password
+        .ok_or(ParseError::new(ParseErrorKind::PasswordRequired, Structure::FileData).at(stored_file.data_offset))?;
+    let data = encryption::decrypt(stored_file, &stored_file.read_data(archive)?, password)?;
+    let consumed = decompress_to(data.as_slice(), output, local_file_header.actual_compression_method(), flag, uncompressed_size)
+        .map_err(|e| e.or_at(stored_file.data_offset))?;
+    Ok((consumed, data.len() as u64))
+}
+
+/// Decompress data compressed with a ZIP compression method, read from input
+/// which holds compressed_size bytes.
+/// The general purpose flag gives options of LZMA and Implode. The uncompressed
+/// size is needed by the methods without end marker: Shrink, Reduce, Implode,
+/// and LZMA when bit 1 of the flag isn't set.
+/// The errors have no offset, the position of input in the archive is unknown.
+pub fn decompress<R: Read>(input: R, compressed_size: u64, compression_method: u16, general_purpose_flag: u16, uncompressed_size: u64) -> Result<DecompressedData, ParseError> {
+    let mut content = Vec::new();
+    let consumed = decompress_to(input, &mut content, compression_method, general_purpose_flag, uncompressed_size)?;
+    Ok(DecompressedData { content, consumed, compressed_size })
+}
+
+/// Decompress data as decompress(), and summarize its content without keeping
+/// it in memory
+pub fn summarize<R: Read>(input: R, compressed_size: u64, compression_method: u16, general_purpose_flag: u16, uncompressed_size: u64) -> Result<ContentSummary, ParseError> {
+    let mut writer = SummaryWriter::new(io::sink());
+    let consumed = decompress_to(input, &mut writer, compression_method, general_purpose_flag, uncompressed_size)?;
+    Ok(writer.finish(consumed, compressed_size))
+}
+
+/// Decompress data as decompress(), writing the content to output.
+/// Returns the number of compressed bytes used.
+fn decompress_to<R: Read, W: Write>(mut input: R, output: &mut W, compression_method: u16, general_purpose_flag: u16, uncompressed_size: u64) -> Result<u64, ParseError> {
+    match compression_method {
+        constants::COMPRESSION_METHOD_STORED => io::copy(&mut input, output)
+            .map_err(|e| ParseError::io(e, Structure::FileData)),
+        constants::COMPRESSION_METHOD_SHRUNK => decompress_with(|| shrink::decompress(input, output, uncompressed_size)),
+        constants::COMPRESSION_METHOD_REDUCED_1..=constants::COMPRESSION_METHOD_REDUCED_4 => {
+            let factor = (compression_method - constants::COMPRESSION_METHOD_REDUCED_1 + 1) as u8;
+            decompress_with(|| reduce::decompress(input, output, factor, uncompressed_size))
+        },
+        constants::COMPRESSION_METHOD_IMPLODED => {
+            let large_window = general_purpose_flag & constants::GENERAL_PURPOSE_FLAG_IMPLODE_8K_WINDOW != 0;
+            let literal_tree = general_purpose_flag & constants::GENERAL_PURPOSE_FLAG_IMPLODE_3_TREES != 0;
+            decompress_with(|| implode::decompress(input, output, large_window, literal_tree, uncompressed_size))
+        },
+        constants::COMPRESSION_METHOD_DEFLATED => decompress_with(|| inflate(input, output)),
+        constants::COMPRESSION_METHOD_DEFLATE64 => decompress_with(|| inflate64(input, output)),
+        constants::COMPRESSION_METHOD_BZIP2 => decompress_with(|| bzip2::decompress(input, output)),
+        constants::COMPRESSION_METHOD_LZMA => {
+            // LZMA data without end marker needs the uncompressed size to know where it ends
+            let uncompressed_size = if general_purpose_flag & constants::GENERAL_PURPOSE_FLAG_LZMA_END_MARKER != 0 {
+                None
+            } else {
+                Some(uncompressed_size)
+            };
+            decompress_with(|| decompress_lzma(input, output, uncompressed_size))
+        },
+        constants::COMPRESSION_METHOD_ZSTANDARD => decompress_with(|| zstd::decompress(input, output)),
+        constants::COMPRESSION_METHOD_XZ => decompress_with(|| xz::decompress(input, output)),
+        _ => Err(ParseError::new(ParseErrorKind::Unsupported(format!("compression method {}", compression_method)), Structure::FileData)),
+    }
+}
+
+/// Decompress LZMA data, preceded by the LZMA header of ZIP files: the version
+/// of the LZMA SDK on 2 bytes, the size of the properties on 2 bytes, then the
+/// properties
+fn decompress_lzma<R: Read, W: Write>(mut input: R, output: &mut W, uncompressed_size: Option<u64>) -> Result<DecompressionSummary, String> {
+    let header = read_chunk(&mut input, 4);
+    if header.len() < 4 {
+        return Err("truncated LZMA header".to_string());
+    }
+    let properties_size = u16::from_le_bytes([header[2], header[3]]) as usize;
+    let properties = read_chunk(&mut input, properties_size);
+    if properties_size < 5 || properties.len() < properties_size {
+        return Err(format!("invalid LZMA properties size {}", properties_size));
+    }
+    let properties = LzmaProperties::new(properties[0], u32::from_le_bytes(properties[1..5].try_into().unwrap()))?;
+
+    let mut summary = lzma::decompress(input, output, &properties, uncompressed_size)?;
+    summary.consumed += (header.len() + properties_size) as u64;
+    Ok(summary)
+}
+
+/// Run a decompression function, and return the number of compressed bytes
+/// it used
+fn decompress_with<F>(decompress_function: F) -> Result<u64, ParseError>
+where
+    F: FnOnce() -> Result<DecompressionSummary, String>,
+{
+    let summary = decompress_function()
+        .map_err(|e| ParseError::new(ParseErrorKind::Decompression(e), Structure::FileData))?;
+    Ok(summary.consumed)
+}
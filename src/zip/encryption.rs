@@ -0,0 +1,239 @@
+//! This module provides the decryption of encrypted files.
+//! The traditional PKWARE encryption (ZipCrypto) is described in section 6.1
+//! of the specification, WinZip AES at https://www.winzip.com/en/support/aes-encryption/
+
+use crate::checksum::crc32_update_byte;
+use crate::crypto::aes::{Aes, BLOCK_SIZE};
+use crate::crypto::hmac::hmac_sha1;
+use crate::crypto::pbkdf2::pbkdf2_hmac_sha1;
+use crate::errors::{ParseError, ParseErrorKind, Structure};
+use super::constants;
+use super::model::StoredFile;
+
+/// Decrypt the data of an encrypted file, with the traditional PKWARE
+/// encryption or WinZip AES depending on the file.
+/// data is the file data, see StoredFile::read_data().
+/// Returns the data still compressed, with the actual compression method
+/// of the local file header.
+pub fn decrypt(stored_file: &StoredFile, data: &[u8], password: &[u8]) -> Result<Vec<u8>, ParseError> {
+    if stored_file.local_file_header.compression_method == constants::COMPRESSION_METHOD_AES {
+        decrypt_winzip_aes(stored_file, data, password)
+    } else {
+        decrypt_zip_crypto(stored_file, data, password)
+    }
+}
+
+/// The traditional PKWARE encryption, also known as ZipCrypto.
+/// It's a stream cipher whose state is made of three 32-bit keys, updated
+/// with each byte of plain text.
+pub struct ZipCrypto {
+    /// The three keys, initialized from the password
+    keys: [u32; 3],
+}
+
+impl ZipCrypto {
+    /// Initialize the keys with a password
+    pub fn new(password: &[u8]) -> ZipCrypto {
+        let mut cipher = ZipCrypto {
+            keys: [0x12345678, 0x23456789, 0x34567890],
+        };
+        for byte in password {
+            cipher.update_keys(*byte);
+        }
+        cipher
+    }
+
+    /// Decrypt data in place
+    pub fn decrypt(&mut self, data: &mut [u8]) {
+        for byte in data {
+            let plain = *byte ^ self.stream_byte();
+            self.update_keys(plain);
+            *byte = plain;
+        }
+    }
+
+    /// Update the keys with a byte of plain text
+    fn update_keys(&mut self, byte: u8) {
+        self.keys[0] = crc32_update_byte(self.keys[0], byte);
+        self.keys[1] = self.keys[1]
+            .wrapping_add(self.keys[0] & 0xff)
+            .wrapping_mul(134775813)
+            .wrapping_add(1);
+        self.keys[2] = crc32_update_byte(self.keys[2], (self.keys[1] >> 24) as u8);
+    }
+
+    /// Compute the next byte of the key stream
+    fn stream_byte(&self) -> u8 {
+        let temp = (self.keys[2] | 2) & 0xffff;
+        ((temp * (temp ^ 1)) >> 8) as u8
+    }
+}
+
+/// Decrypt the data of a file encrypted with the traditional PKWARE encryption.
+/// The data starts with a 12-byte encryption header, whose last byte is
+/// checked against the high byte of the CRC-32, or of the last modification
+/// time when the CRC-32 is in the data descriptor. A wrong password has 1
+/// chance out of 256 to pass this check.
+/// Returns the data without the encryption header, still compressed.
+pub fn decrypt_zip_crypto(stored_file: &StoredFile, data: &[u8], password: &[u8]) -> Result<Vec<u8>, ParseError> {
+    let local_file_header = &stored_file.local_file_header;
+    if local_file_header.general_purpose_flag & constants::GENERAL_PURPOSE_FLAG_ENCRYPTED == 0 {
+        return Err(decryption_error(stored_file, "the file is not encrypted".to_string()));
+    }
+    if local_file_header.uses_strong_encryption()
+            || local_file_header.compression_method == constants::COMPRESSION_METHOD_AES {
+        return Err(decryption_error(stored_file, "the file doesn't use the traditional PKWARE encryption".to_string()));
+    }
+    if data.len() < constants::ZIP_CRYPTO_HEADER_SIZE {
+        return Err(decryption_error(stored_file, "the encryption header is truncated".to_string()));
+    }
+
+    let mut data = data.to_vec();
+    ZipCrypto::new(password).decrypt(&mut data);
+
+    let has_data_descriptor = local_file_header.general_purpose_flag & constants::GENERAL_PURPOSE_FLAG_DATA_DESCRIPTOR != 0;
+    let check_byte = if has_data_descriptor {
+        (local_file_header.file_last_modification_time >> 8) as u8
+    } else {
+        (local_file_header.crc32 >> 24) as u8
+    };
+    if data[constants::ZIP_CRYPTO_HEADER_SIZE - 1] != check_byte {
+        return Err(ParseError::new(ParseErrorKind::WrongPassword, Structure::FileData).at(stored_file.data_offset));
+    }
+
+    data.drain(..constants::ZIP_CRYPTO_HEADER_SIZE);
+    Ok(data)
+}
+
+/// Decrypt the data of a file encrypted with WinZip AES (AE-1 or AE-2).
+/// The data is made of a salt, a password verification value, the encrypted
+/// data, then an authentication code. The encryption key, the authentication
+/// key and the password verification value are derived from the password
+/// and the salt with PBKDF2-HMAC-SHA1.
+/// The authentication code is checked before decrypting.
+/// Returns the decrypted data, still compressed with the method of the AES
+/// extra field.
+pub fn decrypt_winzip_aes(stored_file: &StoredFile, data: &[u8], password: &[u8]) -> Result<Vec<u8>, ParseError> {
+    let local_file_header = &stored_file.local_file_header;
+    if local_file_header.compression_method != constants::COMPRESSION_METHOD_AES {
+        return Err(decryption_error(stored_file, "the file isn't encrypted with WinZip AES".to_string()));
+    }
+    let aes = local_file_header.extra_field.aes()
+        .ok_or(ParseError::new(ParseErrorKind::NotFound, Structure::AesExtraField))?;
+    let key_size = match aes.strength {
+        1 => 16,
+        2 => 24,
+        3 => 32,
+        strength => return Err(ParseError::invalid_value(Structure::AesExtraField, "strength", format!("unknown AES strength {}", strength))),
+    };
+
+    // The salt is half the size of the key
+    let salt_size = key_size / 2;
+    if data.len() < salt_size + constants::WINZIP_AES_PASSWORD_VERIFICATION_SIZE + constants::WINZIP_AES_AUTHENTICATION_CODE_SIZE {
+        return Err(decryption_error(stored_file, "the encrypted data is truncated".to_string()));
+    }
+    let (salt, data) = data.split_at(salt_size);
+    let (password_verification, data) = data.split_at(constants::WINZIP_AES_PASSWORD_VERIFICATION_SIZE);
+    let (encrypted_data, authentication_code) = data.split_at(data.len() - constants::WINZIP_AES_AUTHENTICATION_CODE_SIZE);
+
+    let keys = pbkdf2_hmac_sha1(
+        password,
+        salt,
+        constants::WINZIP_AES_KEY_DERIVATION_ITERATIONS,
+        2 * key_size + constants::WINZIP_AES_PASSWORD_VERIFICATION_SIZE,
+    );
+    let (encryption_key, keys) = keys.split_at(key_size);
+    let (authentication_key, expected_password_verification) = keys.split_at(key_size);
+    if password_verification != expected_password_verification {
+        return Err(ParseError::new(ParseErrorKind::WrongPassword, Structure::FileData).at(stored_file.data_offset));
+    }
+
+    // The authentication code is computed on the encrypted data
+    let expected_authentication_code = hmac_sha1(authentication_key, encrypted_data);
+    if authentication_code != &expected_authentication_code[..constants::WINZIP_AES_AUTHENTICATION_CODE_SIZE] {
+        return Err(decryption_error(stored_file, "wrong authentication code, the data is damaged or was modified".to_string()));
+    }
+
+    let mut decrypted_data = encrypted_data.to_vec();
+    let cipher = Aes::new(encryption_key)
+        .map_err(|e| decryption_error(stored_file, e))?;
+    // WinZip uses AES in counter mode, with a little-endian counter starting at 1
+    for (counter, chunk) in (1u128..).zip(decrypted_data.chunks_mut(BLOCK_SIZE)) {
+        let mut key_stream = counter.to_le_bytes();
+        cipher.encrypt_block(&mut key_stream);
+        for (byte, key_byte) in chunk.iter_mut().zip(key_stream) {
+            *byte ^= key_byte;
+        }
+    }
+    Ok(decrypted_data)
+}
+
+/// Create an error about the decryption of the data of a file
+fn decryption_error(stored_file: &StoredFile, reason: String) -> ParseError {
+    ParseError::new(ParseErrorKind::Decryption(reason), Structure::FileData)
+        .at(stored_file.data_offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::zip::decompression::decompress_stored_file;
+    use crate::zip::reader::{ParsingStrategy, ZipFileReader};
+    use super::*;
+
+    const TEXT: &[u8] = b"The quick brown fox jumps over the lazy dog.\n";
+
+    /// Decompress a file of a fixture archive with a password
+    fn decompress_fixture(archive: &[u8], filename: &str, password: &[u8]) -> Result<Vec<u8>, ParseError> {
+        let zip_file = ZipFileReader::read_bytes(archive, ParsingStrategy::Sequential).unwrap();
+        let stored_file = zip_file.stored_files.iter()
+            .find(|stored_file| stored_file.local_file_header.filename == filename)
+            .unwrap();
+        decompress_stored_file(&mut Cursor::new(archive), stored_file, Some(password))
+            .map(|decompressed_data| decompressed_data.content)
+    }
+
+    // zipcrypto.zip was created by Info-ZIP zip 3.0 with the password
+    // "password", and holds a stored file and a deflated file
+    const ZIP_CRYPTO: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/zipcrypto.zip"));
+
+    #[test]
+    fn decrypts_zip_crypto_stored_file() {
+        let content = decompress_fixture(ZIP_CRYPTO, "stored.txt", b"password").unwrap();
+        assert_eq!(content, TEXT.repeat(40));
+    }
+
+    #[test]
+    fn decrypts_zip_crypto_deflated_file() {
+        let content = decompress_fixture(ZIP_CRYPTO, "deflated.txt", b"password").unwrap();
+        assert_eq!(content, TEXT.repeat(40));
+    }
+
+    #[test]
+    fn rejects_wrong_zip_crypto_password() {
+        let error = decompress_fixture(ZIP_CRYPTO, "deflated.txt", b"wrong").unwrap_err();
+        assert!(matches!(error.kind, ParseErrorKind::WrongPassword));
+    }
+
+    // winzip_aes.zip holds an AE-1 file encrypted with AES-256 and an AE-2
+    // file encrypted with AES-128, with the password "password"
+    const WINZIP_AES: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/winzip_aes.zip"));
+
+    #[test]
+    fn decrypts_winzip_aes_256_ae_1_file() {
+        let content = decompress_fixture(WINZIP_AES, "ae1_aes256.txt", b"password").unwrap();
+        assert_eq!(content, TEXT.repeat(40));
+    }
+
+    #[test]
+    fn decrypts_winzip_aes_128_ae_2_file() {
+        let content = decompress_fixture(WINZIP_AES, "ae2_aes128.txt", b"password").unwrap();
+        assert_eq!(content, &TEXT.repeat(40)[..100]);
+    }
+
+    #[test]
+    fn rejects_wrong_winzip_aes_password() {
+        let error = decompress_fixture(WINZIP_AES, "ae1_aes256.txt", b"wrong").unwrap_err();
+        assert!(matches!(error.kind, ParseErrorKind::WrongPassword));
+    }
+}
@@ -0,0 +1,238 @@
+//! This module extracts the files of an archive to a directory.
+//! The filenames come from the archive and can't be trusted: the ones which
+//! could write outside of the directory ("zip slip") are rejected.
+
+use std::fs::{self, OpenOptions};
+use std::io::{BufWriter, ErrorKind, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use super::decompression::write_stored_file;
+use super::model::{StoredFile, ZipFile};
+use super::verification::verify_crc32;
+
+/// A file which wasn't extracted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedEntry {
+    /// The filename, as stored in the archive
+    pub filename: String,
+    /// Why the file wasn't extracted
+    pub reason: String,
+}
+
+/// The result of an extraction
+#[derive(Debug, Default)]
+pub struct ExtractionReport {
+    /// The paths of the files and directories written
+    pub extracted: Vec<PathBuf>,
+    /// The files which weren't extracted
+    pub rejected: Vec<RejectedEntry>,
+}
+
+/// Extract the files of an archive to a directory, created if needed.
+/// The data of the files is read from archive, the one zip_file was read from.
+/// Encrypted files are decrypted with the password. A file is rejected if its
+/// path is unsafe, if it already exists, if it can't be decompressed, if its
+/// CRC-32 is wrong, or if it can't be written; the other files are still
+/// extracted. Existing files are never overwritten, so of several files with
+/// the same name only the first one is extracted.
+/// Filenames ending with a slash are directories.
+pub fn extract<R: Read + Seek>(archive: &mut R, zip_file: &ZipFile, directory: &Path, password: Option<&[u8]>) -> Result<ExtractionReport, String> {
+    fs::create_dir_all(directory)
+        .map_err(|e| format!("Unable to create {}: {}", directory.display(), e))?;
+
+    let mut report = ExtractionReport::default();
+    for stored_file in &zip_file.stored_files {
+        let filename = &stored_file.local_file_header.filename;
+        let result = sanitize_path(filename)
+            .and_then(|relative_path| {
+                let path = directory.join(relative_path);
+                if filename.ends_with('/') || filename.ends_with('\\') {
+                    fs::create_dir_all(&path)
+                        .map_err(|e| format!("unable to create the directory: {}", e))?;
+                    return Ok(path);
+                }
+
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)
+                        .map_err(|e| format!("unable to create the directory {}: {}", parent.display(), e))?;
+                }
+                let file = OpenOptions::new().write(true).create_new(true).open(&path)
+                    .map_err(|e| match e.kind() {
+                        ErrorKind::AlreadyExists => "the file already exists".to_string(),
+                        _ => format!("unable to create the file: {}", e),
+                    })?;
+                // The file was created for this extraction, it's removed if the
+                // extraction fails
+                extract_file(archive, zip_file, stored_file, password, file)
+                    .inspect_err(|_| {
+                        let _ = fs::remove_file(&path);
+                    })?;
+                Ok(path)
+            });
+
+        match result {
+            Ok(path) => report.extracted.push(path),
+            Err(reason) => report.rejected.push(RejectedEntry {
+                filename: filename.clone(),
+                reason,
+            }),
+        }
+    }
+    Ok(report)
+}
+
+/// Decompress a file into a new file, and check the CRC-32 of its content
+fn extract_file<R: Read + Seek>(archive: &mut R, zip_file: &ZipFile, stored_file: &StoredFile, password: Option<&[u8]>, file: fs::File) -> Result<(), String> {
+    let mut writer = BufWriter::new(file);
+    let summary = write_stored_file(archive, stored_file, password, &mut writer)
+        .map_err(|e| e.to_string())?;
+    writer.flush()
+        .map_err(|e| format!("unable to write the file: {}", e))?;
+    let verification = verify_crc32(stored_file, zip_file.central_directory.as_ref(), summary.crc32);
+    if let Some(mismatch) = verification.and_then(|verification| verification.mismatches.first().cloned()) {
+        return Err(format!("{} CRC wrong", mismatch.source));
+    }
+    Ok(())
+}
+
+/// Convert a filename from an archive to a relative path which can't go
+/// outside of the extraction directory.
+/// Backslashes are handled as separators, and the empty and "." components are
+/// dropped. Absolute paths, drive letters, ".." components and colons (used by
+/// Windows for drives and alternate data streams) are rejected.
+pub fn sanitize_path(filename: &str) -> Result<PathBuf, String> {
+    let normalized = filename.replace('\\', "/");
+    if normalized.starts_with('/') {
+        return Err("absolute path".to_string());
+    }
+    let bytes = normalized.as_bytes();
+    if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        return Err("path with a drive letter".to_string());
+    }
+
+    let mut path = PathBuf::new();
+    for component in normalized.split('/') {
+        match component {
+            "" | "." => (),
+            ".." => return Err("path with a parent directory component (..)".to_string()),
+            _ if component.contains(':') => return Err(format!("colon in the path component {}", component)),
+            _ if component.contains('\0') => return Err("NUL character in the path".to_string()),
+            _ => path.push(component),
+        }
+    }
+    if path.as_os_str().is_empty() {
+        return Err("empty path".to_string());
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::io::Cursor;
+    use crate::zip::reader::{ParsingStrategy, ZipFileReader};
+
+    /// Extract a fixture archive to an empty directory, named after the test
+    fn extract_fixture(name: &str, test: &str, existing_files: &[(&str, &[u8])]) -> (ExtractionReport, PathBuf) {
+        let directory = env::temp_dir().join(format!("archive-analyzer-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        for (filename, content) in existing_files {
+            fs::write(directory.join(filename), content).unwrap();
+        }
+        let data = fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)).unwrap();
+        let zip_file = ZipFileReader::read_bytes(&data, ParsingStrategy::Sequential).unwrap();
+        let report = extract(&mut Cursor::new(&data), &zip_file, &directory, None).unwrap();
+        (report, directory)
+    }
+
+    /// The reason why a file was rejected
+    fn rejection<'a>(report: &'a ExtractionReport, filename: &str) -> Option<&'a str> {
+        report.rejected.iter()
+            .find(|rejected| rejected.filename == filename)
+            .map(|rejected| rejected.reason.as_str())
+    }
+
+    #[test]
+    fn extracts_file_with_the_right_crc() {
+        let (report, directory) = extract_fixture("crc.zip", "right-crc", &[]);
+        assert_eq!(report.extracted, vec![directory.join("good.txt")]);
+        assert_eq!(fs::read(directory.join("good.txt")).unwrap(), b"checked\n");
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn removes_file_with_a_wrong_crc() {
+        let (report, directory) = extract_fixture("crc.zip", "wrong-crc", &[]);
+        assert_eq!(rejection(&report, "header.txt"), Some("header CRC wrong"));
+        assert_eq!(rejection(&report, "central.txt"), Some("central directory CRC wrong"));
+        assert_eq!(rejection(&report, "descriptor.txt"), Some("descriptor CRC wrong"));
+        for filename in ["header.txt", "central.txt", "descriptor.txt"] {
+            assert!(!directory.join(filename).exists());
+        }
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn does_not_overwrite_existing_file() {
+        let (report, directory) = extract_fixture("crc.zip", "existing", &[("good.txt", b"existing\n")]);
+        assert_eq!(rejection(&report, "good.txt"), Some("the file already exists"));
+        assert_eq!(fs::read(directory.join("good.txt")).unwrap(), b"existing\n");
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn extracts_only_the_first_of_files_with_the_same_name() {
+        // Two files named x.txt, "hidden" then "visible"
+        let (report, directory) = extract_fixture("unreferenced.zip", "same-name", &[]);
+        assert_eq!(report.extracted, vec![directory.join("x.txt")]);
+        assert_eq!(rejection(&report, "x.txt"), Some("the file already exists"));
+        assert_eq!(fs::read(directory.join("x.txt")).unwrap(), b"hidden\n");
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn accepts_nested_path() {
+        assert_eq!(sanitize_path("dir/sub/file.txt"), Ok(PathBuf::from("dir").join("sub").join("file.txt")));
+    }
+
+    #[test]
+    fn drops_empty_and_current_directory_components() {
+        assert_eq!(sanitize_path("dir//./file.txt"), Ok(PathBuf::from("dir").join("file.txt")));
+    }
+
+    #[test]
+    fn rejects_absolute_path() {
+        assert!(sanitize_path("/etc/passwd").is_err());
+    }
+
+    #[test]
+    fn rejects_parent_directory_in_the_middle() {
+        assert!(sanitize_path("dir/../../file.txt").is_err());
+    }
+
+    #[test]
+    fn handles_backslashes_as_separators() {
+        assert_eq!(sanitize_path("dir\\file.txt"), Ok(PathBuf::from("dir").join("file.txt")));
+        assert!(sanitize_path("\\server\\share\\file.txt").is_err());
+        assert!(sanitize_path("dir\\..\\..\\file.txt").is_err());
+    }
+
+    #[test]
+    fn rejects_drive_letter() {
+        assert!(sanitize_path("C:/Windows/file.txt").is_err());
+        assert!(sanitize_path("C:file.txt").is_err());
+        assert!(sanitize_path("dir/C:/file.txt").is_err());
+    }
+
+    #[test]
+    fn rejects_nul_character() {
+        assert!(sanitize_path("file.txt\0.exe").is_err());
+    }
+
+    #[test]
+    fn rejects_empty_name() {
+        assert!(sanitize_path("").is_err());
+        assert!(sanitize_path("./").is_err());
+    }
+}
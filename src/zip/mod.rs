@@ -1,4 +1,18 @@
 
+pub mod consistency;
 pub mod constants;
+pub mod decompression;
+pub mod encryption;
+pub mod extraction;
 pub mod model;
-pub mod reader;
\ No newline at end of file
+pub mod reader;
+pub mod split;
+pub mod stream;
+pub mod verification;
+/// Read an archive of the test fixtures with the given strategy
+#[cfg(test)]
+pub(crate) fn read_fixture(name: &str, strategy: reader::ParsingStrategy) -> model::ZipFile {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+    let data = std::fs::read(&path).unwrap();
+    reader::ZipFileReader::read_bytes(&data, strategy).unwrap()
+}
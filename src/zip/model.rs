@@ -3,6 +3,14 @@
 //! Other docs about structure at https://users.cs.jmu.edu/buchhofp/forensics/formats/pkzip-printable.html
 //! and https://docs.fileformat.com/compression/zip/
 
+use crate::errors::{ParseError, Structure};
+use crate::util::civil_from_days;
+use super::constants::{COMPRESSION_METHOD_AES, GENERAL_PURPOSE_FLAG_MASKED_HEADER, GENERAL_PURPOSE_FLAG_STRONG_ENCRYPTION};
+use super::decompression::ContentSummary;
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
 /// Represents a local file header
 pub struct LocalFileHeader {
     /// The minimum version to extract
@@ -40,6 +48,8 @@ pub struct LocalFileHeader {
     /// 15-17: reserved
     /// 18: compressed using IBM TERSE
     /// 19: IBM LZ77 z
+    /// 93: Zstandard
+    /// 95: XZ
     /// 98: PPMd version I, Rev 1
     pub compression_method: u16,
     /// Last modification time of the file
@@ -58,40 +68,568 @@ pub struct LocalFileHeader {
     pub crc32: u32,
     /// Compressed size of the file
     /// if archive is in ZIP64 format, this filed is 0xffffffff and the length
-    /// is stored in the extra field
-    pub compressed_size: u32,
+    /// is stored in the extra field. In that case, the value read from the
+    /// zip64 extended information is stored here.
+    pub compressed_size: u64,
     /// Uncompressed size of the file
     /// if archive is in ZIP64 format, this filed is 0xffffffff and the length is
-    /// stored in the extra field
-    pub uncompressed_size: u32,
-    /// The filename
+    /// stored in the extra field. In that case, the value read from the zip64
+    /// extended information is stored here.
+    pub uncompressed_size: u64,
+    /// The filename, decoded according to filename_encoding
     pub filename: String,
+    /// The filename, as stored in the archive
+    pub filename_raw: Vec<u8>,
+    /// The encoding used to decode the filename
+    pub filename_encoding: TextEncoding,
     /// The extra field
     /// Used to store additional information. The field consistes of a sequence of
     /// header and data pairs, where the header has a 2 byte identifier and a 2
     /// bytes data size field.
-    pub extra_field: Vec<u8>,
+    pub extra_field: ExtraField,
+    /// The zip64 extended information, read from the extra field.
+    /// Only present if at least one of the sizes is 0xffffffff.
+    pub zip64_extended_information: Option<Zip64ExtendedInformation>,
+}
+
+impl LocalFileHeader {
+    /// Whether the values of the header are masked, because the central
+    /// directory is encrypted.
+    /// In that case, the CRC32 and sizes are set to 0 and the filename may be
+    /// replaced by a meaningless value.
+    pub fn is_masked(&self) -> bool {
+        self.general_purpose_flag & GENERAL_PURPOSE_FLAG_MASKED_HEADER != 0
+    }
+
+    /// Whether the file uses strong encryption
+    pub fn uses_strong_encryption(&self) -> bool {
+        self.general_purpose_flag & GENERAL_PURPOSE_FLAG_STRONG_ENCRYPTION != 0
+    }
+
+    /// The compression method of the data once decrypted.
+    /// Files encrypted with WinZip AES store it in their AES extra field.
+    pub fn actual_compression_method(&self) -> u16 {
+        match self.extra_field.aes() {
+            Some(aes) if self.compression_method == COMPRESSION_METHOD_AES => aes.compression_method,
+            _ => self.compression_method,
+        }
+    }
+
+    /// Decode the MS-DOS last modification date and time
+    pub fn last_modification_dos(&self) -> DosDateTime {
+        DosDateTime::new(self.file_last_modification_date, self.file_last_modification_time)
+    }
+
+    /// Get the most precise last modification time available, from the
+    /// extra field or from the MS-DOS date and time
+    pub fn last_modification(&self) -> Timestamp {
+        Timestamp::most_precise(&self.extra_field, &self.last_modification_dos())
+    }
+}
+
+/// The encoding used to decode a filename or a comment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+    /// IBM Code Page 437, the legacy encoding of the ZIP format
+    Cp437,
+    /// UTF-8, announced by bit 11 of the general purpose flag
+    Utf8,
+    /// UTF-8, read from an Info-ZIP Unicode path or comment extra field
+    InfoZipUnicode,
+}
+
+/// Represents an MS-DOS date and time, as stored in the headers.
+/// The values are decoded as is, even if they are invalid.
+pub struct DosDateTime {
+    /// The year, from 1980 to 2107
+    pub year: u16,
+    /// The month, valid from 1 to 12
+    pub month: u8,
+    /// The day, valid from 1 to the number of days in the month
+    pub day: u8,
+    /// The hour, valid from 0 to 23
+    pub hour: u8,
+    /// The minute, valid from 0 to 59
+    pub minute: u8,
+    /// The second, always even, valid from 0 to 58
+    pub second: u8,
+}
+
+impl DosDateTime {
+    /// Decode the bitfields of an MS-DOS date and time
+    pub fn new(date: u16, time: u16) -> DosDateTime {
+        DosDateTime {
+            year: 1980 + (date >> 9),
+            month: ((date >> 5) & 0x0f) as u8,
+            day: (date & 0x1f) as u8,
+            hour: (time >> 11) as u8,
+            minute: ((time >> 5) & 0x3f) as u8,
+            second: ((time & 0x1f) * 2) as u8,
+        }
+    }
+
+    /// List the invalid values of the date and time
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.month == 0 || self.month > 12 {
+            errors.push(format!("invalid month {}", self.month));
+            if self.day == 0 {
+                errors.push("invalid day 0".to_string());
+            }
+        } else if self.day == 0 || self.day > days_in_month(self.year as i64, self.month) {
+            errors.push(format!("invalid day {} for month {}", self.day, self.month));
+        }
+        if self.hour > 23 {
+            errors.push(format!("invalid hour {}", self.hour));
+        }
+        if self.minute > 59 {
+            errors.push(format!("invalid minute {}", self.minute));
+        }
+        if self.second > 59 {
+            errors.push(format!("invalid second {}", self.second));
+        }
+        errors
+    }
+
+    /// Whether all the values of the date and time are valid
+    pub fn is_valid(&self) -> bool {
+        self.errors().is_empty()
+    }
+}
+
+impl fmt::Display for DosDateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02} {:02}:{:02}:{:02}", self.year, self.month, self.day, self.hour, self.minute, self.second)
+    }
+}
+
+/// Get the number of days of a month of the Gregorian calendar
+fn days_in_month(year: i64, month: u8) -> u8 {
+    match month {
+        4 | 6 | 9 | 11 => 30,
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        _ => 31,
+    }
+}
+
+/// The structure a timestamp was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampSource {
+    /// MS-DOS date and time of the header, 2 seconds precision, local time
+    Dos,
+    /// Extended timestamp extra field, 1 second precision, UTC
+    ExtendedTimestamp,
+    /// NTFS extra field, 100 nanoseconds precision, UTC
+    Ntfs,
+}
+
+/// Represents a date and time read from an archive
+pub struct Timestamp {
+    /// The year
+    pub year: i64,
+    /// The month, from 1 to 12
+    pub month: u8,
+    /// The day, from 1 to 31
+    pub day: u8,
+    /// The hour, from 0 to 23
+    pub hour: u8,
+    /// The minute, from 0 to 59
+    pub minute: u8,
+    /// The second, from 0 to 59
+    pub second: u8,
+    /// The nanoseconds, always 0 unless read from the NTFS extra field
+    pub nanosecond: u32,
+    /// Where the timestamp was read from.
+    /// MS-DOS times have no time zone, the other ones are UTC.
+    pub source: TimestampSource,
+}
+
+impl Timestamp {
+    /// Create a timestamp from an MS-DOS date and time.
+    /// Returns None if the date and time are invalid.
+    pub fn from_dos(dos: &DosDateTime) -> Option<Timestamp> {
+        if !dos.is_valid() {
+            return None;
+        }
+        Some(Timestamp {
+            year: dos.year as i64,
+            month: dos.month,
+            day: dos.day,
+            hour: dos.hour,
+            minute: dos.minute,
+            second: dos.second,
+            nanosecond: 0,
+            source: TimestampSource::Dos,
+        })
+    }
+
+    /// Create a timestamp from a number of seconds since 1970-01-01 00:00:00 UTC
+    pub fn from_unix(seconds: i64, nanosecond: u32, source: TimestampSource) -> Timestamp {
+        let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+        let seconds_of_day = seconds.rem_euclid(86400);
+        Timestamp {
+            year,
+            month,
+            day,
+            hour: (seconds_of_day / 3600) as u8,
+            minute: (seconds_of_day / 60 % 60) as u8,
+            second: (seconds_of_day % 60) as u8,
+            nanosecond,
+            source,
+        }
+    }
+
+    /// Create a timestamp from a number of 100 nanoseconds intervals since
+    /// 1601-01-01 00:00:00 UTC, as stored in the NTFS extra field
+    pub fn from_ntfs(ticks: u64) -> Timestamp {
+        // Number of seconds between 1601-01-01 and 1970-01-01
+        let seconds = (ticks / 10_000_000) as i64 - 11_644_473_600;
+        let nanosecond = (ticks % 10_000_000) as u32 * 100;
+        Timestamp::from_unix(seconds, nanosecond, TimestampSource::Ntfs)
+    }
+
+    /// Get the most precise last modification time available.
+    /// The NTFS extra field is preferred, then the extended timestamp, then
+    /// the MS-DOS date and time.
+    /// If there is no extra field and the MS-DOS value is invalid, the invalid
+    /// values are kept as is.
+    pub fn most_precise(extra_field: &ExtraField, dos: &DosDateTime) -> Timestamp {
+        let ntfs = extra_field.records.iter().find_map(|record| match &record.content {
+            ExtraFieldContent::Ntfs(ntfs) => ntfs.modification_time,
+            _ => None,
+        });
+        if let Some(ticks) = ntfs {
+            return Timestamp::from_ntfs(ticks);
+        }
+
+        let extended_timestamp = extra_field.records.iter().find_map(|record| match &record.content {
+            ExtraFieldContent::ExtendedTimestamp(timestamp) => timestamp.modification_time,
+            _ => None,
+        });
+        if let Some(seconds) = extended_timestamp {
+            return Timestamp::from_unix(seconds as i64, 0, TimestampSource::ExtendedTimestamp);
+        }
+
+        Timestamp::from_dos(dos).unwrap_or(Timestamp {
+            year: dos.year as i64,
+            month: dos.month,
+            day: dos.day,
+            hour: dos.hour,
+            minute: dos.minute,
+            second: dos.second,
+            nanosecond: 0,
+            source: TimestampSource::Dos,
+        })
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02} {:02}:{:02}:{:02}", self.year, self.month, self.day, self.hour, self.minute, self.second)?;
+        match self.source {
+            TimestampSource::Dos => Ok(()),
+            TimestampSource::ExtendedTimestamp => write!(f, " UTC"),
+            TimestampSource::Ntfs => write!(f, ".{:07} UTC", self.nanosecond / 100),
+        }
+    }
+}
+
+/// Represents an extra field, split into its records
+pub struct ExtraField {
+    /// The raw bytes of the extra field, as stored in the archive
+    pub raw: Vec<u8>,
+    /// The records of the extra field, in the order they are stored
+    pub records: Vec<ExtraFieldRecord>,
+}
+
+impl ExtraField {
+    /// Find the first record with the given header ID
+    pub fn find(&self, header_id: u16) -> Option<&ExtraFieldRecord> {
+        self.records.iter().find(|record| record.header_id == header_id)
+    }
+
+    /// Get the content of the WinZip AES record, if any
+    pub fn aes(&self) -> Option<&AesExtraField> {
+        self.records.iter().find_map(|record| match &record.content {
+            ExtraFieldContent::Aes(aes) => Some(aes),
+            _ => None,
+        })
+    }
+
+    /// Get the content of the Info-ZIP Unicode path record, if any
+    pub fn unicode_path(&self) -> Option<&InfoZipUnicode> {
+        self.records.iter().find_map(|record| match &record.content {
+            ExtraFieldContent::InfoZipUnicodePath(unicode) => Some(unicode),
+            _ => None,
+        })
+    }
+
+    /// Get the content of the Info-ZIP Unicode comment record, if any
+    pub fn unicode_comment(&self) -> Option<&InfoZipUnicode> {
+        self.records.iter().find_map(|record| match &record.content {
+            ExtraFieldContent::InfoZipUnicodeComment(unicode) => Some(unicode),
+            _ => None,
+        })
+    }
+
+    /// Whether at least one of the records is malformed
+    pub fn is_malformed(&self) -> bool {
+        self.records.iter().any(|record| matches!(record.content, ExtraFieldContent::Malformed(_)))
+    }
+}
+
+/// Represents a record of an extra field
+pub struct ExtraFieldRecord {
+    /// The header ID, identifying the type of the record
+    pub header_id: u16,
+    /// The size of the data, as declared in the record
+    pub data_size: u16,
+    /// The data of the record.
+    /// May be shorter than data_size if the record is truncated
+    pub data: Vec<u8>,
+    /// The offset of the record, relative to the start of the extra field
+    pub offset: usize,
+    /// The decoded content of the record
+    pub content: ExtraFieldContent,
+}
+
+/// Represents the decoded content of an extra field record
+pub enum ExtraFieldContent {
+    /// Zip64 extended information (0x0001)
+    Zip64ExtendedInformation(Zip64ExtendedInformation),
+    /// NTFS file times (0x000a)
+    Ntfs(NtfsTimes),
+    /// Extended timestamp (0x5455)
+    ExtendedTimestamp(ExtendedTimestamp),
+    /// Info-ZIP Unix UID and GID (0x7875)
+    InfoZipUnix(InfoZipUnix),
+    /// Info-ZIP Unicode path (0x7075)
+    InfoZipUnicodePath(InfoZipUnicode),
+    /// Info-ZIP Unicode comment (0x6375)
+    InfoZipUnicodeComment(InfoZipUnicode),
+    /// Strong encryption header (0x0017)
+    StrongEncryptionHeader(StrongEncryptionHeader),
+    /// WinZip AES encryption (0x9901)
+    Aes(AesExtraField),
+    /// A record whose header ID is not supported, only the raw data is kept
+    Unknown,
+    /// A record whose length or content is invalid, with the reason
+    Malformed(String),
+}
+
+/// Represents the zip64 extended information extra field (header ID 0x0001).
+/// Each value is present only if the matching field of the header is set to
+/// 0xffffffff (or 0xffff for the disk number).
+/// When decoded outside of its header, the values are assigned in their order
+/// of storage, as far as the data size allows.
+pub struct Zip64ExtendedInformation {
+    /// The uncompressed size of the file
+    pub uncompressed_size: Option<u64>,
+    /// The compressed size of the file
+    pub compressed_size: Option<u64>,
+    /// The offset of the local file header, only in the central directory
+    pub local_file_header_offset: Option<u64>,
+    /// The disk number where the file starts, only in the central directory
+    pub disk_start: Option<u32>,
+}
+
+/// Represents the NTFS extra field (header ID 0x000a).
+/// Times are stored as the number of 100 nanoseconds intervals since
+/// 1601-01-01 00:00:00 UTC.
+pub struct NtfsTimes {
+    /// Last modification time
+    pub modification_time: Option<u64>,
+    /// Last access time
+    pub access_time: Option<u64>,
+    /// Creation time
+    pub creation_time: Option<u64>,
+}
+
+/// Represents the extended timestamp extra field (header ID 0x5455).
+/// Times are stored as Unix timestamps, in seconds.
+/// In the central directory, only the modification time is stored even if
+/// the flags announce the other ones.
+pub struct ExtendedTimestamp {
+    /// The flags telling which times are present
+    /// Bit 0: modification time
+    /// Bit 1: access time
+    /// Bit 2: creation time
+    pub flags: u8,
+    /// Last modification time
+    pub modification_time: Option<i32>,
+    /// Last access time
+    pub access_time: Option<i32>,
+    /// Creation time
+    pub creation_time: Option<i32>,
+}
+
+/// Represents the Info-ZIP Unix extra field (header ID 0x7875)
+pub struct InfoZipUnix {
+    /// The version of the extra field, currently 1
+    pub version: u8,
+    /// The user ID
+    pub uid: u64,
+    /// The group ID
+    pub gid: u64,
+}
+
+/// Represents the Info-ZIP Unicode path and comment extra fields
+/// (header IDs 0x7075 and 0x6375)
+pub struct InfoZipUnicode {
+    /// The version of the extra field, currently 1
+    pub version: u8,
+    /// The CRC32 of the filename or comment stored in the header.
+    /// Used to check the extra field is still up to date.
+    pub crc32: u32,
+    /// The UTF-8 filename or comment
+    pub value: String,
+}
+
+/// Represents the WinZip AES extra field (header ID 0x9901)
+pub struct AesExtraField {
+    /// The vendor version
+    /// 1: AE-1
+    /// 2: AE-2
+    pub vendor_version: u16,
+    /// The vendor ID, always "AE"
+    pub vendor_id: String,
+    /// The AES key strength
+    /// 1: 128 bits
+    /// 2: 192 bits
+    /// 3: 256 bits
+    pub strength: u8,
+    /// The compression method used before encryption
+    pub compression_method: u16,
+}
+
+/// Represents the strong encryption header extra field (header ID 0x0017)
+pub struct StrongEncryptionHeader {
+    /// The format of the record, currently 2
+    pub format: u16,
+    /// The encryption algorithm, see encryption_algorithm_name()
+    pub algorithm_id: u16,
+    /// The length of the encryption key, in bits
+    pub bit_length: u16,
+    /// The processing flags
+    /// 0x0001: password is required to decrypt
+    /// 0x0002: certificates only
+    /// 0x0003: password or certificate required to decrypt
+    pub flags: u16,
+    /// The certificate data, used by certificate processing
+    pub certificate_data: Vec<u8>,
+}
+
+/// Get the name of a strong encryption algorithm from its identifier
+pub fn encryption_algorithm_name(algorithm_id: u16) -> &'static str {
+    match algorithm_id {
+        0x6601 => "DES",
+        0x6602 => "RC2 (version needed to extract < 5.2)",
+        0x6603 => "3DES 168",
+        0x6609 => "3DES 112",
+        0x660e => "AES 128",
+        0x660f => "AES 192",
+        0x6610 => "AES 256",
+        0x6702 => "RC2 (version needed to extract >= 5.2)",
+        0x6720 => "Blowfish",
+        0x6721 => "Twofish",
+        0x6801 => "RC4",
+        _ => "Unknown",
+    }
+}
+
+/// Represents a Decryption Header.
+/// It's stored at the start of the data of files using strong encryption,
+/// and before an encrypted central directory, as the Archive Decryption
+/// Header.
+pub struct DecryptionHeader {
+    /// The initialization vector
+    pub iv: Vec<u8>,
+    /// The size of the rest of the decryption header
+    pub size: u32,
+    /// The format of the record, currently 3
+    pub format: u16,
+    /// The encryption algorithm, see encryption_algorithm_name()
+    pub algorithm_id: u16,
+    /// The length of the encryption key, in bits
+    pub bit_length: u16,
+    /// The processing flags, see StrongEncryptionHeader
+    pub flags: u16,
+    /// The encrypted random data
+    pub encrypted_random_data: Vec<u8>,
+    /// The number of recipients, for certificate processing
+    pub recipient_count: u32,
+    /// The hash algorithm of the recipient list, only if there are recipients
+    pub hash_algorithm: Option<u16>,
+    /// The size of a hash in the recipient list, only if there are recipients
+    pub hash_size: Option<u16>,
+    /// The hashed public keys of the recipients
+    pub recipient_list: Vec<u8>,
+    /// The password validation data, still encrypted
+    pub password_validation_data: Vec<u8>,
+    /// The CRC32 of the password validation data, once decrypted
+    pub password_validation_crc32: u32,
 }
 
 /// Represents a Data Descriptor for a file stored in a ZIP.
 /// Used only when third bit of the flag in Local File Header is set.
 pub struct DataDescriptor {
+    /// Whether the optional signature 0x08074b50 precedes the data descriptor
+    pub signature_present: bool,
     /// The crc32 of the file
     pub crc32: u32,
     /// The size of the compressed size
-    pub compressed_size: u32,
+    /// Stored on 8 bytes instead of 4 for zip64 files
+    pub compressed_size: u64,
     /// The size of the uncompressed size
-    pub uncompressed_size: u32,
+    /// Stored on 8 bytes instead of 4 for zip64 files
+    pub uncompressed_size: u64,
+}
+
+/// The way the size of the data of a stored file was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataSizeSource {
+    /// The compressed size of the local file header
+    LocalFileHeader,
+    /// The compressed size of the matching central directory file header,
+    /// when the size is only in the data descriptor
+    CentralDirectory,
+    /// The end of the deflate stream, when the size is only in the data
+    /// descriptor
+    DeflateStream,
+    /// A data descriptor with a signature, found after the data
+    SignedDataDescriptor,
+    /// A data descriptor without signature, found after the data because it's
+    /// followed by a known signature
+    UnsignedDataDescriptor,
 }
 
 /// Represents a file stored in a ZIP
 pub struct StoredFile {
     /// The local file header
     pub local_file_header: LocalFileHeader,
-    /// The file data (uncompressed)
-    pub file_data: Vec<u8>,
+    /// The offset of the file data from the beginning of the archive file.
+    /// The data is as stored in the archive: compressed, and encrypted if the
+    /// file is encrypted. See decompression::decompress_stored_file() to get
+    /// the content of the file
+    pub data_offset: u64,
+    /// The size of the file data, lower than the compressed size if the
+    /// archive is truncated
+    pub data_size: u64,
+    /// The file data, kept in memory only when the archive was read from a
+    /// stream, which can't be read again. Otherwise the data is read from the
+    /// archive when needed, see data_reader()
+    pub file_data: Option<Vec<u8>>,
+    /// The summary of the content, made while the data was read when the
+    /// archive was read from a stream without keeping the data
+    pub content_summary: Option<Result<ContentSummary, ParseError>>,
     /// The optional data descriptor
     pub data_descriptor: Option<DataDescriptor>,
+    /// The way the size of the file data was found
+    pub data_size_source: DataSizeSource,
+    /// The decryption header at the start of the data, for files using
+    /// strong encryption
+    pub decryption_header: Option<DecryptionHeader>,
     /// The position of the file in the archive (0-based)
     /// The position is about the order of the files in
     /// the archive, not the order in the central directory
@@ -113,30 +651,64 @@ pub struct StoredFile {
 }
 
 impl StoredFile {
-    /// Update fields related to central directory
+    /// Get a reader on the file data, from memory if it was kept, or else from
+    /// the archive, which must be the one the file was read from
+    pub fn data_reader<'a, R: Read + Seek>(&'a self, archive: &'a mut R) -> Result<Box<dyn Read + 'a>, ParseError> {
+        if let Some(file_data) = &self.file_data {
+            return Ok(Box::new(file_data.as_slice()));
+        }
+        archive.seek(SeekFrom::Start(self.data_offset))
+            .map_err(|e| ParseError::io(e, Structure::FileData).at(self.data_offset))?;
+        Ok(Box::new(archive.take(self.data_size)))
+    }
+
+    /// Read the file data in memory, from the archive the file was read from
+    /// if it wasn't kept
+    pub fn read_data<R: Read + Seek>(&self, archive: &mut R) -> Result<Vec<u8>, ParseError> {
+        let mut data = Vec::new();
+        self.data_reader(archive)?
+            .read_to_end(&mut data)
+            .map_err(|e| ParseError::io(e, Structure::FileData).at(self.data_offset))?;
+        Ok(data)
+    }
+
+    /// Get the central directory file header of the file, the one pointing to
+    /// its local file header.
+    /// A file which no header points to isn't paired by filename: it may be
+    /// hidden behind another file of the same name.
+    pub fn central_directory_file_header<'a>(&self, central_directory: &'a CentralDirectory) -> Option<&'a CentralDirectoryFileHeader> {
+        central_directory.file_headers.iter()
+            .find(|header| header.offset_in_archive == Some(self.offset_in_archive as u64))
+    }
+
+    /// Update fields related to central directory, from the central directory
+    /// file header of the file
     pub fn update_from_central_directory(&mut self, central_directory: &CentralDirectory) {
-        for central_directory_file_header in &central_directory.file_headers {
-            if central_directory_file_header.filename == self.local_file_header.filename {
-                self.found_in_central_directory = true;
-                self.offset_from_central_directory = Some(central_directory.offset_from_start_of_archive - self.offset_in_archive);
-            }
+        if let Some(central_directory_file_header) = self.central_directory_file_header(central_directory) {
+            self.found_in_central_directory = true;
+            self.offset_from_central_directory = Some(central_directory_file_header.local_file_header_offset as usize);
         }
     }
 }
 
-/// Represents an Archive Decryption Header
+/// Represents an Archive Decryption Header, which precedes an encrypted
+/// central directory
 pub struct ArchiveDecryptionHeader {
+    /// The decryption header, needed to decrypt the central directory
+    pub decryption_header: DecryptionHeader,
     /// The optional archive extra data record
     pub archive_extra_data_record: Option<ArchiveExtraDataRecord>,
-    /// The central directory
+    /// The central directory, still encrypted
     /// Has to be encrypted / decrypted when writing / reading in a file
-    pub central_directory: CentralDirectory,
+    pub encrypted_central_directory: Vec<u8>,
+    /// The offset of the archive decryption header, from the start of the archive
+    pub offset_from_start_of_archive: usize,
 }
 
 /// Represents an Archive Extra Data Record
 pub struct ArchiveExtraDataRecord {
     /// The extra field
-    pub extra_field: Vec<u8>,
+    pub extra_field: ExtraField,
 }
 
 /// Represents a File Header in the Central Directory
@@ -156,26 +728,61 @@ pub struct CentralDirectoryFileHeader {
     /// CRC32 of the file
     pub crc32: u32,
     /// File's compressed size
-    pub compressed_size: u32,
+    /// Read from the zip64 extended information if set to 0xffffffff
+    pub compressed_size: u64,
     /// File's uncompressed size
-    pub uncompressed_size: u32,
+    /// Read from the zip64 extended information if set to 0xffffffff
+    pub uncompressed_size: u64,
     /// Disk number where file starts
-    pub disk_start: u16,
+    /// Read from the zip64 extended information if set to 0xffff
+    pub disk_start: u32,
     /// Internal file attributes
     pub internal_file_attributes: u16,
     /// External file attributes
     pub external_file_attributes: u32,
     /// The number of bytes between the start of the first disk on which the
     /// file occurs, and the start of the local file header
-    pub local_file_header_offset: u32,
-    /// The filename
+    /// Read from the zip64 extended information if set to 0xffffffff
+    pub local_file_header_offset: u64,
+    /// The offset of the local file header from the start of the archive.
+    /// Differs from local_file_header_offset for split archives, whose offsets
+    /// are relative to the disk on which the file starts, and for archives
+    /// with data prepended without updating their offsets.
+    /// None if the offset overflows.
+    pub offset_in_archive: Option<u64>,
+    /// The filename, decoded according to filename_encoding
     pub filename: String,
+    /// The filename, as stored in the archive
+    pub filename_raw: Vec<u8>,
+    /// The encoding used to decode the filename
+    pub filename_encoding: TextEncoding,
     /// The extra field
-    pub extra_field: Vec<u8>,
-    /// The file comment
+    pub extra_field: ExtraField,
+    /// The file comment, decoded according to file_comment_encoding
     pub file_comment: String,
+    /// The file comment, as stored in the archive
+    pub file_comment_raw: Vec<u8>,
+    /// The encoding used to decode the file comment
+    pub file_comment_encoding: TextEncoding,
     /// The position of the file in the central directory
     pub position: Option<usize>,
+    /// The zip64 extended information, read from the extra field.
+    /// Only present if at least one of the sizes, the offset or the disk
+    /// number is set to its maximum value.
+    pub zip64_extended_information: Option<Zip64ExtendedInformation>,
+}
+
+impl CentralDirectoryFileHeader {
+    /// Decode the MS-DOS last modification date and time
+    pub fn last_modification_dos(&self) -> DosDateTime {
+        DosDateTime::new(self.file_last_modification_date, self.file_last_modification_time)
+    }
+
+    /// Get the most precise last modification time available, from the
+    /// extra field or from the MS-DOS date and time
+    pub fn last_modification(&self) -> Timestamp {
+        Timestamp::most_precise(&self.extra_field, &self.last_modification_dos())
+    }
 }
 
 /// Represents a digital signature in Central Directory
@@ -185,22 +792,87 @@ pub struct DigitalSignature {
 }
 
 /// Represents the end of the Central Directory
+/// In a zip64 archive, the fields set to their maximum value are replaced
+/// with the values of the zip64 end of central directory record.
 pub struct EndOfCentralDirectoryRecord {
     /// Current disk number
-    pub disk_number: u16,
+    pub disk_number: u32,
     /// Disk where the central directory starts
-    pub disk_start_central_directory: u16,
+    pub disk_start_central_directory: u32,
     /// The number of central directory records on this disk
-    pub central_directory_records_number_on_disk: u16,
+    pub central_directory_records_number_on_disk: u64,
     /// The total number of central directory records
-    pub central_directory_records_total_number: u16,
+    pub central_directory_records_total_number: u64,
     /// The size of the central directory in bytes
-    pub central_directory_size: u32,
+    pub central_directory_size: u64,
     /// Ofset to start of central directory, relative to start of archive
-    pub offset_start_central_directory: u32,
-    /// The comment
+    pub offset_start_central_directory: u64,
+    /// The comment of the archive.
+    /// There is no flag for its encoding, it's decoded as UTF-8 if valid,
+    /// CP437 otherwise.
     pub comment: String,
+    /// The comment of the archive, as stored in the archive
+    pub comment_raw: Vec<u8>,
+
+}
+
+/// Represents the zip64 end of central directory record
+pub struct Zip64EndOfCentralDirectoryRecord {
+    /// The size of the record, without the signature and this field
+    pub size_of_record: u64,
+    /// The version of zip spec used to make the archive
+    pub version_made_by: u16,
+    /// The version of zip spec needed to extract
+    pub minimum_version: u16,
+    /// Current disk number
+    pub disk_number: u32,
+    /// Disk where the central directory starts
+    pub disk_start_central_directory: u32,
+    /// The number of central directory records on this disk
+    pub central_directory_records_number_on_disk: u64,
+    /// The total number of central directory records
+    pub central_directory_records_total_number: u64,
+    /// The size of the central directory in bytes
+    pub central_directory_size: u64,
+    /// Offset to start of central directory, relative to start of archive
+    pub offset_start_central_directory: u64,
+    /// The zip64 extensible data sector, reserved for PKWARE use
+    pub extensible_data_sector: Vec<u8>,
+    /// The encryption of the central directory, read from the extensible
+    /// data sector of a version 2 record
+    pub central_directory_encryption: Option<CentralDirectoryEncryption>,
+}
+
+/// Represents the fields added to the zip64 end of central directory record
+/// (version 2) when the central directory is compressed or encrypted
+pub struct CentralDirectoryEncryption {
+    /// The compression method of the central directory
+    pub compression_method: u16,
+    /// The compressed size of the central directory
+    pub compressed_size: u64,
+    /// The uncompressed size of the central directory
+    pub uncompressed_size: u64,
+    /// The encryption algorithm, see encryption_algorithm_name()
+    pub algorithm_id: u16,
+    /// The length of the encryption key, in bits
+    pub bit_length: u16,
+    /// The encryption flags
+    pub flags: u16,
+    /// The hash algorithm
+    pub hash_id: u16,
+    /// The hash data
+    pub hash_data: Vec<u8>,
+}
 
+/// Represents the zip64 end of central directory locator
+pub struct Zip64EndOfCentralDirectoryLocator {
+    /// Disk where the zip64 end of central directory record starts
+    pub disk_start_zip64_end_of_central_directory: u32,
+    /// Offset of the zip64 end of central directory record, relative to
+    /// start of archive
+    pub offset_zip64_end_of_central_directory_record: u64,
+    /// The total number of disks
+    pub total_number_of_disks: u32,
 }
 
 /// Represents the Central Directory
@@ -208,6 +880,10 @@ pub struct CentralDirectory {
     /// The file headers
     pub file_headers: Vec<CentralDirectoryFileHeader>,
     pub digital_signature: Option<DigitalSignature>,
+    /// The zip64 record for end of central directory
+    pub zip64_end_of_central_directory_record: Option<Zip64EndOfCentralDirectoryRecord>,
+    /// The zip64 locator for end of central directory
+    pub zip64_end_of_central_directory_locator: Option<Zip64EndOfCentralDirectoryLocator>,
     /// The record for end of central directory
     pub end_of_central_directory_record: EndOfCentralDirectoryRecord,
 
@@ -215,6 +891,10 @@ pub struct CentralDirectory {
     /// Not in the specification, but it helps to compute the offset of
     /// local file headers relative to the central directory.
     pub offset_from_start_of_archive: usize,
+    /// The number of bytes prepended to the archive without updating its
+    /// offsets, as in some self-extracting archives. It's added to the offsets
+    /// read in the archive to get the real ones.
+    pub base_offset: u64,
 }
 
 /// Represents a whole ZIP file
@@ -223,9 +903,274 @@ pub struct ZipFile {
     pub stored_files: Vec<StoredFile>,
     /// The archive extra data record
     pub archive_extra_data_record: Option<ArchiveExtraDataRecord>,
+    /// The archive decryption header, when the central directory is encrypted
+    pub archive_decryption_header: Option<ArchiveDecryptionHeader>,
     /// The central directory
     /// In the specification it's not optional, but in practice
     /// it could let us reading a ZIP file even if the central directory
     /// has been removed / damaged
     pub central_directory: Option<CentralDirectory>,
-}
\ No newline at end of file
+    /// The data before the first part of the archive, like the executable
+    /// stub of a self-extracting archive
+    pub prefix: Option<Prefix>,
+    /// The volumes of a split archive, empty if the archive is a single file
+    pub volumes: Vec<Volume>,
+    /// The problems found in the volumes of a split archive
+    pub volume_issues: Vec<VolumeIssue>,
+    /// The problems found while reading the archive, in the order they were met
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Represents the data stored before the first part of an archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prefix {
+    /// The size of the data, in bytes
+    pub size: u64,
+    /// The kind of program stored in the data, if any
+    pub stub_type: StubType,
+}
+
+/// The kinds of executable stubs found before self-extracting archives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StubType {
+    /// A Linux / Unix executable
+    Elf,
+    /// A Windows executable
+    Pe,
+    /// A macOS executable
+    MachO,
+    /// A script starting with a shebang, as made by makeself or unzipsfx wrappers
+    Script,
+    /// Unrecognized data
+    Unknown,
+}
+
+impl StubType {
+    /// Identify a stub from its first bytes
+    pub fn from_magic(magic: &[u8]) -> StubType {
+        match magic {
+            [0x7f, b'E', b'L', b'F', ..] => StubType::Elf,
+            [b'M', b'Z', ..] => StubType::Pe,
+            [0xfe, 0xed, 0xfa, 0xce | 0xcf, ..]
+            | [0xce | 0xcf, 0xfa, 0xed, 0xfe, ..]
+            | [0xca, 0xfe, 0xba, 0xbe, ..] => StubType::MachO,
+            [b'#', b'!', ..] => StubType::Script,
+            _ => StubType::Unknown,
+        }
+    }
+}
+
+/// Represents a volume of a split archive, that is one of the files
+/// containing a part of the archive
+#[derive(Debug, Clone)]
+pub struct Volume {
+    /// The path of the volume
+    pub path: PathBuf,
+    /// The number of the disk stored in this volume, starting at 0
+    pub disk_number: u32,
+    /// The size of the volume
+    pub size: u64,
+    /// The offset of the start of the volume, in the stream made of all the volumes
+    pub offset_in_archive: u64,
+}
+
+/// Represents a problem found in the volumes of a split archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VolumeIssue {
+    /// The volume of a disk was not found
+    Missing {
+        /// The number of the disk whose volume is missing
+        disk_number: u32,
+        /// The path where the volume was expected
+        path: PathBuf,
+    },
+    /// The content expected on a disk was found in the volume of another disk,
+    /// probably because the volumes were renamed
+    OutOfOrder {
+        /// The number of the disk where the content was expected
+        disk_number: u32,
+        /// The number of the disk whose volume contains it
+        found_in_disk_number: u32,
+    },
+}
+
+/// How serious a Diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Something unusual which doesn't prevent reading the archive
+    Info,
+    /// A part of the archive was skipped or recovered, what was read is still reliable
+    Warning,
+    /// A part of the archive couldn't be read, its content is missing from the result
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "INFO"),
+            Severity::Warning => write!(f, "WARNING"),
+            Severity::Error => write!(f, "ERROR"),
+        }
+    }
+}
+
+/// The kinds of Diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticCode {
+    /// A local file header couldn't be read, its file is skipped
+    UnreadableLocalFileHeader,
+    /// No local file header was found at the offset given by the central directory
+    MissingLocalFileHeader,
+    /// A local file header isn't referenced by the central directory, its file
+    /// was removed or hidden
+    UnreferencedLocalFileHeader,
+    /// A record of an extra field is truncated or its content is invalid
+    MalformedExtraField,
+    /// The decryption header of a file using strong encryption couldn't be read
+    UnreadableDecryptionHeader,
+    /// The data of a file is shorter than its announced size, the archive is truncated
+    TruncatedFileData,
+    /// The size of a file is only in its data descriptor, which wasn't found
+    UnknownDataSize,
+    /// The archive extra data record couldn't be read
+    UnreadableArchiveExtraDataRecord,
+    /// The archive decryption header of an encrypted central directory couldn't be read
+    UnreadableArchiveDecryptionHeader,
+    /// The central directory couldn't be read
+    UnreadableCentralDirectory,
+    /// A central directory file header couldn't be read, it's skipped
+    UnreadableCentralDirectoryFileHeader,
+    /// No central directory was found
+    MissingCentralDirectory,
+    /// Bytes which aren't part of any known structure were skipped
+    SkippedBytes,
+    /// The data before the first part of the archive couldn't be read
+    UnreadablePrefix,
+}
+
+impl fmt::Display for DiagnosticCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let code = match self {
+            DiagnosticCode::UnreadableLocalFileHeader => "unreadable-local-file-header",
+            DiagnosticCode::MissingLocalFileHeader => "missing-local-file-header",
+            DiagnosticCode::UnreferencedLocalFileHeader => "unreferenced-local-file-header",
+            DiagnosticCode::MalformedExtraField => "malformed-extra-field",
+            DiagnosticCode::UnreadableDecryptionHeader => "unreadable-decryption-header",
+            DiagnosticCode::TruncatedFileData => "truncated-file-data",
+            DiagnosticCode::UnknownDataSize => "unknown-data-size",
+            DiagnosticCode::UnreadableArchiveExtraDataRecord => "unreadable-archive-extra-data-record",
+            DiagnosticCode::UnreadableArchiveDecryptionHeader => "unreadable-archive-decryption-header",
+            DiagnosticCode::UnreadableCentralDirectory => "unreadable-central-directory",
+            DiagnosticCode::UnreadableCentralDirectoryFileHeader => "unreadable-central-directory-file-header",
+            DiagnosticCode::MissingCentralDirectory => "missing-central-directory",
+            DiagnosticCode::SkippedBytes => "skipped-bytes",
+            DiagnosticCode::UnreadablePrefix => "unreadable-prefix",
+        };
+        write!(f, "{}", code)
+    }
+}
+
+/// Represents a problem found while reading an archive, which didn't stop
+/// the reading: a structure skipped, or read another way
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// How serious the problem is
+    pub severity: Severity,
+    /// The kind of problem
+    pub code: DiagnosticCode,
+    /// The offset of the structure concerned, from the start of the archive,
+    /// if known
+    pub offset: Option<u64>,
+    /// A description of the problem
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Create a Diagnostic
+    pub fn new(severity: Severity, code: DiagnosticCode, offset: Option<u64>, message: String) -> Diagnostic {
+        Diagnostic {
+            severity,
+            code,
+            offset,
+            message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zip::read_fixture;
+    use crate::zip::reader::ParsingStrategy;
+
+    #[test]
+    fn decodes_dos_date_and_time() {
+        let zip_file = read_fixture("timestamps.zip", ParsingStrategy::Sequential);
+        let dos: Vec<_> = zip_file.stored_files.iter()
+            .map(|stored_file| stored_file.local_file_header.last_modification_dos())
+            .collect();
+        assert_eq!((dos[0].year, dos[0].month, dos[0].day, dos[0].hour, dos[0].minute, dos[0].second), (2024, 2, 29, 13, 45, 58));
+        assert!(dos[0].is_valid());
+        // A date of 0 has no month and no day
+        assert_eq!(dos[1].to_string(), "1980-00-00 00:00:00");
+        assert_eq!(dos[1].errors(), ["invalid month 0", "invalid day 0"]);
+        assert_eq!(dos[2].errors(), ["invalid month 13", "invalid hour 24", "invalid minute 60", "invalid second 62"]);
+        assert_eq!(dos[3].errors(), ["invalid day 29 for month 2"]);
+    }
+
+    #[test]
+    fn prefers_most_precise_timestamp() {
+        let zip_file = read_fixture("timestamps.zip", ParsingStrategy::Sequential);
+        let timestamps: Vec<_> = zip_file.stored_files.iter()
+            .map(|stored_file| {
+                let timestamp = stored_file.local_file_header.last_modification();
+                (timestamp.to_string(), timestamp.source)
+            })
+            .collect();
+        assert_eq!(timestamps[0], ("2024-02-29 13:45:58".to_string(), TimestampSource::Dos));
+        // Invalid values are kept as is without extra field
+        assert_eq!(timestamps[1], ("1980-00-00 00:00:00".to_string(), TimestampSource::Dos));
+        assert!(Timestamp::from_dos(&zip_file.stored_files[1].local_file_header.last_modification_dos()).is_none());
+        assert_eq!(timestamps[4], ("2023-11-14 22:13:20 UTC".to_string(), TimestampSource::ExtendedTimestamp));
+        // The NTFS extra field is preferred over the extended timestamp
+        assert_eq!(timestamps[5], ("2022-06-18 04:26:40.1234567 UTC".to_string(), TimestampSource::Ntfs));
+        assert_eq!(Timestamp::from_ntfs(133000000001234567).nanosecond, 123456700);
+        // The central directory has no extra field
+        let header = &zip_file.central_directory.unwrap().file_headers[4];
+        assert_eq!(header.last_modification().source, TimestampSource::Dos);
+    }
+
+    #[test]
+    fn identifies_stub_types() {
+        assert_eq!(StubType::from_magic(b"\x7fELF"), StubType::Elf);
+        assert_eq!(StubType::from_magic(b"MZ\x90\x00"), StubType::Pe);
+        assert_eq!(StubType::from_magic(&[0xcf, 0xfa, 0xed, 0xfe]), StubType::MachO);
+        assert_eq!(StubType::from_magic(&[0xca, 0xfe, 0xba, 0xbe]), StubType::MachO);
+        assert_eq!(StubType::from_magic(b"#!/b"), StubType::Script);
+        assert_eq!(StubType::from_magic(b"M"), StubType::Unknown);
+    }
+
+    #[test]
+    fn pairs_files_with_central_directory_only_by_offset() {
+        // Two files named x.txt, the central directory references only the second one
+        let zip_file = read_fixture("unreferenced.zip", ParsingStrategy::Sequential);
+        let central_directory = zip_file.central_directory.as_ref().unwrap();
+        let [hidden, visible] = &zip_file.stored_files[..] else { panic!("expected 2 files") };
+        assert!(!hidden.found_in_central_directory);
+        assert!(hidden.central_directory_file_header(central_directory).is_none());
+        assert!(visible.found_in_central_directory);
+        assert_eq!(visible.central_directory_file_header(central_directory).unwrap().offset_in_archive, Some(visible.offset_in_archive as u64));
+        assert_eq!(visible.offset_from_central_directory, Some(visible.offset_in_archive));
+    }
+
+    #[test]
+    fn reports_unreferenced_local_file_header() {
+        let zip_file = read_fixture("unreferenced.zip", ParsingStrategy::Sequential);
+        let unreferenced: Vec<_> = zip_file.diagnostics.iter()
+            .filter(|diagnostic| diagnostic.code == DiagnosticCode::UnreferencedLocalFileHeader)
+            .collect();
+        assert_eq!(unreferenced.len(), 1);
+        assert_eq!(unreferenced[0].offset, Some(0));
+    }
+}
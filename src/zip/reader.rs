@@ -1,10 +1,98 @@
 //! This module contains readers whose goal is to read and parse a ZIP file
 
-use crate::util::{compare_signature, file_has_remaining_space,read_chunk, read_string_bytes, read_u16_le, read_u32_le, compare_signature_raw, rewind_file_cursor};
-use std::fs::File;
-use std::io::Seek;
+use crate::util::{compare_signature, read_chunk, try_read_chunk, read_string_bytes, read_u16_le, read_u32_le, read_u64_le, compare_signature_raw, rewind_file_cursor, decode_cp437, decode_utf8};
+use crate::checksum::crc32;
+use crate::compression::deflate::inflate;
+use crate::errors::{ParseError, ParseErrorKind, Structure};
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::path::PathBuf;
 use super::constants;
-use super::model::{DataDescriptor, LocalFileHeader, StoredFile, ZipFile, ArchiveExtraDataRecord, CentralDirectory, CentralDirectoryFileHeader, DigitalSignature, EndOfCentralDirectoryRecord};
+use super::decompression::{self, ContentSummary};
+use super::split::SplitArchive;
+use super::stream::LookBackReader;
+use super::model::{DataDescriptor, LocalFileHeader, StoredFile, ZipFile, ArchiveExtraDataRecord, CentralDirectory, CentralDirectoryFileHeader, DigitalSignature, EndOfCentralDirectoryRecord, Zip64ExtendedInformation, Zip64EndOfCentralDirectoryRecord, Zip64EndOfCentralDirectoryLocator, ExtraField, ExtraFieldRecord, ExtraFieldContent, NtfsTimes, ExtendedTimestamp, InfoZipUnix, InfoZipUnicode, AesExtraField, TextEncoding, DataSizeSource, StrongEncryptionHeader, DecryptionHeader, ArchiveDecryptionHeader, CentralDirectoryEncryption, VolumeIssue, Prefix, StubType, Diagnostic, DiagnosticCode, Severity};
+
+/// Reads the fields of a structure one after the other, keeping track of
+/// their offset to locate the errors
+struct FieldReader<'a, R: Read> {
+    file: &'a mut R,
+    /// The structure being read
+    structure: Structure,
+    /// The offset of the next field from the start of the archive
+    offset: u64,
+}
+
+impl<'a, R: Read + Seek> FieldReader<'a, R> {
+    /// Start reading a structure at the current position of the file
+    fn new(file: &'a mut R, structure: Structure) -> Result<FieldReader<'a, R>, ParseError> {
+        let offset = file.stream_position()
+            .map_err(|e| ParseError::io(e, structure))?;
+        Ok(FieldReader {
+            file,
+            structure,
+            offset,
+        })
+    }
+}
+
+impl<R: Read> FieldReader<'_, R> {
+    /// Read the bytes of a field
+    fn bytes(&mut self, length: usize, field: &'static str) -> Result<Vec<u8>, ParseError> {
+        let chunk = try_read_chunk(self.file, length)
+            .map_err(|e| self.error(ParseErrorKind::Io(e), field))?;
+        if chunk.len() < length {
+            return Err(self.error(ParseErrorKind::Truncated, field));
+        }
+        self.offset += length as u64;
+        Ok(chunk)
+    }
+
+    /// Read a field of 2 bytes, little endian
+    fn u16(&mut self, field: &'static str) -> Result<u16, ParseError> {
+        Ok(read_u16_le(&self.bytes(2, field)?).unwrap())
+    }
+
+    /// Read a field of 4 bytes, little endian
+    fn u32(&mut self, field: &'static str) -> Result<u32, ParseError> {
+        Ok(read_u32_le(&self.bytes(4, field)?).unwrap())
+    }
+
+    /// Read a field of 8 bytes, little endian
+    fn u64(&mut self, field: &'static str) -> Result<u64, ParseError> {
+        Ok(read_u64_le(&self.bytes(8, field)?).unwrap())
+    }
+
+    /// Create an error about a field starting at the current offset
+    fn error(&self, kind: ParseErrorKind, field: &'static str) -> ParseError {
+        ParseError::new(kind, self.structure)
+            .with_field(field)
+            .at(self.offset)
+    }
+}
+
+/// Get the current position of the cursor of file, when reading structure
+fn current_position<R: Seek>(file: &mut R, structure: Structure) -> Result<u64, ParseError> {
+    file.stream_position()
+        .map_err(|e| ParseError::io(e, structure))
+}
+
+/// Move the cursor of file to offset, when reading structure
+fn move_to<R: Seek>(file: &mut R, offset: u64, structure: Structure) -> Result<(), ParseError> {
+    file.seek(SeekFrom::Start(offset))
+        .map_err(|e| ParseError::io(e, structure).at(offset))?;
+    Ok(())
+}
+
+/// Get the size of file, or None if it's unknown, as for streams.
+/// The cursor is left at its current position.
+fn file_size<R: Seek>(file: &mut R, structure: Structure) -> Result<Option<u64>, ParseError> {
+    let position = current_position(file, structure)?;
+    let Ok(size) = file.seek(SeekFrom::End(0)) else {
+        return Ok(None);
+    };
+    move_to(file, position, structure)?;
+    Ok(Some(size))
+}
 
 /// A reader for LocalFileHeader
 pub struct LocalFileHeaderReader {
@@ -12,125 +100,665 @@ pub struct LocalFileHeaderReader {
 
 impl LocalFileHeaderReader {
     /// Read a file and try to create a LocalFileHeader
-    pub fn read(file: &mut File) -> Result<LocalFileHeader, String> {
-        let minimum_version_chunk = read_chunk(file, 2); // Minimum version to extract
-        let general_purpose_flag_chunk = read_chunk(file, 2); // General purpose bit flag
-        let compression_method_chunk = read_chunk(file, 2); // Compression method
-        let file_last_modification_time_chunk = read_chunk(file, 2); // File last modification time
-        let file_last_modification_date_chunk = read_chunk(file, 2); // File last modification date
-        let crc32_chunk = read_chunk(file, 4); // CRC32
-        let compressed_size_chunk = read_chunk(file, 4); // Compressed size
-        let uncompressed_size_chunk = read_chunk(file, 4); // Uncompressed size
-        let filename_length_chunk = read_chunk(file, 2); // File name length
-        let filename_length = u16::from_le_bytes(filename_length_chunk.try_into().unwrap());
-        let extra_fields_length_chunk = read_chunk(file, 2); // Extra field length
-        let extra_fields_length = u16::from_le_bytes(extra_fields_length_chunk.try_into().unwrap());
-        let filename_chunk = read_chunk(file, filename_length as usize); // File name
-        let extra_field_chunk = read_chunk(file, extra_fields_length as usize); // Extra field
-
-        let minimum_version = read_u16_le(&minimum_version_chunk);
-        let general_purpose_flag = read_u16_le(&general_purpose_flag_chunk);
-        let compression_method = read_u16_le(&compression_method_chunk);
-        let file_last_modification_time = read_u16_le(&file_last_modification_time_chunk);
-        let file_last_modification_date = read_u16_le(&file_last_modification_date_chunk);
-        let crc32 = read_u32_le(&crc32_chunk);
-        let compressed_size = read_u32_le(&compressed_size_chunk);
-        let uncompressed_size = read_u32_le(&uncompressed_size_chunk);
-
-        if minimum_version.is_err() {
-            return Err("Unable to read Local File Header: unreadable minimum version.".to_string());
-        } else if general_purpose_flag.is_err() {
-            return Err("Unable to read Local File Header: unreadable general purpose flag.".to_string());
-        } else if compression_method.is_err() {
-            return Err("Unable to read Local File Header: unreadable compression method.".to_string());
-        } else if file_last_modification_time.is_err() {
-            return Err("Unable to read Local File Header: unreadable file last modification time.".to_string());
-        } else if file_last_modification_date.is_err() {
-            return Err("Unable to read Local File Header: unreadable file last modification date.".to_string());
-        } else if crc32.is_err() {
-            return Err("Unable to read Local File Header: unreadable crc32.".to_string());
-        } else if compressed_size.is_err() {
-            return Err("Unable to read Local File Header: unreadable compressed size.".to_string());
-        } else if uncompressed_size.is_err() {
-            return Err("Unable to read Local File Header: unreadable uncompressed size.".to_string());
+    pub fn read<R: Read + Seek>(file: &mut R) -> Result<LocalFileHeader, ParseError> {
+        let mut reader = FieldReader::new(file, Structure::LocalFileHeader)?;
+        let minimum_version = reader.u16("minimum version")?;
+        let general_purpose_flag = reader.u16("general purpose flag")?;
+        let compression_method = reader.u16("compression method")?;
+        let file_last_modification_time = reader.u16("file last modification time")?;
+        let file_last_modification_date = reader.u16("file last modification date")?;
+        let crc32 = reader.u32("crc32")?;
+        let compressed_size = reader.u32("compressed size")?;
+        let uncompressed_size = reader.u32("uncompressed size")?;
+        let filename_length = reader.u16("file name length")?;
+        let extra_fields_length = reader.u16("extra field length")?;
+        let filename_chunk = reader.bytes(filename_length as usize, "file name")?;
+        let extra_field_offset = reader.offset;
+        let extra_field_chunk = reader.bytes(extra_fields_length as usize, "extra field")?;
+
+        let extra_field = ExtraFieldReader::read(extra_field_chunk);
+        let (filename, filename_encoding) = decode_text(&filename_chunk, general_purpose_flag, extra_field.unicode_path());
+        let mut compressed_size = compressed_size as u64;
+        let mut uncompressed_size = uncompressed_size as u64;
+
+        // In a zip64 archive, the sizes are stored in the zip64 extended information.
+        // The specification requires both sizes to be present in the local file
+        // header as soon as one of them is.
+        let mut zip64_extended_information = None;
+        if compressed_size == constants::ZIP64_PLACEHOLDER_U32 as u64 || uncompressed_size == constants::ZIP64_PLACEHOLDER_U32 as u64 {
+            let zip64 = Zip64ExtendedInformationReader::read_from_extra_field(&extra_field, true, true, false, false)
+                .map_err(|e| e.or_at(extra_field_offset))?;
+            uncompressed_size = zip64.uncompressed_size.unwrap_or(uncompressed_size);
+            compressed_size = zip64.compressed_size.unwrap_or(compressed_size);
+            zip64_extended_information = Some(zip64);
         }
 
         Ok(LocalFileHeader {
-            minimum_version: minimum_version.unwrap(),
-            general_purpose_flag: general_purpose_flag.unwrap(),
-            compression_method: compression_method.unwrap(),
-            file_last_modification_time: file_last_modification_time.unwrap(),
-            file_last_modification_date: file_last_modification_date.unwrap(),
-            crc32: crc32.unwrap(),
-            compressed_size: compressed_size.unwrap(),
-            uncompressed_size: uncompressed_size.unwrap(),
-            filename: read_string_bytes(&filename_chunk),
-            extra_field: extra_field_chunk,
+            minimum_version,
+            general_purpose_flag,
+            compression_method,
+            file_last_modification_time,
+            file_last_modification_date,
+            crc32,
+            compressed_size,
+            uncompressed_size,
+            filename,
+            filename_raw: filename_chunk,
+            filename_encoding,
+            extra_field,
+            zip64_extended_information,
         })
     }
 }
 
-/// Represents a reader for DataDescriptor
-pub struct DataDescriptorReader {
+/// Decode a filename or a comment stored in a header.
+/// If bit 11 of the general purpose flag is set, the text is UTF-8.
+/// Otherwise it's CP437, unless an Info-ZIP Unicode extra field gives its
+/// UTF-8 version. That extra field is honoured only if its CRC32 matches the
+/// stored text, otherwise the text was modified by a tool unaware of the
+/// extra field, which is outdated.
+fn decode_text(raw: &[u8], general_purpose_flag: u16, unicode: Option<&InfoZipUnicode>) -> (String, TextEncoding) {
+    if general_purpose_flag & constants::GENERAL_PURPOSE_FLAG_UTF8 != 0 {
+        return (decode_utf8(raw), TextEncoding::Utf8);
+    }
+    if let Some(unicode) = unicode {
+        if unicode.version == 1 && unicode.crc32 == crc32(raw) {
+            return (unicode.value.clone(), TextEncoding::InfoZipUnicode);
+        }
+    }
+    (decode_cp437(raw), TextEncoding::Cp437)
+}
 
+/// Add a diagnostic for each malformed record of the extra field of a header.
+/// extra_field_offset is the offset of the extra field in the archive, header
+/// describes the header for the messages.
+fn report_malformed_extra_field(extra_field: &ExtraField, extra_field_offset: u64, header: &str, diagnostics: &mut Vec<Diagnostic>) {
+    for record in &extra_field.records {
+        if let ExtraFieldContent::Malformed(reason) = &record.content {
+            diagnostics.push(Diagnostic::new(Severity::Warning, DiagnosticCode::MalformedExtraField, Some(extra_field_offset + record.offset as u64),
+                format!("The extra field record 0x{:04x} of {} is malformed: {}", record.header_id, header, reason)));
+        }
+    }
 }
 
-impl DataDescriptorReader {
-    /// Read a file and try to create a DataDescriptor
-    pub fn read(file: &mut File) -> Result<DataDescriptor, String> {
-        let crc32_chunk = read_chunk(file, 4);
-        let compressed_size_chunk = read_chunk(file, 4);
-        let uncompressed_size_chunk = read_chunk(file, 4);
+/// Represents a reader for ExtraField
+pub struct ExtraFieldReader {
+
+}
+
+impl ExtraFieldReader {
+    /// Split the bytes of an extra field into records, and decode the known ones.
+    /// This never fails: truncated records and records whose content cannot be
+    /// decoded are kept with a Malformed content.
+    pub fn read(raw: Vec<u8>) -> ExtraField {
+        let mut records = Vec::new();
+        let mut cursor = 0;
+
+        while cursor < raw.len() {
+            let remaining = raw.len() - cursor;
+            if remaining < 4 {
+                // Not enough bytes for the header ID and the data size
+                let header_id = raw.get(cursor..cursor + 2)
+                    .map(|chunk| read_u16_le(chunk).unwrap())
+                    .unwrap_or(0);
+                records.push(ExtraFieldRecord {
+                    header_id,
+                    data_size: 0,
+                    data: raw[cursor..].to_vec(),
+                    offset: cursor,
+                    content: ExtraFieldContent::Malformed(format!("truncated record header: {} bytes remaining", remaining)),
+                });
+                break;
+            }
+
+            let header_id = read_u16_le(&raw[cursor..cursor + 2]).unwrap();
+            let data_size = read_u16_le(&raw[cursor + 2..cursor + 4]).unwrap();
+            let data_start = cursor + 4;
+            let data_end = data_start + data_size as usize;
+
+            if data_end > raw.len() {
+                records.push(ExtraFieldRecord {
+                    header_id,
+                    data_size,
+                    data: raw[data_start..].to_vec(),
+                    offset: cursor,
+                    content: ExtraFieldContent::Malformed(format!("declared data size {} exceeds the {} remaining bytes", data_size, raw.len() - data_start)),
+                });
+                break;
+            }
+
+            let data = raw[data_start..data_end].to_vec();
+            let content = Self::read_content(header_id, &data)
+                .unwrap_or_else(|e| ExtraFieldContent::Malformed(e.to_string()));
+            records.push(ExtraFieldRecord {
+                header_id,
+                data_size,
+                data,
+                offset: cursor,
+                content,
+            });
+            cursor = data_end;
+        }
+
+        ExtraField {
+            raw,
+            records,
+        }
+    }
+
+    /// Decode the data of a record, according to its header ID
+    fn read_content(header_id: u16, data: &[u8]) -> Result<ExtraFieldContent, ParseError> {
+        let content = match header_id {
+            constants::EXTRA_FIELD_ZIP64_EXTENDED_INFORMATION => {
+                // Without the header, it's impossible to know which values
+                // are present. Assign them in order, as far as the size allows.
+                if ![0, 8, 16, 24, 28].contains(&data.len()) {
+                    return Err(ParseError::invalid_value(Structure::Zip64ExtendedInformation, "data size", format!("invalid size {}", data.len())));
+                }
+                ExtraFieldContent::Zip64ExtendedInformation(Zip64ExtendedInformationReader::read(
+                    data, data.len() >= 8, data.len() >= 16, data.len() >= 24, data.len() >= 28)?)
+            },
+            constants::EXTRA_FIELD_NTFS => ExtraFieldContent::Ntfs(NtfsTimesReader::read(data)?),
+            constants::EXTRA_FIELD_EXTENDED_TIMESTAMP => ExtraFieldContent::ExtendedTimestamp(ExtendedTimestampReader::read(data)?),
+            constants::EXTRA_FIELD_INFO_ZIP_UNIX => ExtraFieldContent::InfoZipUnix(InfoZipUnixReader::read(data)?),
+            constants::EXTRA_FIELD_INFO_ZIP_UNICODE_PATH => ExtraFieldContent::InfoZipUnicodePath(InfoZipUnicodeReader::read(data)?),
+            constants::EXTRA_FIELD_INFO_ZIP_UNICODE_COMMENT => ExtraFieldContent::InfoZipUnicodeComment(InfoZipUnicodeReader::read(data)?),
+            constants::EXTRA_FIELD_STRONG_ENCRYPTION_HEADER => ExtraFieldContent::StrongEncryptionHeader(StrongEncryptionHeaderReader::read(data)?),
+            constants::EXTRA_FIELD_AES => ExtraFieldContent::Aes(AesExtraFieldReader::read(data)?),
+            _ => ExtraFieldContent::Unknown,
+        };
+        Ok(content)
+    }
+}
+
+/// Represents a reader for Zip64ExtendedInformation
+pub struct Zip64ExtendedInformationReader {
+
+}
+
+impl Zip64ExtendedInformationReader {
+    /// Read the data of a zip64 extended information record and try to create
+    /// a Zip64ExtendedInformation.
+    /// The values are stored in a fixed order, but only the ones whose matching
+    /// header field is set to its maximum value are present. The caller tells
+    /// which ones are expected.
+    pub fn read(data: &[u8], uncompressed_size: bool, compressed_size: bool, local_file_header_offset: bool, disk_start: bool) -> Result<Zip64ExtendedInformation, ParseError> {
+        let mut cursor = 0;
+        let mut read_u64 = |expected: bool, field: &'static str| -> Result<Option<u64>, ParseError> {
+            if !expected {
+                return Ok(None);
+            }
+            let value = data.get(cursor..cursor + 8)
+                .ok_or(ParseError::truncated(Structure::Zip64ExtendedInformation, field))?;
+            cursor += 8;
+            Ok(Some(read_u64_le(value).unwrap()))
+        };
+
+        let uncompressed_size = read_u64(uncompressed_size, "uncompressed size")?;
+        let compressed_size = read_u64(compressed_size, "compressed size")?;
+        let local_file_header_offset = read_u64(local_file_header_offset, "local file header offset")?;
+        let disk_start = if disk_start {
+            let value = data.get(cursor..cursor + 4)
+                .ok_or(ParseError::truncated(Structure::Zip64ExtendedInformation, "disk start"))?;
+            Some(read_u32_le(value).unwrap())
+        } else {
+            None
+        };
+
+        Ok(Zip64ExtendedInformation {
+            uncompressed_size,
+            compressed_size,
+            local_file_header_offset,
+            disk_start,
+        })
+    }
+
+    /// Find the zip64 extended information in an extra field and read it
+    pub fn read_from_extra_field(extra_field: &ExtraField, uncompressed_size: bool, compressed_size: bool, local_file_header_offset: bool, disk_start: bool) -> Result<Zip64ExtendedInformation, ParseError> {
+        let record = extra_field.find(constants::EXTRA_FIELD_ZIP64_EXTENDED_INFORMATION)
+            .ok_or(ParseError::new(ParseErrorKind::NotFound, Structure::Zip64ExtendedInformation))?;
+        Self::read(&record.data, uncompressed_size, compressed_size, local_file_header_offset, disk_start)
+    }
+}
+
+/// Represents a reader for NtfsTimes
+pub struct NtfsTimesReader {
+
+}
+
+impl NtfsTimesReader {
+    /// Read the data of a NTFS extra field and try to create a NtfsTimes.
+    /// The data starts with 4 reserved bytes, followed by attributes made of a
+    /// 2-bytes tag, a 2-bytes size and the attribute data. Only the tag 0x0001,
+    /// holding the file times, is known.
+    pub fn read(data: &[u8]) -> Result<NtfsTimes, ParseError> {
+        if data.len() < 4 {
+            return Err(ParseError::truncated(Structure::NtfsTimes, "reserved"));
+        }
+
+        let mut times = NtfsTimes {
+            modification_time: None,
+            access_time: None,
+            creation_time: None,
+        };
+        let mut cursor = 4;
+        while cursor < data.len() {
+            let attribute_header = data.get(cursor..cursor + 4)
+                .ok_or(ParseError::truncated(Structure::NtfsTimes, "attribute header"))?;
+            let tag = read_u16_le(&attribute_header[0..2]).unwrap();
+            let size = read_u16_le(&attribute_header[2..4]).unwrap() as usize;
+            let attribute = data.get(cursor + 4..cursor + 4 + size)
+                .ok_or(ParseError::truncated(Structure::NtfsTimes, "attribute"))?;
+
+            if tag == 0x0001 {
+                if size != 24 {
+                    return Err(ParseError::invalid_value(Structure::NtfsTimes, "file times", format!("invalid attribute size {}", size)));
+                }
+                times.modification_time = Some(read_u64_le(&attribute[0..8]).unwrap());
+                times.access_time = Some(read_u64_le(&attribute[8..16]).unwrap());
+                times.creation_time = Some(read_u64_le(&attribute[16..24]).unwrap());
+            }
+            cursor += 4 + size;
+        }
+
+        Ok(times)
+    }
+}
+
+/// Represents a reader for ExtendedTimestamp
+pub struct ExtendedTimestampReader {
+
+}
+
+impl ExtendedTimestampReader {
+    /// Read the data of an extended timestamp extra field and try to create an
+    /// ExtendedTimestamp.
+    /// The times announced by the flags are read as long as there is data left,
+    /// since the central directory only holds the modification time.
+    pub fn read(data: &[u8]) -> Result<ExtendedTimestamp, ParseError> {
+        let flags = *data.first()
+            .ok_or(ParseError::truncated(Structure::ExtendedTimestamp, "flags"))?;
+
+        let mut cursor = 1;
+        let mut read_time = |bit: u8| -> Option<i32> {
+            if flags & bit == 0 {
+                return None;
+            }
+            let value = data.get(cursor..cursor + 4)?;
+            cursor += 4;
+            Some(read_u32_le(value).unwrap() as i32)
+        };
+        let modification_time = read_time(1);
+        let access_time = read_time(2);
+        let creation_time = read_time(4);
+
+        if cursor != data.len() {
+            return Err(ParseError::invalid_value(Structure::ExtendedTimestamp, "times", format!("{} unexpected bytes", data.len() - cursor)));
+        }
+
+        Ok(ExtendedTimestamp {
+            flags,
+            modification_time,
+            access_time,
+            creation_time,
+        })
+    }
+}
+
+/// Represents a reader for InfoZipUnix
+pub struct InfoZipUnixReader {
+
+}
+
+impl InfoZipUnixReader {
+    /// Read the data of an Info-ZIP Unix extra field and try to create an
+    /// InfoZipUnix.
+    /// The UID and GID are each preceded by their size in bytes.
+    pub fn read(data: &[u8]) -> Result<InfoZipUnix, ParseError> {
+        let version = *data.first()
+            .ok_or(ParseError::truncated(Structure::InfoZipUnix, "version"))?;
+
+        let mut cursor = 1;
+        let mut read_id = |name: &'static str| -> Result<u64, ParseError> {
+            let size = *data.get(cursor)
+                .ok_or(ParseError::truncated(Structure::InfoZipUnix, name))? as usize;
+            if size > 8 {
+                return Err(ParseError::invalid_value(Structure::InfoZipUnix, name, format!("size {} is too big", size)));
+            }
+            let value = data.get(cursor + 1..cursor + 1 + size)
+                .ok_or(ParseError::truncated(Structure::InfoZipUnix, name))?;
+            cursor += 1 + size;
+            let mut bytes = [0u8; 8];
+            bytes[..size].copy_from_slice(value);
+            Ok(u64::from_le_bytes(bytes))
+        };
+        let uid = read_id("UID")?;
+        let gid = read_id("GID")?;
+
+        Ok(InfoZipUnix {
+            version,
+            uid,
+            gid,
+        })
+    }
+}
+
+/// Represents a reader for InfoZipUnicode
+pub struct InfoZipUnicodeReader {
+
+}
+
+impl InfoZipUnicodeReader {
+    /// Read the data of an Info-ZIP Unicode path or comment extra field and try
+    /// to create an InfoZipUnicode.
+    pub fn read(data: &[u8]) -> Result<InfoZipUnicode, ParseError> {
+        if data.len() < 5 {
+            return Err(ParseError::truncated(Structure::InfoZipUnicode, "crc32"));
+        }
+        let value = String::from_utf8(data[5..].to_vec())
+            .or(Err(ParseError::invalid_value(Structure::InfoZipUnicode, "value", "invalid UTF-8".to_string())))?;
+
+        Ok(InfoZipUnicode {
+            version: data[0],
+            crc32: read_u32_le(&data[1..5]).unwrap(),
+            value,
+        })
+    }
+}
+
+/// Represents a reader for StrongEncryptionHeader
+pub struct StrongEncryptionHeaderReader {
+
+}
+
+impl StrongEncryptionHeaderReader {
+    /// Read the data of a strong encryption header extra field and try to
+    /// create a StrongEncryptionHeader.
+    pub fn read(data: &[u8]) -> Result<StrongEncryptionHeader, ParseError> {
+        if data.len() < 8 {
+            return Err(ParseError::truncated(Structure::StrongEncryptionHeader, "flags"));
+        }
+
+        Ok(StrongEncryptionHeader {
+            format: read_u16_le(&data[0..2]).unwrap(),
+            algorithm_id: read_u16_le(&data[2..4]).unwrap(),
+            bit_length: read_u16_le(&data[4..6]).unwrap(),
+            flags: read_u16_le(&data[6..8]).unwrap(),
+            certificate_data: data[8..].to_vec(),
+        })
+    }
+}
+
+/// Represents a reader for DecryptionHeader
+pub struct DecryptionHeaderReader {
+
+}
+
+impl DecryptionHeaderReader {
+    /// Read a file and try to create a DecryptionHeader.
+    /// The header starts with the initialization vector, followed by the size
+    /// of the rest of the header. The rest is read as a whole, then decoded.
+    pub fn read<R: Read + Seek>(file: &mut R) -> Result<DecryptionHeader, ParseError> {
+        let mut reader = FieldReader::new(file, Structure::DecryptionHeader)?;
+        let iv_size = reader.u16("IV size")?;
+        let iv = reader.bytes(iv_size as usize, "IV")?;
+        let size = reader.u32("size")?;
+        let data_offset = reader.offset;
+        let data = reader.bytes(size as usize, "header")?;
+
+        // The fields are read from data, the size of the header may be wrong
+        let mut cursor = 0;
+        let mut take = |length: usize, field: &'static str| -> Result<&[u8], ParseError> {
+            let value = data.get(cursor..cursor + length)
+                .ok_or(ParseError::truncated(Structure::DecryptionHeader, field).at(data_offset + cursor as u64))?;
+            cursor += length;
+            Ok(value)
+        };
+
+        let format = read_u16_le(take(2, "format")?).unwrap();
+        let algorithm_id = read_u16_le(take(2, "algorithm ID")?).unwrap();
+        let bit_length = read_u16_le(take(2, "bit length")?).unwrap();
+        let flags = read_u16_le(take(2, "flags")?).unwrap();
+        let encrypted_random_data_size = read_u16_le(take(2, "encrypted random data size")?).unwrap();
+        let encrypted_random_data = take(encrypted_random_data_size as usize, "encrypted random data")?.to_vec();
+        let recipient_count = read_u32_le(take(4, "recipient count")?).unwrap();
+
+        // The recipient list is present only for certificate processing
+        let mut hash_algorithm = None;
+        let mut hash_size = None;
+        let mut recipient_list = Vec::new();
+        if recipient_count > 0 {
+            hash_algorithm = Some(read_u16_le(take(2, "hash algorithm")?).unwrap());
+            let size = read_u16_le(take(2, "hash size")?).unwrap();
+            hash_size = Some(size);
+            recipient_list = take(recipient_count as usize * size as usize, "recipient list")?.to_vec();
+        }
+
+        // The size of the validation data includes its CRC32
+        let validation_size = read_u16_le(take(2, "password validation data size")?).unwrap();
+        let validation_data_size = (validation_size as usize).checked_sub(4)
+            .ok_or(ParseError::invalid_value(Structure::DecryptionHeader, "password validation data size", format!("invalid size {}", validation_size)))?;
+        let password_validation_data = take(validation_data_size, "password validation data")?.to_vec();
+        let password_validation_crc32 = read_u32_le(take(4, "password validation CRC32")?).unwrap();
+
+        Ok(DecryptionHeader {
+            iv,
+            size,
+            format,
+            algorithm_id,
+            bit_length,
+            flags,
+            encrypted_random_data,
+            recipient_count,
+            hash_algorithm,
+            hash_size,
+            recipient_list,
+            password_validation_data,
+            password_validation_crc32,
+        })
+    }
+}
+
+/// Represents a reader for ArchiveDecryptionHeader
+pub struct ArchiveDecryptionHeaderReader {
+
+}
 
-        let crc32 = read_u32_le(&crc32_chunk);
-        let compressed_size = read_u32_le(&compressed_size_chunk);
-        let uncompressed_size = read_u32_le(&uncompressed_size_chunk);
+impl ArchiveDecryptionHeaderReader {
+    /// Read a file and try to create an ArchiveDecryptionHeader.
+    /// The encrypted central directory follows the archive decryption header and
+    /// the optional archive extra data record, up to end_offset.
+    pub fn read<R: Read + Seek>(file: &mut R, end_offset: u64) -> Result<ArchiveDecryptionHeader, ParseError> {
+        let offset_from_start_of_archive = file.stream_position()
+            .map_err(|e| ParseError::io(e, Structure::ArchiveDecryptionHeader))?;
+        let decryption_header = DecryptionHeaderReader::read(file)?;
 
-        if crc32.is_err() {
-            return Err("Unable to read DataDescriptor: unreadable crc32".to_string());
-        } else if compressed_size.is_err() {
-            return Err("Unable to read DataDescriptor: unreadable compressed size".to_string());
-        } else if uncompressed_size.is_err() {
-            return Err("Unable to read DataDescriptor: unreadable uncompressed size".to_string());
+        let mut archive_extra_data_record = None;
+        if compare_signature(file, constants::SIGNATURE_ARCHIVE_EXTRA_DATA_RECORD).unwrap_or(false) {
+            archive_extra_data_record = Some(ArchiveExtraDataRecordReader::read(file)?);
         }
 
+        let current_offset = file.stream_position()
+            .map_err(|e| ParseError::io(e, Structure::ArchiveDecryptionHeader))?;
+        let encrypted_central_directory = try_read_chunk(file, end_offset.saturating_sub(current_offset) as usize)
+            .map_err(|e| ParseError::io(e, Structure::ArchiveDecryptionHeader).with_field("encrypted central directory").at(current_offset))?;
+
+        Ok(ArchiveDecryptionHeader {
+            decryption_header,
+            archive_extra_data_record,
+            encrypted_central_directory,
+            offset_from_start_of_archive: offset_from_start_of_archive as usize,
+        })
+    }
+}
+
+/// Represents a reader for AesExtraField
+pub struct AesExtraFieldReader {
+
+}
+
+impl AesExtraFieldReader {
+    /// Read the data of a WinZip AES extra field and try to create an
+    /// AesExtraField.
+    pub fn read(data: &[u8]) -> Result<AesExtraField, ParseError> {
+        if data.len() != 7 {
+            return Err(ParseError::invalid_value(Structure::AesExtraField, "data size", format!("invalid size {}", data.len())));
+        }
+
+        Ok(AesExtraField {
+            vendor_version: read_u16_le(&data[0..2]).unwrap(),
+            vendor_id: read_string_bytes(&data[2..4]),
+            strength: data[4],
+            compression_method: read_u16_le(&data[5..7]).unwrap(),
+        })
+    }
+}
+
+/// Represents a reader for DataDescriptor
+pub struct DataDescriptorReader {
+
+}
+
+impl DataDescriptorReader {
+    /// Read a file and try to create a DataDescriptor.
+    /// The signature of the data descriptor is optional, it's consumed only if
+    /// present. For zip64 files, the sizes are stored on 8 bytes.
+    pub fn read<R: Read + Seek>(file: &mut R, zip64: bool) -> Result<DataDescriptor, ParseError> {
+        let signature_present = compare_signature(file, constants::SIGNATURE_DATA_DESCRIPTOR)?;
+        let size_length = if zip64 { 8 } else { 4 };
+        let mut reader = FieldReader::new(file, Structure::DataDescriptor)?;
+        let crc32 = reader.u32("crc32")?;
+        let compressed_size = read_size_le(&reader.bytes(size_length, "compressed size")?).unwrap();
+        let uncompressed_size = read_size_le(&reader.bytes(size_length, "uncompressed size")?).unwrap();
+
         Ok(DataDescriptor {
-            crc32: crc32.unwrap(),
-            compressed_size: compressed_size.unwrap(),
-            uncompressed_size: uncompressed_size.unwrap(),
+            signature_present,
+            crc32,
+            compressed_size,
+            uncompressed_size,
         })
     }
 }
 
+/// Read a size stored on 4 or 8 bytes, depending on the length of the chunk
+fn read_size_le(chunk: &[u8]) -> Option<u64> {
+    match chunk.len() {
+        4 => read_u32_le(chunk).ok().map(|size| size as u64),
+        _ => read_u64_le(chunk).ok(),
+    }
+}
+
+/// What is done with the data of the files while they are read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataHandling {
+    /// The data is skipped, it's read again from the archive when needed
+    Skip,
+    /// The data is kept in memory, for a stream which can't be read again
+    Keep,
+    /// The data of a stream is decompressed as it's read, and only the summary
+    /// of the content is kept. The data of encrypted files isn't decompressed.
+    Summarize,
+}
+
 /// Represents a reader for StoredFile
 pub struct StoredFileReader {
 
 }
 
 impl StoredFileReader {
-    /// Read a file and try to create a StoredFile
-    pub fn read(file: &mut File, position: usize) -> Result<StoredFile, String> {
+    /// Read a file and try to create a StoredFile.
+    /// The central directory, if already known, helps to find the size of the
+    /// files whose sizes are only in the data descriptor.
+    /// The file data is skipped and only its position is recorded, unless
+    /// data_handling says otherwise: a stream can't be read again, its data
+    /// has to be kept or summarized.
+    /// The problems which don't prevent reading the file are added to diagnostics.
+    pub fn read<R: Read + Seek>(file: &mut R, position: usize, central_directory: Option<&CentralDirectory>, data_handling: DataHandling, diagnostics: &mut Vec<Diagnostic>) -> Result<StoredFile, ParseError> {
         // Read the offset, or stop the function and return the error
-        let mut offset_in_archive = file.stream_position()
-                                .or(Err("Unable to read current position in archive".to_string()))?;
+        let mut offset_in_archive = current_position(file, Structure::LocalFileHeader)?;
 
         // The StoredFile begins with a 4-bytes signature. This signature has already been consumed.
         // So substract 4 to the current offset, to match the reality
         offset_in_archive -= 4;
         let local_file_header = LocalFileHeaderReader::read(file)?;
-        let file_data = read_chunk(file, local_file_header.compressed_size as usize);
+        report_malformed_extra_field(&local_file_header.extra_field,
+            offset_in_archive + constants::LOCAL_FILE_HEADER_SIZE + local_file_header.filename_raw.len() as u64,
+            &format!("the local file header of {}", local_file_header.filename), diagnostics);
+        let has_data_descriptor = local_file_header.general_purpose_flag & constants::GENERAL_PURPOSE_FLAG_DATA_DESCRIPTOR != 0;
+        let data_offset = current_position(file, Structure::FileData)?;
+
+        // Files using strong encryption start with a decryption header
+        let mut decryption_header = None;
+        if local_file_header.uses_strong_encryption() {
+            match DecryptionHeaderReader::read(file) {
+                Ok(header) => decryption_header = Some(header),
+                Err(e) => diagnostics.push(Diagnostic::new(Severity::Warning, DiagnosticCode::UnreadableDecryptionHeader, Some(data_offset), e.to_string())),
+            }
+            move_to(file, data_offset, Structure::FileData)?;
+        }
+
+        // The data of unknown size is kept until its end is found, even when
+        // it's summarized
+        let keep_data = data_handling != DataHandling::Skip;
+        let mut content_summary = None;
+        let (data_size, mut file_data, data_size_source) = if has_data_descriptor {
+            read_data_of_unknown_size(file, &local_file_header, offset_in_archive, central_directory, keep_data)?
+        } else if data_handling == DataHandling::Summarize {
+            let (data_size, summary) = summarize_data(file, &local_file_header, data_offset)?;
+            content_summary = summary;
+            (data_size, None, DataSizeSource::LocalFileHeader)
+        } else {
+            let (data_size, file_data) = read_or_skip_data(file, local_file_header.compressed_size, keep_data)?;
+            (data_size, file_data, DataSizeSource::LocalFileHeader)
+        };
+
+        if has_data_descriptor && data_size_source == DataSizeSource::LocalFileHeader {
+            diagnostics.push(Diagnostic::new(Severity::Warning, DiagnosticCode::UnknownDataSize, Some(data_offset),
+                format!("The data descriptor of {} wasn't found, the size of the local file header is used", local_file_header.filename)));
+        }
+        let announced_size = match data_size_source {
+            DataSizeSource::LocalFileHeader => Some(local_file_header.compressed_size),
+            DataSizeSource::CentralDirectory => central_directory
+                .and_then(|central_directory| central_directory.file_headers.iter()
+                    .find(|header| header.offset_in_archive == Some(offset_in_archive)))
+                .map(|header| header.compressed_size),
+            _ => None,
+        };
+        if let Some(announced_size) = announced_size.filter(|announced_size| data_size < *announced_size) {
+            diagnostics.push(Diagnostic::new(Severity::Warning, DiagnosticCode::TruncatedFileData, Some(data_offset),
+                format!("Only {} of the {} bytes of data of {} are present", data_size, announced_size, local_file_header.filename)));
+        }
+
         let mut data_descriptor: Option<DataDescriptor> = None;
         // If bit 3 of general purpose flag is set, read data descriptor
-        if local_file_header.general_purpose_flag & 4 == 4 {
-            data_descriptor = Some(DataDescriptorReader::read(file)?);
+        if has_data_descriptor {
+            let zip64 = local_file_header.extra_field.find(constants::EXTRA_FIELD_ZIP64_EXTENDED_INFORMATION).is_some();
+            data_descriptor = Some(DataDescriptorReader::read(file, zip64)?);
+        }
+
+        // The data kept until its end was found is summarized now, with the
+        // uncompressed size of the data descriptor
+        if data_handling == DataHandling::Summarize {
+            let encrypted = local_file_header.general_purpose_flag & constants::GENERAL_PURPOSE_FLAG_ENCRYPTED != 0;
+            if let Some(data) = file_data.take().filter(|_| !encrypted) {
+                let uncompressed_size = data_descriptor.as_ref()
+                    .map_or(local_file_header.uncompressed_size, |data_descriptor| data_descriptor.uncompressed_size);
+                content_summary = Some(decompression::summarize(data.as_slice(), data_size, local_file_header.compression_method, local_file_header.general_purpose_flag, uncompressed_size)
+                    .map_err(|e| e.or_at(data_offset)));
+            }
         }
 
         Ok(StoredFile {
-            local_file_header: local_file_header,
-            file_data: file_data,
-            data_descriptor: data_descriptor,
+            local_file_header,
+            data_offset,
+            data_size,
+            file_data,
+            content_summary,
+            data_descriptor,
+            data_size_source,
+            decryption_header,
             // Position is computed in ZipFile
-            position: position,
+            position,
             // Set to true when reading the central directory
             found_in_central_directory: false,
             offset_in_archive: offset_in_archive as usize,
@@ -140,6 +768,200 @@ impl StoredFileReader {
     }
 }
 
+/// Read the data of a file from a stream, and summarize its content while
+/// it's decompressed, without keeping the data. The data of encrypted files
+/// is only read.
+/// Returns the size of the data, lower than the compressed size if the stream
+/// ends first, and the summary of the content.
+fn summarize_data<R: Read>(file: &mut R, local_file_header: &LocalFileHeader, data_offset: u64) -> Result<(u64, Option<Result<ContentSummary, ParseError>>), ParseError> {
+    let compressed_size = local_file_header.compressed_size;
+    let encrypted = local_file_header.general_purpose_flag & constants::GENERAL_PURPOSE_FLAG_ENCRYPTED != 0;
+    let mut data = file.take(compressed_size);
+    let mut content_summary = (!encrypted).then(|| {
+        decompression::summarize(&mut data, compressed_size, local_file_header.compression_method, local_file_header.general_purpose_flag, local_file_header.uncompressed_size)
+            .map_err(|e| e.or_at(data_offset))
+    });
+    // The bytes left after the end of the compressed stream, if any
+    io::copy(&mut data, &mut io::sink())
+        .map_err(|e| ParseError::io(e, Structure::FileData))?;
+
+    let data_size = compressed_size - data.limit();
+    if let Some(Ok(summary)) = &mut content_summary {
+        summary.compressed_size = data_size;
+    }
+    Ok((data_size, content_summary))
+}
+
+/// The size of the chunks in which the data of a file is kept
+const DATA_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// The size of the chunks in which a signature is searched
+const SEARCH_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Read the data of a file if keep_data is set, or else skip it.
+/// Returns the size of the data, lower than size if the archive is truncated,
+/// and the data if it was read.
+fn read_or_skip_data<R: Read + Seek>(file: &mut R, size: u64, keep_data: bool) -> Result<(u64, Option<Vec<u8>>), ParseError> {
+    if keep_data {
+        // The size comes from the archive, the data is read by chunks until
+        // it's reached or the archive ends
+        let mut data = Vec::new();
+        while (data.len() as u64) < size {
+            let chunk_size = (size - data.len() as u64).min(DATA_CHUNK_SIZE);
+            let chunk = try_read_chunk(file, chunk_size as usize)
+                .map_err(|e| ParseError::io(e, Structure::FileData))?;
+            data.extend_from_slice(&chunk);
+            if (chunk.len() as u64) < chunk_size {
+                break;
+            }
+        }
+        return Ok((data.len() as u64, Some(data)));
+    }
+
+    let data_start = current_position(file, Structure::FileData)?;
+    let end_of_file = file.seek(SeekFrom::End(0))
+        .map_err(|e| ParseError::io(e, Structure::Archive))?;
+    let data_end = data_start.saturating_add(size).min(end_of_file).max(data_start);
+    move_to(file, data_end, Structure::FileData)?;
+    Ok((data_end - data_start, None))
+}
+
+/// Read the data of a file whose sizes are in the data descriptor, as
+/// read_or_skip_data().
+/// The local file header usually holds 0 as compressed size in this case, as
+/// the size wasn't known yet when it was written.
+/// In order, the size is taken from:
+/// 1. the matching central directory file header, if the central directory is known
+/// 2. the end of the deflate stream, for deflated files
+/// 3. the data descriptor following the data, found by searching it
+/// 4. the local file header, as a last resort
+///
+/// The file cursor must be at the start of the data, it's left at its end.
+/// When the data is kept, the cursor only moves back over the bytes read
+/// ahead, so that a stream can be read, except in the last resort case.
+fn read_data_of_unknown_size<R: Read + Seek>(file: &mut R, local_file_header: &LocalFileHeader, offset_in_archive: u64, central_directory: Option<&CentralDirectory>, keep_data: bool) -> Result<(u64, Option<Vec<u8>>, DataSizeSource), ParseError> {
+    let data_start = current_position(file, Structure::FileData)?;
+
+    let central_directory_file_header = central_directory.and_then(|central_directory| {
+        central_directory.file_headers.iter()
+            .find(|header| header.offset_in_archive == Some(offset_in_archive))
+    });
+    if let Some(header) = central_directory_file_header {
+        let (data_size, file_data) = read_or_skip_data(file, header.compressed_size, keep_data)?;
+        return Ok((data_size, file_data, DataSizeSource::CentralDirectory));
+    }
+
+    // The bytes read from data_start, when the data is kept. They are searched
+    // for the data descriptor if the deflate stream is unreadable.
+    let mut data = Vec::new();
+    let encrypted = local_file_header.general_purpose_flag & constants::GENERAL_PURPOSE_FLAG_ENCRYPTED != 0;
+    if local_file_header.compression_method == constants::COMPRESSION_METHOD_DEFLATED && !encrypted {
+        let mut recorder = RecordingReader { input: &mut *file, data, recording: keep_data };
+        let result = inflate(&mut recorder, &mut io::sink());
+        data = recorder.data;
+        if let Ok(summary) = result {
+            data.truncate(summary.consumed as usize);
+            move_to(file, data_start + summary.consumed, Structure::FileData)?;
+            return Ok((summary.consumed, keep_data.then_some(data), DataSizeSource::DeflateStream));
+        }
+        // Without the bytes read, start again from the start of the data
+        if !keep_data {
+            move_to(file, data_start, Structure::FileData)?;
+        }
+    }
+
+    let zip64 = local_file_header.extra_field.find(constants::EXTRA_FIELD_ZIP64_EXTENDED_INFORMATION).is_some();
+    if let Some((data_size, data_size_source)) = find_data_descriptor(file, &mut data, zip64, keep_data) {
+        data.truncate(data_size as usize);
+        move_to(file, data_start + data_size, Structure::FileData)?;
+        return Ok((data_size, keep_data.then_some(data), data_size_source));
+    }
+
+    move_to(file, data_start, Structure::FileData)?;
+    let (data_size, file_data) = read_or_skip_data(file, local_file_header.compressed_size, keep_data)?;
+    Ok((data_size, file_data, DataSizeSource::LocalFileHeader))
+}
+
+/// A reader keeping a copy of the bytes read, if recording is set
+struct RecordingReader<'a, R: Read> {
+    input: &'a mut R,
+    data: Vec<u8>,
+    recording: bool,
+}
+
+impl<R: Read> Read for RecordingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.input.read(buf)?;
+        if self.recording {
+            self.data.extend_from_slice(&buf[..read]);
+        }
+        Ok(read)
+    }
+}
+
+/// Search the data descriptor following data of unknown size.
+/// data holds the bytes already read from the start of the data, the next
+/// ones are read from file and appended to it. Unless keep_data is set, the
+/// bytes which can't be part of the data descriptor are dropped from data.
+/// A data descriptor is recognized when its compressed size matches the
+/// number of bytes since the start of the data and:
+/// - it starts with the data descriptor signature, or
+/// - it's followed by the signature of the next part of the archive.
+///
+/// Returns the size of the data, or None if no data descriptor was found.
+fn find_data_descriptor<R: Read>(file: &mut R, data: &mut Vec<u8>, zip64: bool, keep_data: bool) -> Option<(u64, DataSizeSource)> {
+    let size_length = if zip64 { 8 } else { 4 };
+    // CRC32, compressed size and uncompressed size
+    let unsigned_length = 4 + 2 * size_length;
+    let signature = constants::SIGNATURE_DATA_DESCRIPTOR.to_le_bytes();
+    let next_signatures = [
+        constants::SIGNATURE_HEADER_LOCAL_FILE,
+        constants::SIGNATURE_ARCHIVE_EXTRA_DATA_RECORD,
+        constants::SIGNATURE_HEADER_CENTRAL_DIRECTORY,
+        constants::SIGNATURE_ZIP64_END_OF_CENTRAL_DIRECTORY_RECORD,
+        constants::SIGNATURE_END_OF_CENTRAL_DIRECTORY_RECORD,
+    ];
+
+    let mut end_of_file = false;
+    let mut data_size = 0;
+    // The number of bytes dropped from the start of data
+    let mut dropped = 0;
+    loop {
+        let index = data_size - dropped;
+        // The longest check needs a signed data descriptor and the next signature.
+        // At most one chunk is read ahead, so that a stream can give it back.
+        while !end_of_file && data.len() < index + 4 + unsigned_length + 4 {
+            let chunk = read_chunk(file, 65536);
+            end_of_file = chunk.is_empty();
+            data.extend(chunk);
+        }
+        if data.len() < index + unsigned_length {
+            return None;
+        }
+
+        let candidate = &data[index..];
+        if candidate[0..4] == signature
+            && candidate.get(8..8 + size_length).and_then(read_size_le) == Some(data_size as u64)
+        {
+            return Some((data_size as u64, DataSizeSource::SignedDataDescriptor));
+        }
+        if read_size_le(&candidate[4..4 + size_length]) == Some(data_size as u64) {
+            let next = candidate.get(unsigned_length..unsigned_length + 4)
+                .and_then(|chunk| read_u32_le(chunk).ok());
+            if next.is_some_and(|next| next_signatures.contains(&next)) {
+                return Some((data_size as u64, DataSizeSource::UnsignedDataDescriptor));
+            }
+        }
+
+        data_size += 1;
+        // Drop the bytes which can't be part of a data descriptor anymore
+        if !keep_data && index >= 65536 {
+            data.drain(..index);
+            dropped += index;
+        }
+    }
+}
+
 /// Represents a reader for ArchiveExtraDataRecord
 pub struct ArchiveExtraDataRecordReader {
 
@@ -147,14 +969,13 @@ pub struct ArchiveExtraDataRecordReader {
 
 impl ArchiveExtraDataRecordReader {
     /// Read a file and try to create an ArchiveExtraDataRecord
-    pub fn read(file: &mut File) -> Result<ArchiveExtraDataRecord, String> {
-        let extra_field_length_chunk = read_chunk(file, 4);
-        let extra_field_length = read_u32_le(&extra_field_length_chunk)
-            .or(Err("Unable to read the archive extra data record: unreadable extra field length".to_string()))?;
+    pub fn read<R: Read + Seek>(file: &mut R) -> Result<ArchiveExtraDataRecord, ParseError> {
+        let mut reader = FieldReader::new(file, Structure::ArchiveExtraDataRecord)?;
+        let extra_field_length = reader.u32("extra field length")?;
 
-        let extra_field = read_chunk(file, extra_field_length as usize);
+        let extra_field = ExtraFieldReader::read(reader.bytes(extra_field_length as usize, "extra field")?);
         Ok(ArchiveExtraDataRecord {
-            extra_field: extra_field,
+            extra_field,
         })
     }
 }
@@ -166,83 +987,80 @@ pub struct CentralDirectoryFileHeaderReader {
 
 impl CentralDirectoryFileHeaderReader {
     /// Read a file and try to create a CentralDirectory
-    pub fn read(file: &mut File) -> Result<CentralDirectoryFileHeader, String> {
-        let version_made_by_chunk = read_chunk(file, 2);
-        let minimum_version_chunk = read_chunk(file, 2);
-        let general_purpose_flag_chunk = read_chunk(file, 2);
-        let compression_method_chunk = read_chunk(file, 2);
-        let file_last_modification_time_chunk = read_chunk(file, 2);
-        let file_last_modification_date_chunk = read_chunk(file, 2);
-        let crc32_chunk = read_chunk(file, 4);
-        let compressed_size_chunk = read_chunk(file, 4);
-        let uncompressed_size_chunk = read_chunk(file, 4);
-        let filename_length_chunk = read_chunk(file, 2);
-        let extra_field_length_chunk = read_chunk(file, 2);
-        let file_comment_length_chunk = read_chunk(file, 2);
-        let disk_number_where_file_starts_chunk = read_chunk(file, 2);
-        let internal_file_attributes_chunk = read_chunk(file, 2);
-        let external_file_attributes_chunk = read_chunk(file, 4);
-        let relative_offset_of_local_header_chunk = read_chunk(file, 4);
-
-        // Handle errors and read numbers from values above
-        let version_made_by = read_u16_le(&version_made_by_chunk)
-            .or(Err("Unable to read the central directory file header: unreadable version made by".to_string()))?;
-        let minimum_version = read_u16_le(&minimum_version_chunk)
-            .or(Err("Unable to read the central directory file header: unreadable minimum version".to_string()))?;
-        let general_purpose_flag = read_u16_le(&general_purpose_flag_chunk)
-            .or(Err("Unable to read the central directory file header: unreadable general purpose flag".to_string()))?;
-        let compression_method = read_u16_le(&compression_method_chunk)
-            .or(Err("Unable to read the central directory file header: unreadable compression method".to_string()))?;
-        let file_last_modification_time = read_u16_le(&file_last_modification_time_chunk)
-            .or(Err("Unable to read the central directory file header: unreadable file last modification time".to_string()))?;
-        let file_last_modification_date = read_u16_le(&file_last_modification_date_chunk)
-            .or(Err("Unable to read the central directory file header: unreadable file last modification date".to_string()))?;
-        let crc32 = read_u32_le(&crc32_chunk)
-            .or(Err("Unable to read the central directory file header: unreadable crc32".to_string()))?;
-        let compressed_size = read_u32_le(&compressed_size_chunk)
-            .or(Err("Unable to read the central directory file header: unreadable compressed size".to_string()))?;
-        let uncompressed_size = read_u32_le(&uncompressed_size_chunk)
-            .or(Err("Unable to read the central directory file header: unreadable uncompressed size".to_string()))?;
-        let filename_length = read_u16_le(&filename_length_chunk)
-            .or(Err("Unable to read the central directory file header: unreadable filename length".to_string()))?;
-        let extra_field_length = read_u16_le(&extra_field_length_chunk)
-            .or(Err("Unable to read the central directory file header: unreadable extra field length".to_string()))?;
-        let file_comment_length = read_u16_le(&file_comment_length_chunk)
-            .or(Err("Unable to read the central directory file header: unreadable file comment length".to_string()))?;
-        let disk_number_where_file_starts = read_u16_le(&disk_number_where_file_starts_chunk)
-            .or(Err("Unable to read the central directory file header: unreadable disk number where file starts".to_string()))?;
-        let internal_file_attributes = read_u16_le(&internal_file_attributes_chunk)
-            .or(Err("Unable to read the central directory file header: unreadable internal file attributes".to_string()))?;
-        let external_file_attributes = read_u32_le(&external_file_attributes_chunk)
-            .or(Err("Unable to read the central directory file header: unreadable external file attributes".to_string()))?;
-        let relative_offset_of_local_header = read_u32_le(&relative_offset_of_local_header_chunk)
-            .or(Err("Unable to read the central directory file header: unreadable internal file attributes".to_string()))?;
-
-        let filename_chunk = read_chunk(file, filename_length as usize);
-        let extra_field_chunk = read_chunk(file, extra_field_length as usize);
-        let file_comment_chunk = read_chunk(file, file_comment_length as usize);
-
-        let filename = read_string_bytes(&filename_chunk);
-        let file_comment = read_string_bytes(&file_comment_chunk);
+    pub fn read<R: Read + Seek>(file: &mut R) -> Result<CentralDirectoryFileHeader, ParseError> {
+        let mut reader = FieldReader::new(file, Structure::CentralDirectoryFileHeader)?;
+        let version_made_by = reader.u16("version made by")?;
+        let minimum_version = reader.u16("minimum version")?;
+        let general_purpose_flag = reader.u16("general purpose flag")?;
+        let compression_method = reader.u16("compression method")?;
+        let file_last_modification_time = reader.u16("file last modification time")?;
+        let file_last_modification_date = reader.u16("file last modification date")?;
+        let crc32 = reader.u32("crc32")?;
+        let compressed_size = reader.u32("compressed size")?;
+        let uncompressed_size = reader.u32("uncompressed size")?;
+        let filename_length = reader.u16("file name length")?;
+        let extra_field_length = reader.u16("extra field length")?;
+        let file_comment_length = reader.u16("file comment length")?;
+        let disk_number_where_file_starts = reader.u16("disk number where file starts")?;
+        let internal_file_attributes = reader.u16("internal file attributes")?;
+        let external_file_attributes = reader.u32("external file attributes")?;
+        let relative_offset_of_local_header = reader.u32("relative offset of local header")?;
+        let filename_chunk = reader.bytes(filename_length as usize, "file name")?;
+        let extra_field_offset = reader.offset;
+        let extra_field_chunk = reader.bytes(extra_field_length as usize, "extra field")?;
+        let file_comment_chunk = reader.bytes(file_comment_length as usize, "file comment")?;
+
+        let extra_field = ExtraFieldReader::read(extra_field_chunk);
+        let (filename, filename_encoding) = decode_text(&filename_chunk, general_purpose_flag, extra_field.unicode_path());
+        let (file_comment, file_comment_encoding) = decode_text(&file_comment_chunk, general_purpose_flag, extra_field.unicode_comment());
+
+        let mut compressed_size = compressed_size as u64;
+        let mut uncompressed_size = uncompressed_size as u64;
+        let mut relative_offset_of_local_header = relative_offset_of_local_header as u64;
+        let mut disk_number_where_file_starts = disk_number_where_file_starts as u32;
+
+        // In a zip64 archive, the values too big to be stored in the header
+        // are stored in the zip64 extended information.
+        let uncompressed_size_in_zip64 = uncompressed_size == constants::ZIP64_PLACEHOLDER_U32 as u64;
+        let compressed_size_in_zip64 = compressed_size == constants::ZIP64_PLACEHOLDER_U32 as u64;
+        let offset_in_zip64 = relative_offset_of_local_header == constants::ZIP64_PLACEHOLDER_U32 as u64;
+        let disk_start_in_zip64 = disk_number_where_file_starts == constants::ZIP64_PLACEHOLDER_U16 as u32;
+        let mut zip64_extended_information = None;
+        if uncompressed_size_in_zip64 || compressed_size_in_zip64 || offset_in_zip64 || disk_start_in_zip64 {
+            let zip64 = Zip64ExtendedInformationReader::read_from_extra_field(&extra_field, uncompressed_size_in_zip64, compressed_size_in_zip64, offset_in_zip64, disk_start_in_zip64)
+                .map_err(|e| e.or_at(extra_field_offset))?;
+            uncompressed_size = zip64.uncompressed_size.unwrap_or(uncompressed_size);
+            compressed_size = zip64.compressed_size.unwrap_or(compressed_size);
+            relative_offset_of_local_header = zip64.local_file_header_offset.unwrap_or(relative_offset_of_local_header);
+            disk_number_where_file_starts = zip64.disk_start.unwrap_or(disk_number_where_file_starts);
+            zip64_extended_information = Some(zip64);
+        }
 
         Ok(CentralDirectoryFileHeader {
-            version_made_by: version_made_by,
-            minimum_version: minimum_version,
-            general_purpose_flag: general_purpose_flag,
+            version_made_by,
+            minimum_version,
+            general_purpose_flag,
             compression_method,
-            file_last_modification_time: file_last_modification_time,
-            file_last_modification_date: file_last_modification_date,
-            crc32: crc32,
-            compressed_size: compressed_size,
-            uncompressed_size: uncompressed_size,
+            file_last_modification_time,
+            file_last_modification_date,
+            crc32,
+            compressed_size,
+            uncompressed_size,
             disk_start: disk_number_where_file_starts,
-            internal_file_attributes: internal_file_attributes,
-            external_file_attributes: external_file_attributes,
+            internal_file_attributes,
+            external_file_attributes,
             local_file_header_offset: relative_offset_of_local_header,
-            filename: filename,
-            extra_field: extra_field_chunk,
-            file_comment: file_comment,
+            // Resolved by the CentralDirectoryReader for split archives
+            offset_in_archive: Some(relative_offset_of_local_header),
+            filename,
+            filename_raw: filename_chunk,
+            filename_encoding,
+            extra_field,
+            file_comment,
+            file_comment_raw: file_comment_chunk,
+            file_comment_encoding,
             position: None,
+            zip64_extended_information,
         })
     }
 }
@@ -254,12 +1072,10 @@ pub struct DigitalSignatureReader {
 
 impl DigitalSignatureReader {
     /// Read a file and try to create a DigitalSignature
-    pub fn read(file: &mut File) -> Result<DigitalSignature, String> {
-        let size_of_data_chunk = read_chunk(file, 2);
-        let size_of_data = read_u16_le(&size_of_data_chunk)
-            .or(Err("Unable to read digital signature: unreadable size of data".to_string()))?;
-
-        let signature_data_chunk = read_chunk(file, size_of_data as usize);
+    pub fn read<R: Read + Seek>(file: &mut R) -> Result<DigitalSignature, ParseError> {
+        let mut reader = FieldReader::new(file, Structure::DigitalSignature)?;
+        let size_of_data = reader.u16("size of data")?;
+        let signature_data_chunk = reader.bytes(size_of_data as usize, "signature data")?;
 
         Ok(DigitalSignature {
             signature_data: signature_data_chunk,
@@ -274,40 +1090,133 @@ pub struct EndOfCentralDirectoryRecordReader {
 
 impl EndOfCentralDirectoryRecordReader {
     /// Read a file and try to create a EndOfCentralDirectoryRecord
-    pub fn read(file: &mut File) -> Result<EndOfCentralDirectoryRecord, String> {
-        let number_of_this_disk_chunk = read_chunk(file, 2);
-        let disk_where_central_directory_starts_chunk = read_chunk(file, 2);
-        let number_of_central_directory_records_on_this_disk_chunk = read_chunk(file, 2);
-        let total_number_of_central_directory_records_chunk = read_chunk(file, 2);
-        let size_of_central_directory_chunk = read_chunk(file, 4);
-        let offset_start_of_central_directory_from_archive_chunk = read_chunk(file, 4);
-        let comment_length_chunk = read_chunk(file, 2);
-        let comment_length = read_u16_le(&comment_length_chunk)
-            .or(Err("Unable to read end of central directory: unreadable comment length".to_string()))?;
-        let comment_chunk = read_chunk(file, comment_length as usize);
-        let comment = read_string_bytes(&comment_chunk);
-
-        let number_of_this_disk = read_u16_le(&number_of_this_disk_chunk)
-            .or(Err("Unable to read end of central directory: unreadable disk number".to_string()))?;
-        let disk_where_central_directory_starts = read_u16_le(&disk_where_central_directory_starts_chunk)
-            .or(Err("Unable to read end of central directory: unreadable disk where central directory starts".to_string()))?;
-        let number_of_central_directory_records_on_this_disk = read_u16_le(&number_of_central_directory_records_on_this_disk_chunk)
-            .or(Err("Unable to read end of central directory: unreadable number of central directory records on this disk".to_string()))?;
-        let total_number_of_central_directory_records = read_u16_le(&total_number_of_central_directory_records_chunk)
-            .or(Err("Unable to read end of central directory: unreadable total number or central directory records".to_string()))?;
-        let size_of_central_directory = read_u32_le(&size_of_central_directory_chunk)
-            .or(Err("Unable to read end of central directory: unreadable size of central directory".to_string()))?;
-        let offset_start_of_central_directory_from_archive = read_u32_le(&offset_start_of_central_directory_from_archive_chunk)
-            .or(Err("Unable to read end of central directory: unreadable offset of start of central directory from archive".to_string()))?;
+    pub fn read<R: Read + Seek>(file: &mut R) -> Result<EndOfCentralDirectoryRecord, ParseError> {
+        let mut reader = FieldReader::new(file, Structure::EndOfCentralDirectoryRecord)?;
+        let number_of_this_disk = reader.u16("disk number")?;
+        let disk_where_central_directory_starts = reader.u16("disk where central directory starts")?;
+        let number_of_central_directory_records_on_this_disk = reader.u16("number of central directory records on this disk")?;
+        let total_number_of_central_directory_records = reader.u16("total number of central directory records")?;
+        let size_of_central_directory = reader.u32("size of central directory")?;
+        let offset_start_of_central_directory_from_archive = reader.u32("offset of start of central directory from archive")?;
+        let comment_length = reader.u16("comment length")?;
+        // The comment is at the end of the archive, a truncated one is kept as is
+        let comment_chunk = try_read_chunk(reader.file, comment_length as usize)
+            .map_err(|e| reader.error(ParseErrorKind::Io(e), "comment"))?;
+        // The archive comment has no encoding flag. Most tools write
+        // either ASCII, which is valid in both encodings, or UTF-8.
+        let comment = match std::str::from_utf8(&comment_chunk) {
+            Ok(comment) => comment.to_string(),
+            Err(_) => decode_cp437(&comment_chunk),
+        };
 
         Ok(EndOfCentralDirectoryRecord {
+            disk_number: number_of_this_disk as u32,
+            disk_start_central_directory: disk_where_central_directory_starts as u32,
+            central_directory_records_number_on_disk: number_of_central_directory_records_on_this_disk as u64,
+            central_directory_records_total_number: total_number_of_central_directory_records as u64,
+            central_directory_size: size_of_central_directory as u64,
+            offset_start_central_directory: offset_start_of_central_directory_from_archive as u64,
+            comment,
+            comment_raw: comment_chunk,
+        })
+    }
+}
+
+/// Represents a reader for Zip64EndOfCentralDirectoryRecord
+pub struct Zip64EndOfCentralDirectoryRecordReader {
+
+}
+
+impl Zip64EndOfCentralDirectoryRecordReader {
+    /// Read a file and try to create a Zip64EndOfCentralDirectoryRecord.
+    /// The record must end before end_offset, the offset of the zip64 end of
+    /// central directory locator if it's known, or else before the end of the
+    /// file.
+    pub fn read<R: Read + Seek>(file: &mut R, end_offset: Option<u64>) -> Result<Zip64EndOfCentralDirectoryRecord, ParseError> {
+        let end_offset = match end_offset {
+            Some(end_offset) => Some(end_offset),
+            None => file_size(file, Structure::Zip64EndOfCentralDirectoryRecord)?,
+        };
+        let mut reader = FieldReader::new(file, Structure::Zip64EndOfCentralDirectoryRecord)?;
+        let size_of_record_offset = reader.offset;
+        let size_of_record = reader.u64("size of record")?;
+        let version_made_by = reader.u16("version made by")?;
+        let minimum_version = reader.u16("minimum version")?;
+        let number_of_this_disk = reader.u32("disk number")?;
+        let disk_where_central_directory_starts = reader.u32("disk where central directory starts")?;
+        let number_of_central_directory_records_on_this_disk = reader.u64("number of central directory records on this disk")?;
+        let total_number_of_central_directory_records = reader.u64("total number of central directory records")?;
+        let size_of_central_directory = reader.u64("size of central directory")?;
+        let offset_start_of_central_directory_from_archive = reader.u64("offset of start of central directory from archive")?;
+
+        // The size of the record doesn't include the leading 12 bytes (signature
+        // and size of record). The fixed fields use 44 bytes, the rest is the
+        // extensible data sector.
+        let extensible_data_sector_size = size_of_record.checked_sub(44)
+            .ok_or(ParseError::invalid_value(Structure::Zip64EndOfCentralDirectoryRecord, "size of record", format!("{} is too small", size_of_record)))?;
+        let extensible_data_sector_offset = reader.offset;
+        if let Some(end_offset) = end_offset {
+            if extensible_data_sector_size > end_offset.saturating_sub(extensible_data_sector_offset) {
+                return Err(ParseError::invalid_value(Structure::Zip64EndOfCentralDirectoryRecord, "size of record", format!("{} goes beyond offset {}", size_of_record, end_offset))
+                    .at(size_of_record_offset));
+            }
+        }
+        let extensible_data_sector = reader.bytes(extensible_data_sector_size as usize, "extensible data sector")?;
+
+        // Version 2 of the record, introduced in version 6.2 of the specification,
+        // stores the compression and encryption of the central directory at the
+        // start of the extensible data sector
+        let mut central_directory_encryption = None;
+        if minimum_version >= 62 && extensible_data_sector.len() >= 28 {
+            let data = &extensible_data_sector;
+            let hash_length = read_u16_le(&data[26..28]).unwrap() as usize;
+            let hash_data = data.get(28..28 + hash_length)
+                .ok_or(ParseError::truncated(Structure::Zip64EndOfCentralDirectoryRecord, "hash data").at(extensible_data_sector_offset + 28))?;
+            central_directory_encryption = Some(CentralDirectoryEncryption {
+                compression_method: read_u16_le(&data[0..2]).unwrap(),
+                compressed_size: read_u64_le(&data[2..10]).unwrap(),
+                uncompressed_size: read_u64_le(&data[10..18]).unwrap(),
+                algorithm_id: read_u16_le(&data[18..20]).unwrap(),
+                bit_length: read_u16_le(&data[20..22]).unwrap(),
+                flags: read_u16_le(&data[22..24]).unwrap(),
+                hash_id: read_u16_le(&data[24..26]).unwrap(),
+                hash_data: hash_data.to_vec(),
+            });
+        }
+
+        Ok(Zip64EndOfCentralDirectoryRecord {
+            size_of_record,
+            version_made_by,
+            minimum_version,
             disk_number: number_of_this_disk,
             disk_start_central_directory: disk_where_central_directory_starts,
             central_directory_records_number_on_disk: number_of_central_directory_records_on_this_disk,
             central_directory_records_total_number: total_number_of_central_directory_records,
             central_directory_size: size_of_central_directory,
             offset_start_central_directory: offset_start_of_central_directory_from_archive,
-            comment: comment,
+            extensible_data_sector,
+            central_directory_encryption,
+        })
+    }
+}
+
+/// Represents a reader for Zip64EndOfCentralDirectoryLocator
+pub struct Zip64EndOfCentralDirectoryLocatorReader {
+
+}
+
+impl Zip64EndOfCentralDirectoryLocatorReader {
+    /// Read a file and try to create a Zip64EndOfCentralDirectoryLocator
+    pub fn read<R: Read + Seek>(file: &mut R) -> Result<Zip64EndOfCentralDirectoryLocator, ParseError> {
+        let mut reader = FieldReader::new(file, Structure::Zip64EndOfCentralDirectoryLocator)?;
+        let disk_start_zip64_end_of_central_directory = reader.u32("disk where zip64 end of central directory starts")?;
+        let offset_zip64_end_of_central_directory_record = reader.u64("offset of zip64 end of central directory")?;
+        let total_number_of_disks = reader.u32("total number of disks")?;
+
+        Ok(Zip64EndOfCentralDirectoryLocator {
+            disk_start_zip64_end_of_central_directory,
+            offset_zip64_end_of_central_directory_record,
+            total_number_of_disks,
         })
     }
 }
@@ -318,80 +1227,638 @@ pub struct CentralDirectoryReader {
 }
 
 impl CentralDirectoryReader {
-    pub fn read(file: &mut File) -> Result<CentralDirectory, String> {
+    /// Read a file and try to create a CentralDirectory.
+    /// The central directory is read sequentially from the current position
+    /// of the file, up to the end of central directory record.
+    /// The central directory file headers which can't be read are skipped and
+    /// added to diagnostics.
+    pub fn read<R: Read + Seek>(file: &mut R, diagnostics: &mut Vec<Diagnostic>) -> Result<CentralDirectory, ParseError> {
+        Self::read_on_disks(file, &[], diagnostics)
+    }
+
+    /// Read a file and try to create a CentralDirectory, as read(), for an
+    /// archive made of several disks.
+    /// disk_offsets gives the offset of the start of each disk in the file,
+    /// see SplitArchive::disk_offsets(). It's empty for a single file.
+    pub fn read_on_disks<R: Read + Seek>(file: &mut R, disk_offsets: &[u64], diagnostics: &mut Vec<Diagnostic>) -> Result<CentralDirectory, ParseError> {
         let mut central_directory_file_headers: Vec<CentralDirectoryFileHeader> = Vec::new();
-        let offset_from_start_of_archive = file.stream_position()
-            .or(Err("Unable to read the current position in the archive".to_string()))?;
+        let offset_from_start_of_archive = current_position(file, Structure::CentralDirectory)?;
 
-        while compare_signature(file, constants::SIGNATURE_HEADER_CENTRAL_DIRECTORY)? {
-            // TODO: Better handle errors.
-            // Actually if one header is unreadable, the whole CentralDirectory will be
-            // ignored. It would be better to log the error and ignore only this header
-            let mut header = CentralDirectoryFileHeaderReader::read(file)?;
-            header.position = Some(central_directory_file_headers.len());
-            central_directory_file_headers.push(header);
+        loop {
+            let header_offset = current_position(file, Structure::CentralDirectoryFileHeader)?;
+            if !compare_signature(file, constants::SIGNATURE_HEADER_CENTRAL_DIRECTORY)? {
+                break;
+            }
+            match CentralDirectoryFileHeaderReader::read(file) {
+                Ok(mut header) => {
+                    report_malformed_extra_field(&header.extra_field,
+                        header_offset + constants::CENTRAL_DIRECTORY_FILE_HEADER_SIZE + header.filename_raw.len() as u64,
+                        &format!("the central directory file header of {}", header.filename), diagnostics);
+                    header.position = Some(central_directory_file_headers.len());
+                    central_directory_file_headers.push(header);
+                },
+                Err(e) => {
+                    diagnostics.push(Diagnostic::new(Severity::Error, DiagnosticCode::UnreadableCentralDirectoryFileHeader, Some(header_offset), e.to_string()));
+                    // The length of the header is unknown, search the next
+                    // part of the central directory after its signature
+                    move_to(file, header_offset + 1, Structure::CentralDirectory)?;
+                    if !find_next_central_directory_part(file)? {
+                        break;
+                    }
+                },
+            }
         }
 
-        // TODO: as in ZipFileReader, loop over the file byte by byte to
-        // find the next signature.
-        // by doing so, it would be possible to ignore a damaged central directory header
-        // while still being able to read the next one / next part of the file.
-
         // Check if digital signature is present
         let mut digital_signature = None;
         if compare_signature(file, constants::SIGNATURE_CENTRAL_DIRECTORY_DIGITAL_SIGNATURE)
-                .or::<String>(Ok(false))
-                .unwrap()
+                .unwrap_or(false)
         {
             digital_signature = Some(DigitalSignatureReader::read(file)?);
         }
 
+        // Read the zip64 end of central directory record and locator, if present
+        let mut zip64_end_of_central_directory_record = None;
+        let zip64_end_of_central_directory_offset = current_position(file, Structure::Zip64EndOfCentralDirectoryRecord)?;
+        if compare_signature(file, constants::SIGNATURE_ZIP64_END_OF_CENTRAL_DIRECTORY_RECORD)
+                .unwrap_or(false) {
+            zip64_end_of_central_directory_record = Some(Zip64EndOfCentralDirectoryRecordReader::read(file, None)?);
+        }
+        let mut zip64_end_of_central_directory_locator = None;
+        if compare_signature(file, constants::SIGNATURE_ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR)
+                .unwrap_or(false) {
+            zip64_end_of_central_directory_locator = Some(Zip64EndOfCentralDirectoryLocatorReader::read(file)?);
+        }
+
         // Read end of central directory record
         let mut end_of_central_directory_record = None;
+        let end_of_central_directory_offset = current_position(file, Structure::EndOfCentralDirectoryRecord)?;
         if compare_signature(file, constants::SIGNATURE_END_OF_CENTRAL_DIRECTORY_RECORD)
-                .or::<String>(Ok(false))
-                .unwrap() {
+                .unwrap_or(false) {
             end_of_central_directory_record = Some(EndOfCentralDirectoryRecordReader::read(file)?);
         }
 
-        if end_of_central_directory_record.is_none() {
-            return Err("Unable to read central directory: end of central directory not found".to_string());
+        let mut end_of_central_directory_record = end_of_central_directory_record
+            .ok_or(ParseError::new(ParseErrorKind::NotFound, Structure::EndOfCentralDirectoryRecord).at(end_of_central_directory_offset))?;
+
+        // The values too big for the end of central directory record are
+        // stored in the zip64 end of central directory record
+        if let Some(zip64) = &zip64_end_of_central_directory_record {
+            update_from_zip64_end_of_central_directory_record(&mut end_of_central_directory_record, zip64);
         }
 
+        // Compare the offset of the central directory stored in the archive with
+        // the real one, to find data prepended to the archive
+        let end_offset = current_position(file, Structure::CentralDirectory)?;
+        let stored_offset = resolve_central_directory_offset(disk_offsets, &end_of_central_directory_record)?;
+        let end_of_central_directory = match zip64_end_of_central_directory_record {
+            Some(_) => zip64_end_of_central_directory_offset,
+            None => end_of_central_directory_offset,
+        };
+        let expected_offset = end_of_central_directory.saturating_sub(end_of_central_directory_record.central_directory_size);
+        let base_offset = find_base_offset(file, stored_offset, expected_offset)?;
+        move_to(file, end_offset, Structure::CentralDirectory)?;
+        resolve_local_file_header_offsets(&mut central_directory_file_headers, disk_offsets, base_offset);
+
         Ok(CentralDirectory {
             file_headers: central_directory_file_headers,
             digital_signature,
-            end_of_central_directory_record: end_of_central_directory_record.unwrap(),
+            zip64_end_of_central_directory_record,
+            zip64_end_of_central_directory_locator,
+            end_of_central_directory_record,
             offset_from_start_of_archive: offset_from_start_of_archive as usize,
+            base_offset,
         })
     }
 }
 
+impl CentralDirectoryReader {
+    /// Read a file and try to create a CentralDirectory, the way unzip tools do.
+    /// The end of central directory record is searched backwards from the end
+    /// of the file, then the central directory file headers are read at the
+    /// offset given by the (zip64) end of central directory record.
+    /// The reading of the central directory stops at the first central
+    /// directory file header which can't be read, it's added to diagnostics.
+    pub fn read_from_end<R: Read + Seek>(file: &mut R, diagnostics: &mut Vec<Diagnostic>) -> Result<CentralDirectory, ParseError> {
+        Self::read_from_end_on_disks(file, &[], diagnostics)
+    }
+
+    /// Read a file and try to create a CentralDirectory, as read_from_end(),
+    /// for an archive made of several disks.
+    /// disk_offsets gives the offset of the start of each disk in the file,
+    /// see SplitArchive::disk_offsets(). It's empty for a single file.
+    pub fn read_from_end_on_disks<R: Read + Seek>(file: &mut R, disk_offsets: &[u64], diagnostics: &mut Vec<Diagnostic>) -> Result<CentralDirectory, ParseError> {
+        let end_of_central_directory_offset = find_end_of_central_directory_record(file)?;
+        move_to(file, end_of_central_directory_offset + 4, Structure::EndOfCentralDirectoryRecord)?;
+        let mut end_of_central_directory_record = EndOfCentralDirectoryRecordReader::read(file)?;
+
+        // The zip64 end of central directory locator, if any, is right before
+        // the end of central directory record. It gives the offset of the zip64
+        // end of central directory record.
+        let mut zip64_end_of_central_directory_locator = None;
+        let mut zip64_end_of_central_directory_record = None;
+        let mut zip64_end_of_central_directory_offset = None;
+        if end_of_central_directory_offset >= 20 {
+            let locator_offset = end_of_central_directory_offset - 20;
+            move_to(file, locator_offset, Structure::Zip64EndOfCentralDirectoryLocator)?;
+            if compare_signature(file, constants::SIGNATURE_ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR)? {
+                let locator = Zip64EndOfCentralDirectoryLocatorReader::read(file)?;
+                let stored_offset = resolve_disk_offset(disk_offsets, locator.disk_start_zip64_end_of_central_directory, locator.offset_zip64_end_of_central_directory_record);
+                // If data was prepended to the archive, the record is not at the
+                // offset stored. It's usually right before the locator.
+                let expected_offset = locator_offset.checked_sub(constants::ZIP64_END_OF_CENTRAL_DIRECTORY_RECORD_SIZE);
+                for zip64_offset in [stored_offset, expected_offset].into_iter().flatten() {
+                    move_to(file, zip64_offset, Structure::Zip64EndOfCentralDirectoryRecord)?;
+                    if compare_signature(file, constants::SIGNATURE_ZIP64_END_OF_CENTRAL_DIRECTORY_RECORD).unwrap_or(false) {
+                        zip64_end_of_central_directory_record = Some(Zip64EndOfCentralDirectoryRecordReader::read(file, Some(locator_offset))?);
+                        zip64_end_of_central_directory_offset = Some(zip64_offset);
+                        break;
+                    }
+                }
+                zip64_end_of_central_directory_locator = Some(locator);
+            }
+        }
+
+        if let Some(zip64) = &zip64_end_of_central_directory_record {
+            update_from_zip64_end_of_central_directory_record(&mut end_of_central_directory_record, zip64);
+        }
+
+        // The central directory is right before the (zip64) end of central
+        // directory record. If it's not at the offset stored, data was
+        // prepended to the archive.
+        let stored_offset = resolve_central_directory_offset(disk_offsets, &end_of_central_directory_record)?;
+        let end_of_central_directory = zip64_end_of_central_directory_offset.unwrap_or(end_of_central_directory_offset);
+        let expected_offset = end_of_central_directory.saturating_sub(end_of_central_directory_record.central_directory_size);
+        let base_offset = find_base_offset(file, stored_offset, expected_offset)?;
+        let offset_from_start_of_archive = stored_offset.saturating_add(base_offset);
+        move_to(file, offset_from_start_of_archive, Structure::CentralDirectory)?;
+
+        let mut central_directory_file_headers: Vec<CentralDirectoryFileHeader> = Vec::new();
+        loop {
+            let header_offset = current_position(file, Structure::CentralDirectoryFileHeader)?;
+            if !compare_signature(file, constants::SIGNATURE_HEADER_CENTRAL_DIRECTORY)? {
+                break;
+            }
+            match CentralDirectoryFileHeaderReader::read(file) {
+                Ok(mut header) => {
+                    report_malformed_extra_field(&header.extra_field,
+                        header_offset + constants::CENTRAL_DIRECTORY_FILE_HEADER_SIZE + header.filename_raw.len() as u64,
+                        &format!("the central directory file header of {}", header.filename), diagnostics);
+                    header.position = Some(central_directory_file_headers.len());
+                    central_directory_file_headers.push(header);
+                },
+                // Unzip tools stop there, the headers already read are kept
+                Err(e) => {
+                    diagnostics.push(Diagnostic::new(Severity::Error, DiagnosticCode::UnreadableCentralDirectoryFileHeader, Some(header_offset), e.to_string()));
+                    break;
+                },
+            }
+        }
+        resolve_local_file_header_offsets(&mut central_directory_file_headers, disk_offsets, base_offset);
+
+        let mut digital_signature = None;
+        if compare_signature(file, constants::SIGNATURE_CENTRAL_DIRECTORY_DIGITAL_SIGNATURE)
+                .unwrap_or(false)
+        {
+            digital_signature = Some(DigitalSignatureReader::read(file)?);
+        }
+
+        Ok(CentralDirectory {
+            file_headers: central_directory_file_headers,
+            digital_signature,
+            zip64_end_of_central_directory_record,
+            zip64_end_of_central_directory_locator,
+            end_of_central_directory_record,
+            offset_from_start_of_archive: offset_from_start_of_archive as usize,
+            base_offset,
+        })
+    }
+}
+
+/// Convert an offset relative to a disk into an offset from the start of the
+/// file, using the offsets of the disks. The offset is unchanged if the disk
+/// is unknown, as in archives made of a single file.
+/// Returns None if the offset overflows.
+fn resolve_disk_offset(disk_offsets: &[u64], disk: u32, offset: u64) -> Option<u64> {
+    match disk_offsets.get(disk as usize) {
+        Some(disk_offset) => disk_offset.checked_add(offset),
+        None => Some(offset),
+    }
+}
+
+/// Convert the offset of the central directory stored in the (zip64) end of
+/// central directory record into an offset from the start of the file.
+fn resolve_central_directory_offset(disk_offsets: &[u64], record: &EndOfCentralDirectoryRecord) -> Result<u64, ParseError> {
+    resolve_disk_offset(disk_offsets, record.disk_start_central_directory, record.offset_start_central_directory)
+        .ok_or(ParseError::invalid_value(
+            Structure::EndOfCentralDirectoryRecord,
+            "offset of start of central directory",
+            format!("{} overflows on disk {}", record.offset_start_central_directory, record.disk_start_central_directory),
+        ))
+}
+
+/// Set the offset in archive of each central directory file header, from the
+/// disk on which its file starts and the number of bytes prepended to the archive.
+/// The offset is None if it overflows.
+fn resolve_local_file_header_offsets(headers: &mut [CentralDirectoryFileHeader], disk_offsets: &[u64], base_offset: u64) {
+    for header in headers {
+        header.offset_in_archive = resolve_disk_offset(disk_offsets, header.disk_start, header.local_file_header_offset)
+            .and_then(|offset| offset.checked_add(base_offset));
+    }
+}
+
+/// Find the number of bytes prepended to an archive without updating its
+/// offsets, from the offset of the central directory stored in the archive
+/// and the offset where it's expected, right before the (zip64) end of central
+/// directory record.
+/// It's 0 if a central directory file header is at the offset stored.
+/// A stream may not go back to the offset stored, the archive is considered
+/// prepended then.
+fn find_base_offset<R: Read + Seek>(file: &mut R, stored_offset: u64, expected_offset: u64) -> Result<u64, ParseError> {
+    if expected_offset <= stored_offset {
+        return Ok(0);
+    }
+    if move_to(file, stored_offset, Structure::CentralDirectory).is_ok()
+            && compare_signature(file, constants::SIGNATURE_HEADER_CENTRAL_DIRECTORY).unwrap_or(false) {
+        return Ok(0);
+    }
+    Ok(expected_offset - stored_offset)
+}
+
+/// Move the cursor to the next part of the central directory, by searching the
+/// signature of a central directory file header, of the digital signature or
+/// of the (zip64) end of central directory record from the current position.
+/// Returns false if none is found before the end of the file.
+fn find_next_central_directory_part<R: Read + Seek>(file: &mut R) -> Result<bool, ParseError> {
+    let signatures = [
+        constants::SIGNATURE_HEADER_CENTRAL_DIRECTORY,
+        constants::SIGNATURE_CENTRAL_DIRECTORY_DIGITAL_SIGNATURE,
+        constants::SIGNATURE_ZIP64_END_OF_CENTRAL_DIRECTORY_RECORD,
+        constants::SIGNATURE_END_OF_CENTRAL_DIRECTORY_RECORD,
+    ].map(u32::to_le_bytes);
+    let mut search_start = current_position(file, Structure::CentralDirectory)?;
+    loop {
+        let chunk = try_read_chunk(file, SEARCH_CHUNK_SIZE)
+            .map_err(|e| ParseError::io(e, Structure::CentralDirectory).at(search_start))?;
+        if let Some(position) = chunk.windows(4).position(|window| signatures.iter().any(|signature| window == signature)) {
+            move_to(file, search_start + position as u64, Structure::CentralDirectory)?;
+            return Ok(true);
+        }
+        if chunk.len() < SEARCH_CHUNK_SIZE {
+            return Ok(false);
+        }
+        // A signature may start in the last 3 bytes of the chunk
+        search_start += (chunk.len() - 3) as u64;
+        move_to(file, search_start, Structure::CentralDirectory)?;
+    }
+}
+
+/// Find the offset of the end of central directory record, by searching its
+/// signature backwards from the end of the file.
+/// The record is 22 bytes long, followed by a comment of up to 65535 bytes.
+/// A signature whose comment length matches the remaining bytes of the file
+/// is preferred. Otherwise, the last signature found is used, in case some
+/// data was appended to the archive.
+pub fn find_end_of_central_directory_record<R: Read + Seek>(file: &mut R) -> Result<u64, ParseError> {
+    let not_found = ParseError::new(ParseErrorKind::NotFound, Structure::EndOfCentralDirectoryRecord);
+    let end_of_file = file.seek(SeekFrom::End(0))
+        .map_err(|e| ParseError::io(e, Structure::Archive))?;
+    if end_of_file < 22 {
+        return Err(not_found);
+    }
+
+    let search_size = end_of_file.min(22 + u16::MAX as u64);
+    let search_start = end_of_file - search_size;
+    move_to(file, search_start, Structure::EndOfCentralDirectoryRecord)?;
+    let chunk = try_read_chunk(file, search_size as usize)
+        .map_err(|e| ParseError::io(e, Structure::EndOfCentralDirectoryRecord).at(search_start))?;
+    if chunk.len() < 22 {
+        return Err(not_found);
+    }
+
+    let signature = constants::SIGNATURE_END_OF_CENTRAL_DIRECTORY_RECORD.to_le_bytes();
+    let mut last_found = None;
+    for position in (0..=chunk.len() - 22).rev() {
+        if chunk[position..position + 4] != signature {
+            continue;
+        }
+        let comment_length = read_u16_le(&chunk[position + 20..position + 22]).unwrap() as usize;
+        if position + 22 + comment_length == chunk.len() {
+            return Ok(search_start + position as u64);
+        }
+        if last_found.is_none() {
+            last_found = Some(search_start + position as u64);
+        }
+    }
+
+    last_found.ok_or(not_found)
+}
+
+/// Replace the values of the end of central directory record set to their
+/// maximum value by the ones of the zip64 end of central directory record.
+fn update_from_zip64_end_of_central_directory_record(record: &mut EndOfCentralDirectoryRecord, zip64: &Zip64EndOfCentralDirectoryRecord) {
+    let u16_placeholder = constants::ZIP64_PLACEHOLDER_U16 as u64;
+    let u32_placeholder = constants::ZIP64_PLACEHOLDER_U32 as u64;
+
+    if record.disk_number as u64 == u16_placeholder {
+        record.disk_number = zip64.disk_number;
+    }
+    if record.disk_start_central_directory as u64 == u16_placeholder {
+        record.disk_start_central_directory = zip64.disk_start_central_directory;
+    }
+    if record.central_directory_records_number_on_disk == u16_placeholder {
+        record.central_directory_records_number_on_disk = zip64.central_directory_records_number_on_disk;
+    }
+    if record.central_directory_records_total_number == u16_placeholder {
+        record.central_directory_records_total_number = zip64.central_directory_records_total_number;
+    }
+    if record.central_directory_size == u32_placeholder {
+        record.central_directory_size = zip64.central_directory_size;
+    }
+    if record.offset_start_central_directory == u32_placeholder {
+        record.offset_start_central_directory = zip64.offset_start_central_directory;
+    }
+}
+
+/// Create the diagnostic for the bytes from start to end, which are not part
+/// of any known structure
+fn skipped_bytes_diagnostic(start: u64, end: u64) -> Diagnostic {
+    Diagnostic::new(Severity::Warning, DiagnosticCode::SkippedBytes, Some(start),
+        format!("{} bytes skipped, no known structure found in them", end - start))
+}
+
+/// The strategies available to parse a ZIP file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParsingStrategy {
+    /// Read the file from start to end, and search the next known signature
+    /// when a part is unreadable. Finds the files hidden from the central
+    /// directory.
+    Sequential,
+    /// Locate the end of central directory record from the end of the file,
+    /// read the central directory, then read each local file header at the
+    /// offset given by the central directory. This is how unzip tools work.
+    CentralDirectoryFirst,
+}
+
 /// Represents a reader for ZipFile
 pub struct ZipFileReader {
 
 }
 
 impl ZipFileReader {
-    /// Read a file and try to create a ZipFile
-    pub fn read(file: &mut File) -> Result<ZipFile, String> {
+    /// Read a file and try to create a ZipFile, with the given strategy
+    pub fn read_with_strategy<R: Read + Seek>(file: &mut R, strategy: ParsingStrategy) -> Result<ZipFile, ParseError> {
+        match strategy {
+            ParsingStrategy::Sequential => Self::read(file),
+            ParsingStrategy::CentralDirectoryFirst => Self::read_from_central_directory(file),
+        }
+    }
+
+    /// Read an archive already in memory, like a downloaded archive or a file
+    /// stored in another archive, and try to create a ZipFile with the given
+    /// strategy
+    pub fn read_bytes(data: &[u8], strategy: ParsingStrategy) -> Result<ZipFile, ParseError> {
+        Self::read_with_strategy(&mut Cursor::new(data), strategy)
+    }
+
+    /// Read a file and try to create a ZipFile, starting from the central
+    /// directory.
+    /// Only the files announced in the central directory are read. Their
+    /// position is their order of appearance in the archive.
+    pub fn read_from_central_directory<R: Read + Seek>(file: &mut R) -> Result<ZipFile, ParseError> {
+        Self::read_from_central_directory_on_disks(file, &[])
+    }
+
+    /// Read the volumes of a split archive and try to create a ZipFile, with
+    /// the given strategy.
+    /// The volumes and the problems found in them are reported in the ZipFile.
+    pub fn read_split(archive: &mut SplitArchive, strategy: ParsingStrategy) -> Result<ZipFile, ParseError> {
+        let disk_offsets = archive.disk_offsets();
+        let result = match strategy {
+            ParsingStrategy::Sequential => Self::read_on_disks(archive, &disk_offsets),
+            ParsingStrategy::CentralDirectoryFirst => Self::read_from_central_directory_on_disks(archive, &disk_offsets),
+        };
+
+        // The missing volumes are the likely cause of an error, report them instead
+        let mut zip_file = result.map_err(|e| {
+            let missing: Vec<PathBuf> = archive.issues().iter()
+                .filter_map(|issue| match issue {
+                    VolumeIssue::Missing { path, .. } => Some(path.clone()),
+                    _ => None,
+                })
+                .collect();
+            if missing.is_empty() {
+                e
+            } else {
+                ParseError {
+                    kind: ParseErrorKind::MissingVolumes(missing),
+                    structure: Structure::Volume,
+                    field: None,
+                    offset: e.offset,
+                }
+            }
+        })?;
+
+        if let Some(central_directory) = &zip_file.central_directory {
+            archive.check_volume_order(central_directory);
+        }
+        zip_file.volumes = archive.volumes().to_vec();
+        zip_file.volume_issues = archive.issues().to_vec();
+        Ok(zip_file)
+    }
+
+    /// Read a file made of several disks and try to create a ZipFile, as
+    /// read_from_central_directory()
+    fn read_from_central_directory_on_disks<R: Read + Seek>(file: &mut R, disk_offsets: &[u64]) -> Result<ZipFile, ParseError> {
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        let central_directory = CentralDirectoryReader::read_from_end_on_disks(file, disk_offsets, &mut diagnostics)?;
+
+        let mut offsets: Vec<u64> = central_directory.file_headers.iter()
+            .filter_map(|header| header.offset_in_archive)
+            .collect();
+        offsets.sort_unstable();
+        offsets.dedup();
+
         let mut stored_files: Vec<StoredFile> = Vec::new();
+        for header in &central_directory.file_headers {
+            let Some(offset_in_archive) = header.offset_in_archive else {
+                diagnostics.push(Diagnostic::new(Severity::Error, DiagnosticCode::MissingLocalFileHeader, None,
+                    format!("No local file header for {}, the offset given by the central directory overflows", header.filename)));
+                continue;
+            };
+            let found = move_to(file, offset_in_archive, Structure::LocalFileHeader).is_ok()
+                && compare_signature(file, constants::SIGNATURE_HEADER_LOCAL_FILE).unwrap_or(false);
+            if !found {
+                diagnostics.push(Diagnostic::new(Severity::Error, DiagnosticCode::MissingLocalFileHeader, Some(offset_in_archive),
+                    format!("No local file header for {} at the offset given by the central directory", header.filename)));
+                continue;
+            }
+            let position = offsets.binary_search(&offset_in_archive).unwrap();
+            match StoredFileReader::read(file, position, Some(&central_directory), DataHandling::Skip, &mut diagnostics) {
+                Ok(mut stored_file) => {
+                    stored_file.update_from_central_directory(&central_directory);
+                    stored_files.push(stored_file);
+                },
+                Err(e) => diagnostics.push(Diagnostic::new(Severity::Error, DiagnosticCode::UnreadableLocalFileHeader, Some(offset_in_archive), e.to_string())),
+            }
+        }
+        stored_files.sort_by_key(|stored_file| stored_file.position);
+
+        let archive_decryption_header = Self::read_archive_decryption_header(file, &central_directory, &stored_files, disk_offsets)
+            .unwrap_or_else(|e| {
+                diagnostics.push(Diagnostic::new(Severity::Error, DiagnosticCode::UnreadableArchiveDecryptionHeader, e.offset, e.to_string()));
+                None
+            });
+        let prefix = Self::read_prefix(file, &stored_files, Some(&central_directory))?;
+
+        Ok(ZipFile {
+            stored_files,
+            // The archive extra data record has no known position, it would
+            // have to be searched before the central directory
+            archive_extra_data_record: None,
+            archive_decryption_header,
+            central_directory: Some(central_directory),
+            prefix,
+            volumes: Vec::new(),
+            volume_issues: Vec::new(),
+            diagnostics,
+        })
+    }
+
+    /// Find the data stored before the first part of the archive, like the
+    /// executable stub of a self-extracting archive.
+    /// The first part is the first local file header announced by the central
+    /// directory, else the first stored file found, else the central directory.
+    fn read_prefix<R: Read + Seek>(file: &mut R, stored_files: &[StoredFile], central_directory: Option<&CentralDirectory>) -> Result<Option<Prefix>, ParseError> {
+        let size = central_directory
+            .and_then(|central_directory| central_directory.file_headers.iter()
+                .filter_map(|header| header.offset_in_archive)
+                .min())
+            .or(stored_files.iter().map(|stored_file| stored_file.offset_in_archive as u64).min())
+            .or(central_directory.map(|central_directory| central_directory.offset_from_start_of_archive as u64))
+            .unwrap_or(0);
+        if size == 0 {
+            return Ok(None);
+        }
+
+        move_to(file, 0, Structure::Archive)?;
+        let magic = read_chunk(file, size.min(4) as usize);
+        // An archive starting with a local file header has no prefix, even if
+        // its first part is unknown, as when the central directory is encrypted.
+        // The first volume of a split archive starts with a signature, it's not a prefix either.
+        let signature = read_u32_le(&magic).ok().filter(|_| magic.len() == 4);
+        if signature == Some(constants::SIGNATURE_HEADER_LOCAL_FILE)
+            || (size == 4 && signature == Some(constants::SIGNATURE_SPLIT_ARCHIVE)) {
+            return Ok(None);
+        }
+
+        Ok(Some(Prefix {
+            size,
+            stub_type: StubType::from_magic(&magic),
+        }))
+    }
+
+    /// Read the archive decryption header, if the central directory is encrypted.
+    /// The central directory is considered encrypted when the zip64 end of central
+    /// directory record describes its encryption, or when a local file header is
+    /// masked, and no central directory file header could be read.
+    fn read_archive_decryption_header<R: Read + Seek>(file: &mut R, central_directory: &CentralDirectory, stored_files: &[StoredFile], disk_offsets: &[u64]) -> Result<Option<ArchiveDecryptionHeader>, ParseError> {
+        let zip64_record = central_directory.zip64_end_of_central_directory_record.as_ref();
+        let is_encrypted = zip64_record.is_some_and(|record| record.central_directory_encryption.is_some())
+            || stored_files.iter().any(|stored_file| stored_file.local_file_header.is_masked());
+        if !is_encrypted || !central_directory.file_headers.is_empty() {
+            return Ok(None);
+        }
+
+        // The archive decryption header is at the start of the central directory,
+        // whose size includes it
+        let record = &central_directory.end_of_central_directory_record;
+        let start_offset = resolve_central_directory_offset(disk_offsets, record)?
+            .checked_add(central_directory.base_offset)
+            .ok_or(ParseError::invalid_value(Structure::CentralDirectory, "offset", format!("{} bytes prepended overflow the offset", central_directory.base_offset)))?;
+        let end_offset = start_offset.checked_add(record.central_directory_size)
+            .ok_or(ParseError::invalid_value(Structure::EndOfCentralDirectoryRecord, "central directory size", format!("{} overflows from offset {}", record.central_directory_size, start_offset)))?;
+        move_to(file, start_offset, Structure::ArchiveDecryptionHeader)?;
+
+        Ok(Some(ArchiveDecryptionHeaderReader::read(file, end_offset)?))
+    }
+
+    /// Read a file and try to create a ZipFile, reading it sequentially
+    pub fn read<R: Read + Seek>(file: &mut R) -> Result<ZipFile, ParseError> {
+        Self::read_on_disks(file, &[])
+    }
+
+    /// Read a file made of several disks and try to create a ZipFile, as read()
+    fn read_on_disks<R: Read + Seek>(file: &mut R, disk_offsets: &[u64]) -> Result<ZipFile, ParseError> {
+        // If possible, locate the central directory first. It's not used to find
+        // the files, but it gives the sizes of the files whose sizes are only
+        // in their data descriptor.
+        let start_offset = current_position(file, Structure::Archive)?;
+        let mut diagnostics = Vec::new();
+        // The central directory file headers which can't be read are reported
+        // when the central directory is read again after the files
+        let known_central_directory = match CentralDirectoryReader::read_from_end_on_disks(file, disk_offsets, &mut Vec::new()) {
+            Ok(central_directory) => Some(central_directory),
+            // Without end of central directory record, the missing central
+            // directory is reported once the archive is read
+            Err(e) if matches!(e.kind, ParseErrorKind::NotFound) => None,
+            Err(e) => {
+                diagnostics.push(Diagnostic::new(Severity::Warning, DiagnosticCode::UnreadableCentralDirectory, e.offset,
+                    format!("The central directory can't be read from the end of the archive, the files are read without it: {}", e)));
+                None
+            },
+        };
+        move_to(file, start_offset, Structure::Archive)?;
+
+        Self::read_forward(file, disk_offsets, known_central_directory.as_ref(), DataHandling::Skip, diagnostics)
+    }
+
+    /// Read an archive from a non-seekable stream, like the standard input,
+    /// and try to create a ZipFile.
+    /// The parts are read as they arrive, without locating the central
+    /// directory first: the sizes of the files which are only in their data
+    /// descriptor are found from the data. The data stored before the first
+    /// part of the archive is reported only if it's still in the look-back
+    /// buffer, see LookBackReader. The stream can't be read again: the file
+    /// data is kept in memory if keep_data is set, otherwise the content of
+    /// the files is summarized as it's read, see StoredFile::content_summary.
+    pub fn read_stream<R: Read>(input: R, keep_data: bool) -> Result<ZipFile, ParseError> {
+        let data_handling = if keep_data { DataHandling::Keep } else { DataHandling::Summarize };
+        Self::read_forward(&mut LookBackReader::new(input), &[], None, data_handling, Vec::new())
+    }
+
+    /// Read a file from its current position to its end and try to create a
+    /// ZipFile, as read().
+    /// Unless the data is skipped, the cursor never moves back further than
+    /// the bytes read ahead, so that a stream can be read.
+    /// The diagnostics already found are kept in the ZipFile.
+    fn read_forward<R: Read + Seek>(file: &mut R, disk_offsets: &[u64], known_central_directory: Option<&CentralDirectory>, data_handling: DataHandling, mut diagnostics: Vec<Diagnostic>) -> Result<ZipFile, ParseError> {
+        let mut stored_files: Vec<StoredFile> = Vec::new();
+        // The first volume of a split archive starts with a signature, which
+        // isn't part of any structure
+        if current_position(file, Structure::Archive)? == 0 {
+            compare_signature(file, constants::SIGNATURE_SPLIT_ARCHIVE)?;
+        }
         // Read the stored files
         while compare_signature(file, constants::SIGNATURE_HEADER_LOCAL_FILE)
-                .or::<String>(Ok(false)).unwrap()
+                .unwrap_or(false)
         {
-            let current_offset = file.stream_position()
-                .or(Err("Unable to read current position in archive"))?;
-            let stored_file = StoredFileReader::read(file, stored_files.len());
+            let current_offset = current_position(file, Structure::LocalFileHeader)?;
+            let stored_file = StoredFileReader::read(file, stored_files.len(), known_central_directory, data_handling, &mut diagnostics);
 
-            if stored_file.is_ok() {
-                stored_files.push(stored_file.unwrap());
-            } else {
-                // If the stored file cannot be read, reset the file cursor
-                // and continue reading manually
-                let new_current_offset = file.stream_position()
-                    .or(Err("Unable to read current position in archive"))?;
-                rewind_file_cursor(file, new_current_offset - current_offset)?
+            match stored_file {
+                Ok(stored_file) => stored_files.push(stored_file),
+                Err(e) => {
+                    diagnostics.push(Diagnostic::new(Severity::Error, DiagnosticCode::UnreadableLocalFileHeader, Some(current_offset - 4), e.to_string()));
+                    // If the stored file cannot be read, reset the file cursor
+                    // and continue reading manually. A stream may not go back that
+                    // far, the search continues from the current position then.
+                    let new_current_offset = current_position(file, Structure::LocalFileHeader)?;
+                    if let Err(e) = rewind_file_cursor(file, new_current_offset - current_offset) {
+                        diagnostics.push(Diagnostic::new(Severity::Warning, DiagnosticCode::SkippedBytes, Some(current_offset),
+                            format!("The {} bytes read with the unreadable local file header can't be searched: {}", new_current_offset - current_offset, e)));
+                    }
+                },
             }
         }
 
@@ -413,7 +1880,8 @@ impl ZipFileReader {
          * That means that once we finished to read the series of Local File
          * Headers, signatures are checked for Local File Header, Archive Extra
          * Data Record, and Central Directory.
-         * TODO: add check and reading for Archive Decryption Header
+         * The Archive Decryption Header has no signature, so it's read once the
+         * Central Directory tells where it starts.
          * By doing this, as soon as a known section is found somewhere in the
          * file, reading can continue.
          *
@@ -421,56 +1889,538 @@ impl ZipFileReader {
          * possible to have 4 bytes somewhere whose value matches a signature.
          * It would break reading of the rest of the file.
          */
+        let known_signatures = [
+            constants::SIGNATURE_HEADER_LOCAL_FILE,
+            constants::SIGNATURE_ARCHIVE_EXTRA_DATA_RECORD,
+            constants::SIGNATURE_HEADER_CENTRAL_DIRECTORY,
+            constants::SIGNATURE_ZIP64_END_OF_CENTRAL_DIRECTORY_RECORD,
+            constants::SIGNATURE_END_OF_CENTRAL_DIRECTORY_RECORD,
+        ];
+        // The offset of the first byte skipped since the last known structure
+        let mut skipped_from: Option<u64> = None;
         loop {
             let chunk = read_chunk(file, 4);
+            if chunk.len() < 4 {
+                // We reached the end of the file, stop here
+                if let Some(start) = skipped_from.take() {
+                    let end = current_position(file, Structure::Archive)?;
+                    diagnostics.push(skipped_bytes_diagnostic(start, end));
+                }
+                break;
+            }
+            // A known structure ends the bytes skipped, if any
+            if known_signatures.contains(&read_u32_le(&chunk).unwrap()) {
+                if let Some(start) = skipped_from.take() {
+                    let end = current_position(file, Structure::Archive)? - 4;
+                    diagnostics.push(skipped_bytes_diagnostic(start, end));
+                }
+            }
+
             // Did we found another local file header?
             if compare_signature_raw(file, &chunk, constants::SIGNATURE_HEADER_LOCAL_FILE, false)? {
-                let stored_file = StoredFileReader::read(file, stored_files.len());
-                // TODO: handle the case if stored_file is an Err. Log it, at least
-                if stored_file.is_ok() {
-                    stored_files.push(stored_file.unwrap());
+                let offset = current_position(file, Structure::LocalFileHeader)? - 4;
+                let stored_file = StoredFileReader::read(file, stored_files.len(), known_central_directory, data_handling, &mut diagnostics);
+                match stored_file {
+                    Ok(stored_file) => stored_files.push(stored_file),
+                    Err(e) => diagnostics.push(Diagnostic::new(Severity::Error, DiagnosticCode::UnreadableLocalFileHeader, Some(offset), e.to_string())),
                 }
             } else if compare_signature_raw(file, &chunk, constants::SIGNATURE_ARCHIVE_EXTRA_DATA_RECORD, false)? {
                 // Did we found the archive extra data record?
-                archive_extra_data_record = Some(ArchiveExtraDataRecordReader::read(file)?);
-            } else if compare_signature_raw(file, &chunk, constants::SIGNATURE_HEADER_CENTRAL_DIRECTORY, false)? {
+                let offset = current_position(file, Structure::ArchiveExtraDataRecord)? - 4;
+                match ArchiveExtraDataRecordReader::read(file) {
+                    Ok(record) => archive_extra_data_record = Some(record),
+                    Err(e) => diagnostics.push(Diagnostic::new(Severity::Error, DiagnosticCode::UnreadableArchiveExtraDataRecord, Some(offset), e.to_string())),
+                }
+            } else if compare_signature_raw(file, &chunk, constants::SIGNATURE_HEADER_CENTRAL_DIRECTORY, false)?
+                    || compare_signature_raw(file, &chunk, constants::SIGNATURE_ZIP64_END_OF_CENTRAL_DIRECTORY_RECORD, false)?
+                    || compare_signature_raw(file, &chunk, constants::SIGNATURE_END_OF_CENTRAL_DIRECTORY_RECORD, false)? {
                 // Did we found the central directory?
+                // An empty central directory starts directly with the (zip64) end
+                // of central directory record.
                 // This struct is repeated for each file, so the CentralDirectoryReader
                 // will loop on each file. For this, it needs to read the signature. Since
                 // we already consumed it because of the usage of compare_signature_raw(),
                 // rewind the file cursor.
                 rewind_file_cursor(file, 4)?;
-                let cd_result = CentralDirectoryReader::read(file);
-                if cd_result.is_ok() {
-                    let cd = cd_result.unwrap();
-                    central_directory = Some(cd);
-
-                    // Set StoredFile values with the ones found in CentralDirectory
-                    for stored_file in &mut stored_files {
-                        stored_file.update_from_central_directory(central_directory.as_ref().unwrap());
-                    }
-                } else {
-                    // TODO: use a logger instead of printing to STDOUT
-                    if let Err(e) = cd_result {
-                        println!("ERROR when reading central directory header: {}", e);
-                    }
+                let offset = current_position(file, Structure::CentralDirectory)?;
+                match CentralDirectoryReader::read_on_disks(file, disk_offsets, &mut diagnostics) {
+                    Ok(cd) => {
+                        // Set StoredFile values with the ones found in CentralDirectory
+                        for stored_file in &mut stored_files {
+                            stored_file.update_from_central_directory(&cd);
+                        }
+                        central_directory = Some(cd);
+                    },
+                    Err(e) => diagnostics.push(Diagnostic::new(Severity::Error, DiagnosticCode::UnreadableCentralDirectory, Some(offset), e.to_string())),
                 }
                 // Central directory is the last part of a ZIP, if we found it
                 // we can exit the loop
                 break;
-            } else if !file_has_remaining_space(file, 4)? {
-                // It seems we reached the end of the file, stop here
-                break;
             } else {
                 // We didn't find anything. Shift of 1 byte, and try again
                 rewind_file_cursor(file, 3)?;
+                if skipped_from.is_none() {
+                    skipped_from = Some(current_position(file, Structure::Archive)? - 1);
+                }
             }
         }
 
+        let mut archive_decryption_header = None;
+        if let Some(cd) = &central_directory {
+            archive_decryption_header = Self::read_archive_decryption_header(file, cd, &stored_files, disk_offsets)
+                .unwrap_or_else(|e| {
+                    diagnostics.push(Diagnostic::new(Severity::Error, DiagnosticCode::UnreadableArchiveDecryptionHeader, e.offset, e.to_string()));
+                    None
+                });
+            // The files of an encrypted central directory can't be paired
+            if archive_decryption_header.is_none() {
+                for stored_file in stored_files.iter().filter(|stored_file| !stored_file.found_in_central_directory) {
+                    diagnostics.push(Diagnostic::new(Severity::Warning, DiagnosticCode::UnreferencedLocalFileHeader, Some(stored_file.offset_in_archive as u64),
+                        format!("The central directory doesn't reference the local file header of {}", stored_file.local_file_header.filename)));
+                }
+            }
+        } else if !diagnostics.iter().any(|diagnostic| diagnostic.code == DiagnosticCode::UnreadableCentralDirectory) {
+            diagnostics.push(Diagnostic::new(Severity::Warning, DiagnosticCode::MissingCentralDirectory, None,
+                "No central directory found, the files are known only from their local file header".to_string()));
+        }
+
+        // The start of a stream may be out of reach, its prefix is unknown then
+        let prefix = Self::read_prefix(file, &stored_files, central_directory.as_ref())
+            .unwrap_or_else(|e| {
+                diagnostics.push(Diagnostic::new(Severity::Info, DiagnosticCode::UnreadablePrefix, Some(0), e.to_string()));
+                None
+            });
+
+        // The bytes skipped in the data before the archive or in an encrypted
+        // central directory are described by them, they are not reported
+        let mut described_ranges = Vec::new();
+        if let Some(prefix) = &prefix {
+            described_ranges.push(0..prefix.size);
+        }
+        if let (Some(header), Some(cd)) = (&archive_decryption_header, &central_directory) {
+            let start = header.offset_from_start_of_archive as u64;
+            described_ranges.push(start..start.saturating_add(cd.end_of_central_directory_record.central_directory_size));
+        }
+        diagnostics.retain(|diagnostic| diagnostic.code != DiagnosticCode::SkippedBytes
+            || !diagnostic.offset.is_some_and(|offset| described_ranges.iter().any(|range| range.contains(&offset))));
+
         Ok(ZipFile {
-            stored_files: stored_files,
-            archive_extra_data_record: archive_extra_data_record,
-            central_directory: central_directory,
+            stored_files,
+            archive_extra_data_record,
+            archive_decryption_header,
+            central_directory,
+            prefix,
+            volumes: Vec::new(),
+            volume_issues: Vec::new(),
+            diagnostics,
         })
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zip::read_fixture;
+
+    #[test]
+    fn reads_zip64_sizes_and_offsets() {
+        // The 32-bit fields of big.txt are 0xffffffff, its values are in its
+        // zip64 extended information. small.txt only has its offset there.
+        for strategy in [ParsingStrategy::Sequential, ParsingStrategy::CentralDirectoryFirst] {
+            let zip_file = read_fixture("zip64.zip", strategy);
+            let big = &zip_file.stored_files[0].local_file_header;
+            assert_eq!((big.compressed_size, big.uncompressed_size), (11, 11));
+            let zip64 = big.zip64_extended_information.as_ref().unwrap();
+            assert_eq!((zip64.uncompressed_size, zip64.compressed_size, zip64.local_file_header_offset), (Some(11), Some(11), None));
+            assert!(zip_file.stored_files[1].local_file_header.zip64_extended_information.is_none());
+
+            let central_directory = zip_file.central_directory.unwrap();
+            let [big, small] = &central_directory.file_headers[..] else { panic!("expected 2 headers") };
+            assert_eq!((big.compressed_size, big.uncompressed_size, big.local_file_header_offset), (11, 11, 0));
+            assert_eq!((small.compressed_size, small.uncompressed_size, small.local_file_header_offset), (6, 6, 68));
+            let zip64 = small.zip64_extended_information.as_ref().unwrap();
+            assert_eq!((zip64.uncompressed_size, zip64.compressed_size, zip64.local_file_header_offset), (None, None, Some(68)));
+
+            let record = central_directory.zip64_end_of_central_directory_record.unwrap();
+            assert_eq!((record.size_of_record, record.version_made_by, record.minimum_version), (44, 45, 45));
+            assert_eq!((record.central_directory_records_total_number, record.central_directory_size, record.offset_start_central_directory), (2, 148, 113));
+            let locator = central_directory.zip64_end_of_central_directory_locator.unwrap();
+            assert_eq!((locator.offset_zip64_end_of_central_directory_record, locator.total_number_of_disks), (261, 1));
+            // The placeholders of the end of central directory record are replaced
+            let end = central_directory.end_of_central_directory_record;
+            assert_eq!((end.central_directory_records_total_number, end.central_directory_size, end.offset_start_central_directory), (2, 148, 113));
+            assert!(zip_file.diagnostics.is_empty());
+        }
+    }
+
+    #[test]
+    fn reads_zip64_extended_information_fields_in_order() {
+        let data = [1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0];
+        let zip64 = Zip64ExtendedInformationReader::read(&data, false, true, true, true).unwrap();
+        assert_eq!((zip64.uncompressed_size, zip64.compressed_size, zip64.local_file_header_offset, zip64.disk_start), (None, Some(1), Some(2), Some(3)));
+        let e = Zip64ExtendedInformationReader::read(&data[..8], true, true, false, false).err().unwrap();
+        assert_eq!(e.field, Some("compressed size"));
+    }
+
+    #[test]
+    fn decodes_known_extra_field_records() {
+        let zip_file = read_fixture("extra_fields.zip", ParsingStrategy::Sequential);
+        let extra_field = &zip_file.stored_files[0].local_file_header.extra_field;
+        let header_ids: Vec<_> = extra_field.records.iter().map(|record| (record.header_id, record.data_size, record.offset)).collect();
+        assert_eq!(header_ids, [(0x5455, 9, 0), (0x7875, 11, 13), (0x000a, 32, 28), (0x9901, 7, 64), (0xcafe, 3, 75)]);
+        assert!(!extra_field.is_malformed());
+
+        let ExtraFieldContent::ExtendedTimestamp(timestamp) = &extra_field.records[0].content else { panic!("expected an extended timestamp") };
+        assert_eq!((timestamp.flags, timestamp.modification_time, timestamp.access_time, timestamp.creation_time), (3, Some(1700000000), Some(1700000100), None));
+        let ExtraFieldContent::InfoZipUnix(unix) = &extra_field.records[1].content else { panic!("expected Info-ZIP Unix") };
+        assert_eq!((unix.version, unix.uid, unix.gid), (1, 1000, 100));
+        let ExtraFieldContent::Ntfs(ntfs) = &extra_field.records[2].content else { panic!("expected NTFS times") };
+        assert_eq!((ntfs.modification_time, ntfs.access_time, ntfs.creation_time), (Some(133000000000000000), Some(133000000010000000), Some(132000000000000000)));
+        let aes = extra_field.aes().unwrap();
+        assert_eq!((aes.vendor_version, aes.vendor_id.as_str(), aes.strength, aes.compression_method), (2, "AE", 3, 8));
+        assert!(matches!(extra_field.records[4].content, ExtraFieldContent::Unknown));
+        assert_eq!(extra_field.records[4].data, [1, 2, 3]);
+
+        // The central directory only holds the modification time
+        let header = &zip_file.central_directory.unwrap().file_headers[0];
+        let ExtraFieldContent::ExtendedTimestamp(timestamp) = &header.extra_field.records[0].content else { panic!("expected an extended timestamp") };
+        assert_eq!((timestamp.flags, timestamp.modification_time, timestamp.access_time), (3, Some(1700000000), None));
+    }
+
+    #[test]
+    fn keeps_extra_field_records_whose_content_is_invalid() {
+        let zip_file = read_fixture("extra_fields.zip", ParsingStrategy::Sequential);
+        let extra_field = &zip_file.stored_files[1].local_file_header.extra_field;
+        assert!(extra_field.is_malformed());
+        let header_ids: Vec<_> = extra_field.records.iter().map(|record| record.header_id).collect();
+        assert_eq!(header_ids, [0x5455, 0x0001]);
+        assert!(extra_field.records.iter().all(|record| matches!(record.content, ExtraFieldContent::Malformed(_))));
+    }
+
+    #[test]
+    fn keeps_truncated_extra_field_records() {
+        // A record header cut after its header ID
+        let extra_field = ExtraFieldReader::read(vec![0x75, 0x78]);
+        assert_eq!((extra_field.records[0].header_id, extra_field.records[0].data_size), (0x7875, 0));
+        assert!(matches!(extra_field.records[0].content, ExtraFieldContent::Malformed(_)));
+        // Data shorter than its declared size, after a valid record
+        let extra_field = ExtraFieldReader::read(vec![0xfe, 0xca, 0, 0, 0x55, 0x54, 9, 0, 1]);
+        assert_eq!(extra_field.records.len(), 2);
+        assert!(matches!(extra_field.records[0].content, ExtraFieldContent::Unknown));
+        assert_eq!((extra_field.records[1].data_size, extra_field.records[1].data.as_slice()), (9, &[1u8][..]));
+        assert!(matches!(extra_field.records[1].content, ExtraFieldContent::Malformed(_)));
+    }
+
+    #[test]
+    fn decodes_filenames_according_to_their_encoding() {
+        let zip_file = read_fixture("names.zip", ParsingStrategy::Sequential);
+        let names: Vec<_> = zip_file.stored_files.iter()
+            .map(|stored_file| (stored_file.local_file_header.filename.as_str(), stored_file.local_file_header.filename_encoding))
+            .collect();
+        assert_eq!(names, [
+            ("été.txt", TextEncoding::Utf8),
+            ("été ╔═╗.txt", TextEncoding::Cp437),
+            ("日本.txt", TextEncoding::InfoZipUnicode),
+            // The CRC-32 of the Info-ZIP Unicode path doesn't match the name
+            ("é.txt", TextEncoding::Cp437),
+            ("\u{fffd}.txt", TextEncoding::Utf8),
+        ]);
+        assert_eq!(zip_file.stored_files[1].local_file_header.filename_raw, b"\x82t\x82 \xc9\xcd\xbb.txt");
+        assert_eq!(zip_file.stored_files[2].local_file_header.filename_raw, b"\x82.txt");
+        assert_eq!(zip_file.stored_files[4].local_file_header.filename_raw, b"\xff.txt");
+    }
+
+    #[test]
+    fn decodes_comments_according_to_their_encoding() {
+        let central_directory = read_fixture("names.zip", ParsingStrategy::Sequential).central_directory.unwrap();
+        let comments: Vec<_> = central_directory.file_headers.iter()
+            .map(|header| (header.file_comment.as_str(), header.file_comment_encoding))
+            .collect();
+        assert_eq!(comments[..3], [("café", TextEncoding::Utf8), ("£5", TextEncoding::Cp437), ("€", TextEncoding::InfoZipUnicode)]);
+        assert_eq!(central_directory.file_headers[2].file_comment_raw, b"\x9c");
+        assert_eq!(central_directory.file_headers[2].filename, "日本.txt");
+        let end = central_directory.end_of_central_directory_record;
+        assert_eq!((end.comment.as_str(), end.comment_raw.as_slice()), ("αß", &b"\xe0\xe1"[..]));
+    }
+
+    #[test]
+    fn finds_end_of_central_directory_record_from_the_end() {
+        // The comment holds a fake record whose comment length is wrong
+        let data = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/comment.zip")).unwrap();
+        assert_eq!(find_end_of_central_directory_record(&mut Cursor::new(&data)).unwrap(), 185);
+        // Without any matching comment length, the last signature is used
+        let appended = [&data[..], b"appended"].concat();
+        assert_eq!(find_end_of_central_directory_record(&mut Cursor::new(&appended)).unwrap(), 212);
+        assert!(matches!(find_end_of_central_directory_record(&mut Cursor::new(&data[..185])).err().unwrap().kind, ParseErrorKind::NotFound));
+    }
+
+    #[test]
+    fn reads_files_at_the_offsets_of_the_central_directory() {
+        // The central directory lists b.txt before a.txt
+        let zip_file = read_fixture("comment.zip", ParsingStrategy::CentralDirectoryFirst);
+        let files: Vec<_> = zip_file.stored_files.iter()
+            .map(|stored_file| (stored_file.local_file_header.filename.as_str(), stored_file.position, stored_file.offset_in_archive, stored_file.offset_from_central_directory))
+            .collect();
+        assert_eq!(files, [("a.txt", 0, 0, Some(0)), ("b.txt", 1, 41, Some(41))]);
+        assert!(zip_file.stored_files.iter().all(|stored_file| stored_file.found_in_central_directory));
+
+        let central_directory = zip_file.central_directory.unwrap();
+        let headers: Vec<_> = central_directory.file_headers.iter()
+            .map(|header| (header.filename.as_str(), header.position, header.offset_in_archive))
+            .collect();
+        assert_eq!(headers, [("b.txt", Some(0), Some(41)), ("a.txt", Some(1), Some(0))]);
+        assert_eq!(central_directory.offset_from_start_of_archive, 83);
+        assert!(central_directory.end_of_central_directory_record.comment.starts_with("fake PK"));
+        assert!(zip_file.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn reads_only_the_files_of_the_central_directory() {
+        // Two files named x.txt, the central directory references only the second one
+        let zip_file = read_fixture("unreferenced.zip", ParsingStrategy::CentralDirectoryFirst);
+        let [visible] = &zip_file.stored_files[..] else { panic!("expected 1 file") };
+        assert_eq!((visible.offset_in_archive, visible.data_size), (42, 8));
+        assert!(zip_file.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn finds_data_of_unknown_size_without_central_directory() {
+        // The sizes of the files are only in their data descriptor, the stream
+        // is read without the central directory
+        let data = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/descriptors.zip")).unwrap();
+        let zip_file = ZipFileReader::read_stream(&data[..], true).unwrap();
+        let sizes: Vec<_> = zip_file.stored_files.iter()
+            .map(|stored_file| (stored_file.local_file_header.filename.as_str(), stored_file.data_size, stored_file.data_size_source))
+            .collect();
+        assert_eq!(sizes, [
+            ("deflated.txt", 21, DataSizeSource::DeflateStream),
+            ("signed.txt", 64, DataSizeSource::SignedDataDescriptor),
+            ("unsigned.txt", 64, DataSizeSource::UnsignedDataDescriptor),
+            ("zip64.txt", 64, DataSizeSource::SignedDataDescriptor),
+        ]);
+        for stored_file in &zip_file.stored_files[1..] {
+            assert_eq!(stored_file.file_data.as_deref(), Some(&b"data descriptor\n".repeat(4)[..]));
+        }
+        let descriptors: Vec<_> = zip_file.stored_files.iter()
+            .map(|stored_file| {
+                let descriptor = stored_file.data_descriptor.as_ref().unwrap();
+                (descriptor.signature_present, descriptor.crc32, descriptor.compressed_size, descriptor.uncompressed_size)
+            })
+            .collect();
+        assert_eq!(descriptors, [
+            (true, 0xefd7_8ecb, 21, 64),
+            (true, 0xefd7_8ecb, 64, 64),
+            (false, 0xefd7_8ecb, 64, 64),
+            (true, 0xefd7_8ecb, 64, 64),
+        ]);
+        assert!(zip_file.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn takes_size_of_unknown_data_from_central_directory() {
+        let zip_file = read_fixture("descriptors.zip", ParsingStrategy::Sequential);
+        assert!(zip_file.stored_files.iter().all(|stored_file| stored_file.data_size_source == DataSizeSource::CentralDirectory));
+        // The zip64 data descriptor has 8-byte sizes
+        let descriptor = zip_file.stored_files[3].data_descriptor.as_ref().unwrap();
+        assert_eq!((descriptor.compressed_size, descriptor.uncompressed_size), (64, 64));
+        assert!(!zip_file.stored_files[2].data_descriptor.as_ref().unwrap().signature_present);
+        assert!(zip_file.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn reads_strong_encryption_structures() {
+        let zip_file = read_fixture("strong_encryption.zip", ParsingStrategy::Sequential);
+        let stored_file = &zip_file.stored_files[0];
+        assert!(stored_file.local_file_header.uses_strong_encryption());
+        assert!(!stored_file.local_file_header.is_masked());
+        let ExtraFieldContent::StrongEncryptionHeader(strong) = &stored_file.local_file_header.extra_field.records[0].content else { panic!("expected a strong encryption header") };
+        assert_eq!((strong.format, strong.algorithm_id, strong.bit_length, strong.flags), (2, 0x660e, 128, 1));
+
+        let header = stored_file.decryption_header.as_ref().unwrap();
+        assert_eq!((header.iv.as_slice(), header.size, header.format, header.algorithm_id, header.bit_length, header.flags), (&[0x11; 16][..], 48, 3, 0x660e, 128, 1));
+        assert_eq!((header.encrypted_random_data.as_slice(), header.recipient_count, header.hash_algorithm), (&[0x22; 16][..], 0, None));
+        assert_eq!((header.password_validation_data.as_slice(), header.password_validation_crc32), (&[0x33; 12][..], 0x44444444));
+        // The data includes the decryption header
+        assert_eq!(stored_file.data_size, 86);
+        assert!(zip_file.archive_decryption_header.is_none());
+        assert!(zip_file.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn reads_archive_decryption_header_of_encrypted_central_directory() {
+        for strategy in [ParsingStrategy::Sequential, ParsingStrategy::CentralDirectoryFirst] {
+            let zip_file = read_fixture("encrypted_central_directory.zip", strategy);
+            // The values of the local file header are masked. Without central
+            // directory file header, the file is only found sequentially.
+            if strategy == ParsingStrategy::Sequential {
+                let local_file_header = &zip_file.stored_files[0].local_file_header;
+                assert!(local_file_header.is_masked());
+                assert_eq!((local_file_header.crc32, local_file_header.uncompressed_size, local_file_header.filename_raw.as_slice()), (0, 0, &[0; 8][..]));
+            } else {
+                assert!(zip_file.stored_files.is_empty());
+            }
+
+            let archive_decryption_header = zip_file.archive_decryption_header.unwrap();
+            assert_eq!(archive_decryption_header.offset_from_start_of_archive, 128);
+            assert_eq!((archive_decryption_header.decryption_header.algorithm_id, archive_decryption_header.decryption_header.bit_length), (0x6610, 256));
+            assert!(archive_decryption_header.archive_extra_data_record.is_some());
+            assert_eq!(archive_decryption_header.encrypted_central_directory.len(), 64);
+
+            let central_directory = zip_file.central_directory.unwrap();
+            assert!(central_directory.file_headers.is_empty());
+            let encryption = central_directory.zip64_end_of_central_directory_record.unwrap().central_directory_encryption.unwrap();
+            assert_eq!((encryption.compressed_size, encryption.uncompressed_size, encryption.algorithm_id, encryption.bit_length), (64, 64, 0x6610, 256));
+            assert!(zip_file.diagnostics.is_empty());
+        }
+    }
+
+    #[test]
+    fn finds_base_offset_of_self_extracting_archive() {
+        // The offsets of the archive don't count the 64 bytes of the ELF stub
+        for strategy in [ParsingStrategy::Sequential, ParsingStrategy::CentralDirectoryFirst] {
+            let zip_file = read_fixture("sfx.zip", strategy);
+            let prefix = zip_file.prefix.unwrap();
+            assert_eq!((prefix.size, prefix.stub_type), (64, StubType::Elf));
+            let files: Vec<_> = zip_file.stored_files.iter()
+                .map(|stored_file| (stored_file.offset_in_archive, stored_file.offset_from_central_directory))
+                .collect();
+            assert_eq!(files, [(64, Some(0)), (105, Some(41))]);
+
+            let central_directory = zip_file.central_directory.unwrap();
+            assert_eq!(central_directory.base_offset, 64);
+            assert_eq!((central_directory.end_of_central_directory_record.offset_start_central_directory, central_directory.offset_from_start_of_archive), (83, 147));
+            let headers: Vec<_> = central_directory.file_headers.iter()
+                .map(|header| (header.local_file_header_offset, header.offset_in_archive))
+                .collect();
+            assert_eq!(headers, [(0, Some(64)), (41, Some(105))]);
+            assert!(zip_file.diagnostics.is_empty());
+        }
+    }
+
+    #[test]
+    fn keeps_offsets_of_self_extracting_archive_already_adjusted() {
+        // The offsets of the archive count the 64 bytes of the PE stub
+        for strategy in [ParsingStrategy::Sequential, ParsingStrategy::CentralDirectoryFirst] {
+            let zip_file = read_fixture("sfx_adjusted.zip", strategy);
+            let prefix = zip_file.prefix.unwrap();
+            assert_eq!((prefix.size, prefix.stub_type), (64, StubType::Pe));
+            let files: Vec<_> = zip_file.stored_files.iter()
+                .map(|stored_file| (stored_file.offset_in_archive, stored_file.offset_from_central_directory))
+                .collect();
+            assert_eq!(files, [(64, Some(64)), (105, Some(105))]);
+            assert_eq!(zip_file.central_directory.unwrap().base_offset, 0);
+            assert!(zip_file.diagnostics.is_empty());
+        }
+    }
+
+    #[test]
+    fn reads_archive_from_a_stream() {
+        let data = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sfx.zip")).unwrap();
+        let zip_file = ZipFileReader::read_stream(&data[..], false).unwrap();
+        // The stub is still in the look-back buffer
+        let prefix = zip_file.prefix.unwrap();
+        assert_eq!((prefix.size, prefix.stub_type), (64, StubType::Elf));
+        let files: Vec<_> = zip_file.stored_files.iter()
+            .map(|stored_file| (stored_file.offset_in_archive, stored_file.found_in_central_directory))
+            .collect();
+        assert_eq!(files, [(64, true), (105, true)]);
+        // The content is summarized as it's read, and not kept
+        let summary = zip_file.stored_files[1].content_summary.as_ref().unwrap().as_ref().unwrap();
+        assert_eq!((summary.crc32, summary.size), (crc32(b"second\n"), 7));
+        assert!(zip_file.stored_files[1].file_data.is_none());
+        assert_eq!(zip_file.central_directory.unwrap().base_offset, 64);
+        assert!(zip_file.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn reports_prefix_out_of_reach_of_a_stream() {
+        let archive = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sfx.zip")).unwrap();
+        // The stub is longer than the bytes kept behind the cursor
+        let data = [vec![0; 1000], archive].concat();
+        let zip_file = ZipFileReader::read_forward(&mut LookBackReader::with_look_back_size(&data[..], 16), &[], None, DataHandling::Summarize, Vec::new()).unwrap();
+        assert!(zip_file.prefix.is_none());
+        assert_eq!(zip_file.stored_files.len(), 2);
+        let codes: Vec<_> = zip_file.diagnostics.iter().map(|diagnostic| (diagnostic.severity, diagnostic.code)).collect();
+        assert_eq!(codes, [(Severity::Warning, DiagnosticCode::SkippedBytes), (Severity::Info, DiagnosticCode::UnreadablePrefix)]);
+    }
+
+    #[test]
+    fn reports_damages_of_archive_without_central_directory() {
+        let zip_file = read_fixture("damaged.zip", ParsingStrategy::Sequential);
+        let diagnostics: Vec<_> = zip_file.diagnostics.iter()
+            .map(|diagnostic| (diagnostic.severity, diagnostic.code, diagnostic.offset))
+            .collect();
+        assert_eq!(diagnostics, [
+            (Severity::Warning, DiagnosticCode::SkippedBytes, Some(41)),
+            (Severity::Warning, DiagnosticCode::UnknownDataSize, Some(86)),
+            (Severity::Warning, DiagnosticCode::TruncatedFileData, Some(138)),
+            (Severity::Warning, DiagnosticCode::MissingCentralDirectory, None),
+        ]);
+        assert_eq!(zip_file.stored_files.len(), 3);
+    }
+
+    #[test]
+    fn reports_missing_local_file_header_at_offset_of_central_directory() {
+        // The central directory gives b.txt the offset 20, in the middle of a.txt
+        let zip_file = read_fixture("wrong_offset.zip", ParsingStrategy::CentralDirectoryFirst);
+        let diagnostic = zip_file.diagnostics.iter()
+            .find(|diagnostic| diagnostic.code == DiagnosticCode::MissingLocalFileHeader)
+            .unwrap();
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.offset, Some(20));
+        assert_eq!(zip_file.stored_files.len(), 1);
+    }
+
+    #[test]
+    fn stops_central_directory_at_unreadable_header() {
+        // The central directory file header of b.txt, the second one, can't be read
+        let zip_file = read_fixture("bad_cd_header.zip", ParsingStrategy::CentralDirectoryFirst);
+        let filenames: Vec<_> = zip_file.stored_files.iter().map(|stored_file| stored_file.local_file_header.filename.as_str()).collect();
+        assert_eq!(filenames, ["a.txt"]);
+        assert_eq!(zip_file.central_directory.unwrap().file_headers.len(), 1);
+        let diagnostic = zip_file.diagnostics.iter()
+            .find(|diagnostic| diagnostic.code == DiagnosticCode::UnreadableCentralDirectoryFileHeader)
+            .unwrap();
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.offset, Some(175));
+    }
+
+    #[test]
+    fn skips_unreadable_central_directory_header_when_read_sequentially() {
+        let zip_file = read_fixture("bad_cd_header.zip", ParsingStrategy::Sequential);
+        assert_eq!(zip_file.stored_files.len(), 3);
+        let filenames: Vec<_> = zip_file.central_directory.unwrap().file_headers.iter().map(|header| header.filename.clone()).collect();
+        assert_eq!(filenames, ["a.txt", "c.txt"]);
+        let unreadable = zip_file.diagnostics.iter()
+            .filter(|diagnostic| diagnostic.code == DiagnosticCode::UnreadableCentralDirectoryFileHeader)
+            .count();
+        assert_eq!(unreadable, 1);
+    }
+
+    #[test]
+    fn reports_bytes_which_can_not_be_searched_again_in_a_stream() {
+        // The local file header of the first file is more than 1000 bytes
+        // long, and can't be read
+        let data = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/long_bad_header.zip")).unwrap();
+        let mut stream = LookBackReader::with_look_back_size(&data[..], 16);
+        let zip_file = ZipFileReader::read_forward(&mut stream, &[], None, DataHandling::Keep, Vec::new()).unwrap();
+        let codes: Vec<_> = zip_file.diagnostics.iter().map(|diagnostic| (diagnostic.code, diagnostic.offset)).collect();
+        assert_eq!(codes[..2], [(DiagnosticCode::UnreadableLocalFileHeader, Some(0)), (DiagnosticCode::SkippedBytes, Some(4))]);
+        let filenames: Vec<_> = zip_file.stored_files.iter().map(|stored_file| stored_file.local_file_header.filename.as_str()).collect();
+        assert_eq!(filenames, ["b.txt"]);
+    }
+
+    #[test]
+    fn reports_truncated_extra_field_records() {
+        // The extended timestamp record of both headers is truncated
+        for strategy in [ParsingStrategy::Sequential, ParsingStrategy::CentralDirectoryFirst] {
+            let zip_file = read_fixture("malformed_extra.zip", strategy);
+            let mut offsets: Vec<_> = zip_file.diagnostics.iter()
+                .filter(|diagnostic| diagnostic.code == DiagnosticCode::MalformedExtraField)
+                .map(|diagnostic| diagnostic.offset)
+                .collect();
+            offsets.sort();
+            assert_eq!(offsets, [Some(35), Some(97)]);
+            assert!(zip_file.stored_files[0].local_file_header.extra_field.is_malformed());
+        }
+    }
+}
@@ -0,0 +1,457 @@
+//! This module provides a reader for split archives, whose volumes are read
+//! as a single stream.
+//! Two naming schemes are supported:
+//! - the spanned archives made by PKZIP or Info-ZIP (archive.z01, archive.z02,
+//!   ..., archive.zip), whose offsets are relative to the disk they are on
+//! - the archives cut in pieces by a generic tool (archive.zip.001,
+//!   archive.zip.002, ...), whose offsets are relative to the whole archive
+
+use crate::errors::{ParseError, ParseErrorKind, Structure};
+use crate::util::{compare_signature, read_chunk, read_u32_le};
+use super::constants;
+use super::model::{CentralDirectory, Volume, VolumeIssue};
+use super::reader::{find_end_of_central_directory_record, EndOfCentralDirectoryRecordReader, Zip64EndOfCentralDirectoryLocatorReader};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// The maximum number of disks of a spanned archive
+const MAX_SPANNED_DISK_COUNT: u32 = u16::MAX as u32;
+
+/// Represents a split archive.
+/// The volumes found are read as a single stream, each volume following the
+/// previous one. The missing volumes are skipped.
+pub struct SplitArchive {
+    /// The volumes found, sorted by disk number
+    volumes: Vec<Volume>,
+    /// The opened volumes, in the same order as volumes
+    files: Vec<File>,
+    /// The problems found in the volumes
+    issues: Vec<VolumeIssue>,
+    /// Whether the offsets in the archive are relative to the disk they are on
+    spanned: bool,
+    /// The number of disks of the archive, including the missing ones
+    disk_count: u32,
+    /// The current position in the stream
+    position: u64,
+}
+
+impl SplitArchive {
+    /// Open the volumes of a split archive, from the path of any of them.
+    /// The missing volumes are reported in issues(), opening fails only if no
+    /// volume at all can be opened.
+    pub fn open(path: &Path) -> Result<SplitArchive, ParseError> {
+        let extension = path.extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("");
+
+        if !extension.is_empty() && extension.bytes().all(|byte| byte.is_ascii_digit()) {
+            Self::open_numbered(path, extension.len())
+        } else if extension.eq_ignore_ascii_case("zip") {
+            Self::open_spanned(path)
+        } else if is_spanned_volume_extension(extension) {
+            // The last volume of a spanned archive is the .zip one, keep the case
+            let last_extension = if extension.starts_with('Z') { "ZIP" } else { "zip" };
+            Self::open_spanned(&path.with_extension(last_extension))
+        } else {
+            Err(ParseError::new(ParseErrorKind::Unsupported(format!("naming scheme of {}", path.display())), Structure::Volume))
+        }
+    }
+
+    /// Open the volumes of a spanned archive, from the path of its last volume.
+    /// The number of disks is read in the end of central directory record.
+    fn open_spanned(last_path: &Path) -> Result<SplitArchive, ParseError> {
+        let mut last_file = File::open(last_path)
+            .or(Err(ParseError::new(ParseErrorKind::MissingVolumes(vec![last_path.to_path_buf()]), Structure::Volume)))?;
+        let last_disk_number = read_last_disk_number(&mut last_file)?;
+        // The number of disks comes from the archive, don't search for more
+        // volumes than the end of central directory record can count
+        if last_disk_number >= MAX_SPANNED_DISK_COUNT {
+            return Err(ParseError::invalid_value(
+                Structure::Zip64EndOfCentralDirectoryLocator,
+                "total number of disks",
+                format!("{} disks is more than the {} supported", last_disk_number as u64 + 1, MAX_SPANNED_DISK_COUNT),
+            ));
+        }
+
+        let prefix = if last_path.extension().is_some_and(|extension| extension == "ZIP") { "Z" } else { "z" };
+        let mut paths: Vec<(u32, PathBuf)> = (0..last_disk_number)
+            .map(|disk_number| (disk_number, last_path.with_extension(format!("{}{:02}", prefix, disk_number + 1))))
+            .collect();
+        paths.push((last_disk_number, last_path.to_path_buf()));
+
+        Self::open_volumes(paths, true)
+    }
+
+    /// Open the volumes of an archive cut in numbered pieces, from the path of
+    /// one of them. All the pieces in the directory are searched, since the
+    /// archive doesn't store their number.
+    fn open_numbered(path: &Path, width: usize) -> Result<SplitArchive, ParseError> {
+        let base_path = path.with_extension("");
+        let base_name = base_path.file_name()
+            .and_then(|name| name.to_str())
+            .ok_or(ParseError::invalid_value(Structure::Volume, "path", format!("{} is not a valid volume name", path.display())))?;
+        let directory = match base_path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let entries = fs::read_dir(&directory)
+            .map_err(|e| ParseError::io(e, Structure::Volume))?;
+        let mut numbers: Vec<u32> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                let number = name.strip_prefix(base_name)?.strip_prefix('.')?;
+                if number.len() != width || !number.bytes().all(|byte| byte.is_ascii_digit()) {
+                    return None;
+                }
+                number.parse().ok()
+            })
+            .collect();
+        numbers.sort_unstable();
+
+        // The pieces are usually numbered from 1, but some tools start at 0
+        let first = numbers.first().map_or(1, |first| (*first).min(1));
+        let last = numbers.last()
+            .copied()
+            .ok_or(ParseError::new(ParseErrorKind::MissingVolumes(vec![path.to_path_buf()]), Structure::Volume))?;
+        let paths = (first..=last)
+            .map(|number| (number - first, directory.join(format!("{}.{:0width$}", base_name, number, width = width))))
+            .collect();
+
+        Self::open_volumes(paths, false)
+    }
+
+    /// Open the given volumes. The ones which cannot be opened are reported
+    /// as missing.
+    fn open_volumes(paths: Vec<(u32, PathBuf)>, spanned: bool) -> Result<SplitArchive, ParseError> {
+        let disk_count = paths.len() as u32;
+        let mut volumes = Vec::new();
+        let mut files = Vec::new();
+        let mut issues = Vec::new();
+        let mut offset_in_archive = 0;
+
+        for (disk_number, path) in paths {
+            let opened = File::open(&path)
+                .and_then(|file| Ok((file.metadata()?.len(), file)));
+            match opened {
+                Ok((size, file)) => {
+                    volumes.push(Volume { path, disk_number, size, offset_in_archive });
+                    files.push(file);
+                    offset_in_archive += size;
+                },
+                Err(_) => issues.push(VolumeIssue::Missing { disk_number, path }),
+            }
+        }
+
+        if volumes.is_empty() {
+            let paths = issues.into_iter()
+                .filter_map(|issue| match issue {
+                    VolumeIssue::Missing { path, .. } => Some(path),
+                    _ => None,
+                })
+                .collect();
+            return Err(ParseError::new(ParseErrorKind::MissingVolumes(paths), Structure::Volume));
+        }
+
+        let mut archive = SplitArchive { volumes, files, issues, spanned, disk_count, position: 0 };
+        if spanned && disk_count > 1 {
+            archive.check_split_signature();
+        }
+        Ok(archive)
+    }
+
+    /// The volumes found, sorted by disk number
+    pub fn volumes(&self) -> &[Volume] {
+        &self.volumes
+    }
+
+    /// The problems found in the volumes
+    pub fn issues(&self) -> &[VolumeIssue] {
+        &self.issues
+    }
+
+    /// Whether the offsets in the archive are relative to the disk they are on
+    pub fn is_spanned(&self) -> bool {
+        self.spanned
+    }
+
+    /// The size of the stream made of all the volumes found
+    pub fn size(&self) -> u64 {
+        self.volumes.last().map_or(0, |volume| volume.offset_in_archive + volume.size)
+    }
+
+    /// The offset of the start of each disk in the stream, indexed by disk
+    /// number. A missing disk starts where the next volume found starts.
+    /// It's empty if the offsets in the archive don't depend on the disk.
+    pub fn disk_offsets(&self) -> Vec<u64> {
+        if !self.spanned {
+            return Vec::new();
+        }
+
+        (0..self.disk_count)
+            .map(|disk_number| {
+                self.volumes.iter()
+                    .find(|volume| volume.disk_number >= disk_number)
+                    .map_or(self.size(), |volume| volume.offset_in_archive)
+            })
+            .collect()
+    }
+
+    /// Check that the local file headers announced by the central directory are
+    /// in the expected volumes. When a local file header is found at the same
+    /// offset in another volume instead, the volumes are reported as out of order.
+    pub fn check_volume_order(&mut self, central_directory: &CentralDirectory) {
+        for header in &central_directory.file_headers {
+            let Some(offset_in_archive) = header.offset_in_archive else {
+                continue;
+            };
+            let index = match self.volume_at(offset_in_archive) {
+                Some(index) => index,
+                None => continue,
+            };
+            // The volume of the disk may be missing, then the offset is in another one
+            if self.spanned && self.volumes[index].disk_number != header.disk_start {
+                continue;
+            }
+            if self.has_signature_at(offset_in_archive, constants::SIGNATURE_HEADER_LOCAL_FILE) {
+                continue;
+            }
+
+            let offset_in_volume = offset_in_archive - self.volumes[index].offset_in_archive;
+            let candidates: Vec<usize> = (0..self.volumes.len())
+                .filter(|other| *other != index && offset_in_volume < self.volumes[*other].size)
+                .collect();
+            let found_in = candidates.into_iter().find(|other| {
+                let offset = self.volumes[*other].offset_in_archive + offset_in_volume;
+                self.has_signature_at(offset, constants::SIGNATURE_HEADER_LOCAL_FILE)
+            });
+            if let Some(other) = found_in {
+                self.add_issue(VolumeIssue::OutOfOrder {
+                    disk_number: self.volumes[index].disk_number,
+                    found_in_disk_number: self.volumes[other].disk_number,
+                });
+            }
+        }
+    }
+
+    /// Check that the first volume of a spanned archive starts with the split
+    /// archive signature. If another volume starts with it, the volumes are
+    /// reported as out of order.
+    fn check_split_signature(&mut self) {
+        let starts_with_signature: Vec<bool> = (0..self.volumes.len())
+            .map(|index| {
+                let offset = self.volumes[index].offset_in_archive;
+                self.has_signature_at(offset, constants::SIGNATURE_SPLIT_ARCHIVE)
+            })
+            .collect();
+
+        if self.volumes[0].disk_number != 0 || starts_with_signature[0] {
+            return;
+        }
+        if let Some(other) = starts_with_signature.iter().position(|starts| *starts) {
+            self.add_issue(VolumeIssue::OutOfOrder {
+                disk_number: 0,
+                found_in_disk_number: self.volumes[other].disk_number,
+            });
+        }
+    }
+
+    /// Check if the 4 bytes at offset in the stream match a signature.
+    /// The current position is kept.
+    fn has_signature_at(&mut self, offset: u64, signature: u32) -> bool {
+        let position = self.position;
+        self.position = offset;
+        let chunk = read_chunk(self, 4);
+        self.position = position;
+        read_u32_le(&chunk).is_ok_and(|value| chunk.len() == 4 && value == signature)
+    }
+
+    /// Add an issue, if it's not already reported
+    fn add_issue(&mut self, issue: VolumeIssue) {
+        if !self.issues.contains(&issue) {
+            self.issues.push(issue);
+        }
+    }
+
+    /// Find the index of the volume containing the given offset of the stream
+    fn volume_at(&self, offset: u64) -> Option<usize> {
+        self.volumes.iter().position(|volume| {
+            offset >= volume.offset_in_archive && offset < volume.offset_in_archive + volume.size
+        })
+    }
+}
+
+impl Read for SplitArchive {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let index = match self.volume_at(self.position) {
+            Some(index) => index,
+            // We reached the end of the last volume
+            None => return Ok(0),
+        };
+
+        // Don't read beyond the end of the volume, the next read will
+        // continue in the next one
+        let volume = &self.volumes[index];
+        let offset_in_volume = self.position - volume.offset_in_archive;
+        let length = (volume.size - offset_in_volume).min(buf.len() as u64) as usize;
+
+        let file = &mut self.files[index];
+        file.seek(SeekFrom::Start(offset_in_volume))?;
+        let read = file.read(&mut buf[..length])?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for SplitArchive {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let new_position = match position {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.size().checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+
+        match new_position {
+            Some(new_position) => {
+                self.position = new_position;
+                Ok(new_position)
+            },
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")),
+        }
+    }
+}
+
+/// Check if an extension is the one of a volume of a spanned archive, like z01
+fn is_spanned_volume_extension(extension: &str) -> bool {
+    extension.len() >= 3
+        && extension.starts_with(['z', 'Z'])
+        && extension[1..].bytes().all(|byte| byte.is_ascii_digit())
+}
+
+/// Read the number of the last disk of an archive, in the end of central
+/// directory record of its last volume.
+/// If it's too big for this record, it's read in the zip64 end of central
+/// directory locator, which stores the total number of disks.
+fn read_last_disk_number(file: &mut File) -> Result<u32, ParseError> {
+    let end_of_central_directory_offset = find_end_of_central_directory_record(file)?;
+    file.seek(SeekFrom::Start(end_of_central_directory_offset + 4))
+        .map_err(|e| ParseError::io(e, Structure::EndOfCentralDirectoryRecord))?;
+    let record = EndOfCentralDirectoryRecordReader::read(file)?;
+
+    if record.disk_number == constants::ZIP64_PLACEHOLDER_U16 as u32 && end_of_central_directory_offset >= 20 {
+        file.seek(SeekFrom::Start(end_of_central_directory_offset - 20))
+            .map_err(|e| ParseError::io(e, Structure::Zip64EndOfCentralDirectoryLocator))?;
+        if compare_signature(file, constants::SIGNATURE_ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR)? {
+            let locator = Zip64EndOfCentralDirectoryLocatorReader::read(file)?;
+            return Ok(locator.total_number_of_disks.saturating_sub(1));
+        }
+    }
+
+    Ok(record.disk_number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zip::reader::{ParsingStrategy, ZipFileReader};
+    use std::env;
+
+    /// The path of a split archive of the test fixtures
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/split").join(name)
+    }
+
+    /// Copy volumes of the test fixtures to a new directory, with new names
+    fn copy_volumes(test: &str, volumes: &[(&str, &str)]) -> PathBuf {
+        let directory = env::temp_dir().join(format!("archive-analyzer-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        for (name, new_name) in volumes {
+            fs::copy(fixture(name), directory.join(new_name)).unwrap();
+        }
+        directory
+    }
+
+    #[test]
+    fn reads_spanned_archive() {
+        // Opened from any volume
+        let mut archive = SplitArchive::open(&fixture("spanned.z02")).unwrap();
+        assert!(archive.is_spanned());
+        let disks: Vec<_> = archive.volumes().iter().map(|volume| (volume.disk_number, volume.size, volume.offset_in_archive)).collect();
+        assert_eq!(disks, [(0, 57, 0), (1, 54, 57), (2, 124, 111)]);
+        assert_eq!(archive.disk_offsets(), [0, 57, 111]);
+        assert!(archive.issues().is_empty());
+
+        for strategy in [ParsingStrategy::Sequential, ParsingStrategy::CentralDirectoryFirst] {
+            let zip_file = ZipFileReader::read_split(&mut archive, strategy).unwrap();
+            let files: Vec<_> = zip_file.stored_files.iter()
+                .map(|stored_file| (stored_file.local_file_header.filename.as_str(), stored_file.offset_in_archive, stored_file.found_in_central_directory))
+                .collect();
+            assert_eq!(files, [("a.txt", 4, true), ("b.txt", 57, true)]);
+            assert_eq!(zip_file.stored_files[1].read_data(&mut archive).unwrap(), b"on the second disk\n");
+            // The offsets of the central directory are relative to the disks
+            let central_directory = zip_file.central_directory.unwrap();
+            let headers: Vec<_> = central_directory.file_headers.iter()
+                .map(|header| (header.disk_start, header.local_file_header_offset, header.offset_in_archive))
+                .collect();
+            assert_eq!(headers, [(0, 4, Some(4)), (1, 0, Some(57))]);
+            assert_eq!(central_directory.offset_from_start_of_archive, 111);
+            assert_eq!(zip_file.volumes.len(), 3);
+            assert!(zip_file.diagnostics.is_empty());
+        }
+    }
+
+    #[test]
+    fn reads_archive_cut_in_numbered_pieces() {
+        let mut archive = SplitArchive::open(&fixture("numbered.zip.003")).unwrap();
+        assert!(!archive.is_spanned());
+        assert!(archive.disk_offsets().is_empty());
+        assert_eq!(archive.size(), 231);
+
+        // The pieces are cut in the middle of the structures
+        let zip_file = ZipFileReader::read_split(&mut archive, ParsingStrategy::Sequential).unwrap();
+        let files: Vec<_> = zip_file.stored_files.iter()
+            .map(|stored_file| (stored_file.local_file_header.filename.as_str(), stored_file.offset_in_archive))
+            .collect();
+        assert_eq!(files, [("a.txt", 0), ("b.txt", 53)]);
+        assert_eq!(zip_file.stored_files[1].read_data(&mut archive).unwrap(), b"on the second disk\n");
+        assert!(zip_file.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn reports_missing_volume() {
+        let directory = copy_volumes("missing-volume", &[("spanned.z01", "spanned.z01"), ("spanned.zip", "spanned.zip")]);
+        let mut archive = SplitArchive::open(&directory.join("spanned.zip")).unwrap();
+        assert_eq!(archive.issues(), [VolumeIssue::Missing { disk_number: 1, path: directory.join("spanned.z02") }]);
+        // The missing disk starts where the next one starts
+        assert_eq!(archive.disk_offsets(), [0, 57, 57]);
+
+        let zip_file = ZipFileReader::read_split(&mut archive, ParsingStrategy::Sequential).unwrap();
+        assert_eq!(zip_file.stored_files.len(), 1);
+        assert_eq!(zip_file.volume_issues.len(), 1);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn reports_volumes_out_of_order() {
+        // The first two volumes are swapped
+        let directory = copy_volumes("volumes-out-of-order", &[("spanned.z01", "spanned.z02"), ("spanned.z02", "spanned.z01"), ("spanned.zip", "spanned.zip")]);
+        let mut archive = SplitArchive::open(&directory.join("spanned.zip")).unwrap();
+        assert_eq!(archive.issues(), [VolumeIssue::OutOfOrder { disk_number: 0, found_in_disk_number: 1 }]);
+
+        let zip_file = ZipFileReader::read_split(&mut archive, ParsingStrategy::CentralDirectoryFirst).unwrap();
+        assert!(zip_file.stored_files.is_empty());
+        assert!(zip_file.volume_issues.contains(&VolumeIssue::OutOfOrder { disk_number: 1, found_in_disk_number: 0 }));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn rejects_unknown_naming_scheme() {
+        let e = SplitArchive::open(Path::new("archive.tar")).err().unwrap();
+        assert!(matches!(e.kind, ParseErrorKind::Unsupported(_)));
+        let e = SplitArchive::open(&fixture("absent.zip")).err().unwrap();
+        assert!(matches!(e.kind, ParseErrorKind::MissingVolumes(_)));
+    }
+}
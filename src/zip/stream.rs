@@ -0,0 +1,154 @@
+//! This module provides a reader for archives coming from a non-seekable
+//! stream, like a pipe or a download.
+//! The parsers move the cursor backwards a little, to rewind a signature or
+//! the bytes read ahead by a decompressor. The last bytes read are kept to
+//! allow it, the positions before them can't be reached anymore.
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// The number of bytes kept behind the cursor by default.
+/// It's more than the bytes read ahead while searching a data descriptor.
+pub const DEFAULT_LOOK_BACK_SIZE: usize = 256 * 1024;
+
+/// Represents a forward-only stream, which can move backwards in its last bytes.
+/// Moving forward skips the bytes. Moving before the bytes kept or from the
+/// end of the stream is an error.
+pub struct LookBackReader<R: Read> {
+    /// The underlying stream
+    input: R,
+    /// The last bytes read from the stream
+    buffer: Vec<u8>,
+    /// The position in the stream of the first byte of buffer
+    buffer_start: u64,
+    /// The minimum number of bytes kept behind the cursor
+    look_back_size: usize,
+    /// The current position in the stream
+    position: u64,
+}
+
+impl<R: Read> LookBackReader<R> {
+    /// Create a reader keeping DEFAULT_LOOK_BACK_SIZE bytes behind the cursor
+    pub fn new(input: R) -> LookBackReader<R> {
+        Self::with_look_back_size(input, DEFAULT_LOOK_BACK_SIZE)
+    }
+
+    /// Create a reader keeping look_back_size bytes behind the cursor
+    pub fn with_look_back_size(input: R, look_back_size: usize) -> LookBackReader<R> {
+        LookBackReader {
+            input,
+            buffer: Vec::new(),
+            buffer_start: 0,
+            look_back_size,
+            position: 0,
+        }
+    }
+
+    /// The position in the stream of the byte following the buffer
+    fn buffer_end(&self) -> u64 {
+        self.buffer_start + self.buffer.len() as u64
+    }
+
+    /// Drop the bytes which are too far behind the cursor.
+    /// They are dropped by batches, to avoid moving the buffer at each read.
+    fn trim(&mut self) {
+        let kept_from = self.position.saturating_sub(self.look_back_size as u64);
+        let droppable = kept_from.saturating_sub(self.buffer_start) as usize;
+        if droppable > self.look_back_size {
+            self.buffer.drain(..droppable);
+            self.buffer_start += droppable as u64;
+        }
+    }
+}
+
+impl<R: Read> Read for LookBackReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Read again the bytes kept after a backward move
+        if self.position < self.buffer_end() {
+            let start = (self.position - self.buffer_start) as usize;
+            let length = (self.buffer.len() - start).min(buf.len());
+            buf[..length].copy_from_slice(&self.buffer[start..start + length]);
+            self.position += length as u64;
+            return Ok(length);
+        }
+
+        let read = self.input.read(buf)?;
+        self.buffer.extend_from_slice(&buf[..read]);
+        self.position += read as u64;
+        self.trim();
+        Ok(read)
+    }
+}
+
+impl<R: Read> Seek for LookBackReader<R> {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let new_position = match position {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(_) => return Err(io::Error::new(io::ErrorKind::Unsupported, "the end of a stream is unknown")),
+        };
+        let new_position = new_position
+            .ok_or(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position"))?;
+        if new_position < self.buffer_start {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, format!("position {} is no longer available in the stream", new_position)));
+        }
+
+        if new_position <= self.buffer_end() {
+            self.position = new_position;
+        } else {
+            // Skip the bytes up to the new position
+            self.position = self.buffer_end();
+            let length = new_position - self.position;
+            io::copy(&mut self.by_ref().take(length), &mut io::sink())?;
+            if self.position < new_position {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("the stream ends before position {}", new_position)));
+            }
+        }
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Read exactly length bytes from the current position
+    fn read_bytes<R: Read>(reader: &mut R, length: usize) -> Vec<u8> {
+        let mut buffer = vec![0; length];
+        reader.read_exact(&mut buffer).unwrap();
+        buffer
+    }
+
+    #[test]
+    fn reads_again_the_bytes_kept_behind_the_cursor() {
+        let data: Vec<u8> = (0..100).collect();
+        let mut reader = LookBackReader::with_look_back_size(&data[..], 8);
+        assert_eq!(read_bytes(&mut reader, 10), data[..10]);
+        assert_eq!(reader.seek(SeekFrom::Current(-4)).unwrap(), 6);
+        assert_eq!(read_bytes(&mut reader, 6), data[6..12]);
+        assert_eq!(reader.seek(SeekFrom::Start(2)).unwrap(), 2);
+        assert_eq!(read_bytes(&mut reader, 3), data[2..5]);
+    }
+
+    #[test]
+    fn drops_the_bytes_too_far_behind_the_cursor() {
+        let data: Vec<u8> = (0..100).collect();
+        let mut reader = LookBackReader::with_look_back_size(&data[..], 4);
+        assert_eq!(read_bytes(&mut reader, 20), data[..20]);
+        // The bytes are dropped by batches, at least the last 4 are kept
+        let e = reader.seek(SeekFrom::Start(15)).err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::Unsupported);
+        assert_eq!(reader.seek(SeekFrom::Start(16)).unwrap(), 16);
+        assert_eq!(read_bytes(&mut reader, 4), data[16..20]);
+    }
+
+    #[test]
+    fn skips_the_bytes_when_moving_forward() {
+        let data: Vec<u8> = (0..100).collect();
+        let mut reader = LookBackReader::with_look_back_size(&data[..], 8);
+        assert_eq!(reader.seek(SeekFrom::Current(50)).unwrap(), 50);
+        assert_eq!(read_bytes(&mut reader, 2), data[50..52]);
+        assert_eq!(reader.seek(SeekFrom::Start(100)).unwrap(), 100);
+        assert_eq!(reader.seek(SeekFrom::Start(101)).err().unwrap().kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(reader.seek(SeekFrom::End(0)).err().unwrap().kind(), io::ErrorKind::Unsupported);
+    }
+}
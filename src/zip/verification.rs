@@ -0,0 +1,133 @@
+//! This module verifies the CRC-32 stored in the records of a file against the
+//! CRC-32 of its content.
+
+use std::fmt;
+use super::constants;
+use super::model::{CentralDirectory, StoredFile};
+
+/// A record storing the CRC-32 of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrcSource {
+    /// The local file header, before the data of the file
+    LocalFileHeader,
+    /// The data descriptor, after the data of the file
+    DataDescriptor,
+    /// The central directory file header of the file
+    CentralDirectory,
+}
+
+impl fmt::Display for CrcSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            CrcSource::LocalFileHeader => "header",
+            CrcSource::DataDescriptor => "descriptor",
+            CrcSource::CentralDirectory => "central directory",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A CRC-32 stored in a record which doesn't match the content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrcMismatch {
+    /// The record storing the CRC-32
+    pub source: CrcSource,
+    /// The CRC-32 stored in the record
+    pub stored: u32,
+}
+
+/// The result of the CRC-32 verification of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrcVerification {
+    /// The CRC-32 of the content
+    pub computed: u32,
+    /// The records checked against the content
+    pub checked: Vec<CrcSource>,
+    /// The records whose CRC-32 doesn't match the content
+    pub mismatches: Vec<CrcMismatch>,
+}
+
+impl CrcVerification {
+    /// Whether all the records checked match the content
+    pub fn is_valid(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Verify the CRC-32 stored in the local file header, the data descriptor and
+/// the central directory file header of a file against the CRC-32 computed
+/// from its decompressed content.
+/// The local file header isn't checked when its CRC-32 is masked, or set to 0
+/// because it's in the data descriptor.
+/// Returns None when the file doesn't store a CRC-32: WinZip AES files of
+/// version AE-2 rely on their authentication code instead.
+pub fn verify_crc32(stored_file: &StoredFile, central_directory: Option<&CentralDirectory>, computed: u32) -> Option<CrcVerification> {
+    let local_file_header = &stored_file.local_file_header;
+    if local_file_header.extra_field.aes().is_some_and(|aes| aes.vendor_version == 2) {
+        return None;
+    }
+
+    let mut stored_values = Vec::new();
+    let deferred_to_data_descriptor = local_file_header.general_purpose_flag & constants::GENERAL_PURPOSE_FLAG_DATA_DESCRIPTOR != 0
+        && local_file_header.crc32 == 0;
+    if !local_file_header.is_masked() && !deferred_to_data_descriptor {
+        stored_values.push((CrcSource::LocalFileHeader, local_file_header.crc32));
+    }
+    if let Some(data_descriptor) = &stored_file.data_descriptor {
+        stored_values.push((CrcSource::DataDescriptor, data_descriptor.crc32));
+    }
+    if let Some(header) = central_directory.and_then(|central_directory| stored_file.central_directory_file_header(central_directory)) {
+        stored_values.push((CrcSource::CentralDirectory, header.crc32));
+    }
+
+    Some(CrcVerification {
+        computed,
+        checked: stored_values.iter().map(|(source, _)| *source).collect(),
+        mismatches: stored_values.into_iter()
+            .filter(|(_, stored)| *stored != computed)
+            .map(|(source, stored)| CrcMismatch { source, stored })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zip::decompression::summarize_stored_file;
+    use crate::zip::read_fixture;
+    use crate::zip::reader::ParsingStrategy;
+    use std::io::Cursor;
+
+    #[test]
+    fn reports_the_records_whose_crc_is_wrong() {
+        let data = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/crc.zip")).unwrap();
+        let zip_file = read_fixture("crc.zip", ParsingStrategy::Sequential);
+        let central_directory = zip_file.central_directory.as_ref();
+        let verifications: Vec<_> = zip_file.stored_files.iter()
+            .map(|stored_file| {
+                let summary = summarize_stored_file(&mut Cursor::new(&data), stored_file, None).unwrap();
+                verify_crc32(stored_file, central_directory, summary.crc32).unwrap()
+            })
+            .collect();
+        let computed = crate::checksum::crc32(b"checked\n");
+        assert!(verifications.iter().all(|verification| verification.computed == computed));
+
+        assert!(verifications[0].is_valid());
+        assert_eq!(verifications[0].checked, [CrcSource::LocalFileHeader, CrcSource::CentralDirectory]);
+        assert_eq!(verifications[1].mismatches, [CrcMismatch { source: CrcSource::LocalFileHeader, stored: computed ^ 1 }]);
+        assert_eq!(verifications[2].mismatches, [CrcMismatch { source: CrcSource::CentralDirectory, stored: computed ^ 1 }]);
+        // The CRC-32 of the local file header is deferred to the data descriptor
+        assert_eq!(verifications[3].checked, [CrcSource::DataDescriptor, CrcSource::CentralDirectory]);
+        assert_eq!(verifications[3].mismatches, [CrcMismatch { source: CrcSource::DataDescriptor, stored: computed ^ 1 }]);
+    }
+
+    #[test]
+    fn skips_winzip_aes_files_without_crc() {
+        let zip_file = read_fixture("winzip_aes.zip", ParsingStrategy::Sequential);
+        let central_directory = zip_file.central_directory.as_ref();
+        let [ae1, ae2] = &zip_file.stored_files[..] else { panic!("expected 2 files") };
+        assert!(verify_crc32(ae1, central_directory, 0).is_some());
+        // AE-2 files rely on their authentication code
+        assert!(verify_crc32(ae2, central_directory, 0).is_none());
+    }
+}